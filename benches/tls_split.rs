@@ -0,0 +1,62 @@
+//! Full-duplex throughput of a split stream: a body is streamed out through the write half
+//! while the echoed bytes are concurrently read back through the read half.
+//!
+//! Run with `cargo bench --features tls --bench tls_split`.
+use gateway::http::stream::tls;
+use std::time::{Duration, Instant};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+const TOTAL: usize = 256 * 1024 * 1024;
+const ROUNDS: usize = 5;
+
+async fn full_duplex<R, W>(mut rx: R, mut tx: W) -> Duration
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let start = Instant::now();
+    let writer = tokio::spawn(async move {
+        let chunk = vec![0xa5_u8; BUFFER_SIZE];
+        for _ in 0..TOTAL / BUFFER_SIZE {
+            tx.write_all(&chunk).await.unwrap();
+        }
+        tx.flush().await.unwrap();
+    });
+    let mut buf = vec![0_u8; BUFFER_SIZE];
+    let mut received = 0;
+    while received < TOTAL {
+        received += rx.read(&mut buf).await.unwrap();
+    }
+    writer.await.unwrap();
+    start.elapsed()
+}
+
+fn echo() -> io::DuplexStream {
+    let (local, remote) = io::duplex(BUFFER_SIZE);
+    tokio::spawn(async move {
+        let (mut rx, mut tx) = io::split(remote);
+        io::copy(&mut rx, &mut tx).await
+    });
+    local
+}
+
+fn report(name: &str, elapsed: Vec<Duration>) {
+    let best = elapsed.into_iter().min().unwrap_or_default();
+    let throughput = TOTAL as f64 / (1024.0 * 1024.0) / best.as_secs_f64();
+    println!("{:<16} {:>10.1?} {:>10.1} MiB/s", name, best, throughput);
+}
+
+#[tokio::main]
+async fn main() {
+    let mut split = Vec::with_capacity(ROUNDS);
+    let mut baseline = Vec::with_capacity(ROUNDS);
+    for _ in 0..ROUNDS {
+        let (rx, tx) = tls::split(echo());
+        split.push(full_duplex(rx, tx).await);
+        let (rx, tx) = io::split(echo());
+        baseline.push(full_duplex(rx, tx).await);
+    }
+    report("tls::split", split);
+    report("tokio::io::split", baseline);
+}
@@ -2,5 +2,7 @@ pub mod basic;
 mod claims;
 pub mod endpoint;
 pub mod jwt;
+#[cfg(feature = "tls")]
+pub mod mtls;
 
 pub use claims::ClaimParser;
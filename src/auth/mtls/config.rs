@@ -0,0 +1,52 @@
+use crate::ClientCertificate;
+
+/// An identity the client certificate has to match.
+#[derive(Debug, Clone)]
+pub enum Identity {
+    /// Distinguished name of the subject, e.g. `CN=client, O=Example`.
+    Subject(String),
+    /// Common name of the subject.
+    CommonName(String),
+    /// One of the subject alternative names.
+    San(String),
+    /// Hex encoded SHA-256 fingerprint of the certificate.
+    Fingerprint(String),
+}
+
+impl Identity {
+    pub fn matches(&self, certificate: &ClientCertificate) -> bool {
+        match self {
+            Identity::Subject(subject) => certificate.subject == *subject,
+            Identity::CommonName(name) => certificate.common_name.as_ref() == Some(name),
+            Identity::San(name) => certificate.san.iter().any(|san| san == name),
+            Identity::Fingerprint(fingerprint) => {
+                certificate.fingerprint.eq_ignore_ascii_case(fingerprint)
+            }
+        }
+    }
+}
+
+/// Identities allowed to access the app.
+/// When empty, any verified client certificate is accepted.
+#[derive(Debug)]
+pub struct App {
+    pub identities: Vec<Identity>,
+}
+
+impl App {
+    pub fn new(identities: Vec<Identity>) -> Self {
+        Self { identities }
+    }
+}
+
+/// Identities allowed to access the endpoint, in addition to the app restrictions.
+#[derive(Debug)]
+pub struct Endpoint {
+    pub identities: Vec<Identity>,
+}
+
+impl Endpoint {
+    pub fn new(identities: Vec<Identity>) -> Self {
+        Self { identities }
+    }
+}
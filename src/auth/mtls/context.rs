@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+
+use crate::{ClientCertificate, ConfigToContext, Result};
+
+use super::config::{self, Identity};
+
+#[derive(Debug)]
+pub struct App {
+    pub identities: Box<[Identity]>,
+}
+
+impl App {
+    pub fn authorize(&self, certificate: &ClientCertificate) -> bool {
+        authorize(&self.identities, certificate)
+    }
+}
+
+#[derive(Debug)]
+pub struct Endpoint {
+    pub identities: Box<[Identity]>,
+}
+
+impl Endpoint {
+    pub fn authorize(&self, certificate: &ClientCertificate) -> bool {
+        authorize(&self.identities, certificate)
+    }
+}
+
+fn authorize(identities: &[Identity], certificate: &ClientCertificate) -> bool {
+    identities.is_empty()
+        || identities
+            .iter()
+            .any(|identity| identity.matches(certificate))
+}
+
+#[async_trait]
+impl ConfigToContext for config::App {
+    type Context = App;
+
+    async fn into_context(self) -> Result<Self::Context> {
+        Ok(App {
+            identities: self.identities.into_boxed_slice(),
+        })
+    }
+}
+
+#[async_trait]
+impl ConfigToContext for config::Endpoint {
+    type Context = Endpoint;
+
+    async fn into_context(self) -> Result<Self::Context> {
+        Ok(Endpoint {
+            identities: self.identities.into_boxed_slice(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate() -> ClientCertificate {
+        ClientCertificate {
+            subject: "CN=client, O=Example".to_string(),
+            common_name: Some("client".to_string()),
+            san: vec!["client.example.com".to_string()],
+            fingerprint: "ab01".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let certificate = certificate();
+        let app = |identities: Vec<Identity>| App {
+            identities: identities.into_boxed_slice(),
+        };
+        assert!(app(vec![]).authorize(&certificate));
+        assert!(app(vec![Identity::CommonName("client".to_string())]).authorize(&certificate));
+        assert!(app(vec![Identity::San("client.example.com".to_string())]).authorize(&certificate));
+        assert!(app(vec![Identity::Fingerprint("AB01".to_string())]).authorize(&certificate));
+        assert!(!app(vec![
+            Identity::Subject("CN=other".to_string()),
+            Identity::San("other.example.com".to_string())
+        ])
+        .authorize(&certificate));
+    }
+}
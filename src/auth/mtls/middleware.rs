@@ -0,0 +1,73 @@
+use crate::{
+    gateway::{middleware::Middleware as TMiddleware, next::Next, Result},
+    http::{headers, HeaderMapExt, Request, Response},
+    Ctx,
+};
+use async_trait::async_trait;
+use http::{header, StatusCode};
+
+#[derive(Debug)]
+pub struct Middleware(super::Context);
+
+impl Middleware {
+    pub(crate) fn new(ctx: super::Context) -> Self {
+        Self(ctx)
+    }
+
+    /// Whether the request is sent to the host the TLS connection was established for.
+    /// The client certificate is verified by the certificate authorities of the SNI host name,
+    /// so a request for another host could get past the certificate authorities of its app.
+    fn is_directed(ctx: &Ctx, request: &Request) -> bool {
+        let server_name = match ctx.peer.server_name.as_deref() {
+            Some(server_name) => server_name,
+            None => {
+                return false;
+            }
+        };
+        request
+            .header(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<http::uri::Authority>().ok())
+            .is_some_and(|authority| authority.host().eq_ignore_ascii_case(server_name))
+    }
+}
+
+#[async_trait]
+impl TMiddleware for Middleware {
+    async fn run(&self, ctx: &Ctx, mut request: Request, next: Next<'_>) -> Result<Response> {
+        let app_ctx = match self.0.get(ctx.app_id) {
+            Some(config) => config,
+            None => {
+                return next.run(request).await;
+            }
+        };
+        let certificate = match ctx.peer.client_certificate.as_ref() {
+            Some(certificate) => certificate,
+            None => {
+                return Ok(Response::new(StatusCode::UNAUTHORIZED));
+            }
+        };
+        if !Self::is_directed(ctx, &request) {
+            return Ok(Response::new(StatusCode::MISDIRECTED_REQUEST));
+        }
+        if !app_ctx.global().authorize(certificate)
+            || app_ctx
+                .get(ctx.endpoint_id)
+                .is_some_and(|endpoint| !endpoint.authorize(certificate))
+        {
+            return Ok(Response::new(StatusCode::FORBIDDEN));
+        }
+        request.remove_header(&headers::CLIENT_SUBJECT);
+        request.remove_header(&headers::CLIENT_SAN);
+        request.remove_header(&headers::CLIENT_FINGERPRINT);
+        request.insert_header(&headers::CLIENT_SUBJECT, certificate.subject.as_str());
+        if !certificate.san.is_empty() {
+            request.insert_header(&headers::CLIENT_SAN, certificate.san.join(","));
+        }
+        request.insert_header(
+            &headers::CLIENT_FINGERPRINT,
+            certificate.fingerprint.as_str(),
+        );
+        next.run(request).await
+    }
+}
@@ -0,0 +1,103 @@
+mod builder;
+pub mod config;
+mod context;
+mod middleware;
+
+use std::collections::HashMap;
+
+pub(crate) use middleware::Middleware;
+
+use crate::{MiddlewareConfig, MiddlewareCtx};
+use builder::MiddlewareBuilder;
+
+type Config = MiddlewareConfig<config::App, config::Endpoint>;
+type Context = MiddlewareCtx<context::App, context::Endpoint>;
+
+#[derive(Debug, Default)]
+pub struct Builder(HashMap<String, (config::App, EndpointBuilder)>);
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require a verified client certificate for all requests to the app.
+    /// Requests whose Host does not match the SNI host name of the connection are rejected
+    /// with 421 Misdirected Request.
+    pub fn require_app_certificate(mut self, app: &str) -> Self {
+        self.0
+            .entry(app.to_string())
+            .or_insert_with(|| (config::App::new(vec![]), EndpointBuilder::new()));
+        self
+    }
+
+    /// Allow the identity to access the app.
+    /// Once an identity is added, certificates matching none of the app identities are rejected.
+    pub fn add_app_identity(mut self, app: &str, identity: config::Identity) -> Self {
+        match self.0.get_mut(app) {
+            Some((config, _)) => {
+                config.identities.push(identity);
+            }
+            None => {
+                self.0.insert(
+                    app.to_string(),
+                    (config::App::new(vec![identity]), EndpointBuilder::new()),
+                );
+            }
+        };
+        self
+    }
+
+    pub fn set_app_endpoints(mut self, app: &str, endpoints: EndpointBuilder) -> Self {
+        match self.0.get_mut(app) {
+            Some((_, config)) => {
+                *config = endpoints;
+            }
+            None => {
+                self.0
+                    .insert(app.to_string(), (config::App::new(vec![]), endpoints));
+            }
+        };
+        self
+    }
+
+    pub fn build(self) -> MiddlewareBuilder {
+        let config: Config = self
+            .0
+            .into_iter()
+            .map(|(app_name, (app, endpoints))| (app_name, (app, endpoints.0).into()))
+            .collect::<HashMap<_, _>>()
+            .into();
+        MiddlewareBuilder::new(config)
+    }
+}
+
+impl<EB: Into<EndpointBuilder>> From<HashMap<String, (config::App, EB)>> for Builder {
+    fn from(auth: HashMap<String, (config::App, EB)>) -> Self {
+        Self(
+            auth.into_iter()
+                .map(|(app, (root, endpoints))| (app, (root, endpoints.into())))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EndpointBuilder(HashMap<String, config::Endpoint>);
+
+impl EndpointBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_endpoint(mut self, endpoint: &str, config: config::Endpoint) -> Self {
+        self.0.insert(endpoint.to_string(), config);
+        self
+    }
+}
+
+impl From<HashMap<String, config::Endpoint>> for EndpointBuilder {
+    fn from(endpoints: HashMap<String, config::Endpoint>) -> Self {
+        Self(endpoints)
+    }
+}
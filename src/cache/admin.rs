@@ -0,0 +1,113 @@
+//! Admin API purging cached responses, served on a separate port.
+//!
+//! All requests need the `Authorization: Bearer <token>` header and the `DELETE` method:
+//! - `/keys/<key>` purges the entry stored under the cache key,
+//! - `/apps/<app>` purges all entries of the app,
+//! - `/apps/<app>/endpoints/<endpoint>` purges all entries of the endpoint,
+//! - `/tags/<tag>` purges all entries with the surrogate key sent by the origin.
+//!
+//! The response body is `{"purged":<count>}`.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use essentials::{error, warn};
+use http::{header, Method, StatusCode};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use super::{datastore, Datastore};
+use crate::http::{server::Handler, HeaderMapExt, ReadRequest, Request};
+
+pub struct Admin {
+    datastore: Box<dyn Datastore + Send + Sync + 'static>,
+    token: String,
+}
+
+impl Admin {
+    /// The datastore should be a handle to the one used by the middleware.
+    pub fn new(
+        datastore: impl Datastore + Send + Sync + 'static,
+        token: impl Into<String>,
+    ) -> Self {
+        Self {
+            datastore: Box::new(datastore),
+            token: token.into(),
+        }
+    }
+
+    fn is_authorized(&self, request: &Request) -> bool {
+        request
+            .header(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), self.token.as_bytes()))
+    }
+
+    async fn purge(&self, request: &Request) -> Result<(StatusCode, Option<usize>)> {
+        if !self.is_authorized(request) {
+            return Ok((StatusCode::UNAUTHORIZED, None));
+        }
+        if request.method != Method::DELETE {
+            return Ok((StatusCode::METHOD_NOT_ALLOWED, None));
+        }
+        let path = request.path.split(['?', '#']).next().unwrap_or_default();
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        let purged = match segments.as_slice() {
+            ["keys", key] => usize::from(self.datastore.purge(key).await?),
+            ["apps", app] => self.datastore.purge_tag(&datastore::app_tag(app)).await?,
+            ["apps", app, "endpoints", endpoint] => {
+                self.datastore
+                    .purge_tag(&datastore::endpoint_tag(app, endpoint))
+                    .await?
+            }
+            ["tags", tag] => {
+                self.datastore
+                    .purge_tag(&datastore::surrogate_tag(tag))
+                    .await?
+            }
+            _ => {
+                return Ok((StatusCode::NOT_FOUND, None));
+            }
+        };
+        Ok((StatusCode::OK, Some(purged)))
+    }
+}
+
+#[async_trait]
+impl Handler for Admin {
+    async fn handle(&self, mut stream: TcpStream) {
+        let request = match BufReader::new(&mut stream).read_request().await {
+            Ok(request) => request,
+            Err(err) => {
+                warn!("Failed to read cache admin request: {}", err);
+                return;
+            }
+        };
+        let (status, purged) = match self.purge(&request).await {
+            Ok(response) => response,
+            Err(err) => {
+                error!("Failed to purge cache: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
+            }
+        };
+        let body = purged
+            .map(|purged| format!("{{\"purged\":{}}}", purged))
+            .unwrap_or_default();
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default(),
+            body.len(),
+            body
+        );
+        if let Err(err) = stream.write_all(response.as_bytes()).await {
+            warn!("Failed to write cache admin response: {}", err);
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
@@ -1,9 +1,26 @@
+use super::datastore;
 use crate::time::Time;
 
+/// Responses larger than this are not cached unless configured otherwise.
+pub const DEFAULT_MAX_SIZE: usize = 8 * 1024 * 1024;
+
 #[derive(Debug)]
 pub struct Endpoint {
     pub expires_in: Time,
     pub vary_headers: Vec<String>,
+    pub key: Key,
+    /// How long after expiry a stale response is served while it is refreshed in the background.
+    pub stale_while_revalidate: Option<Time>,
+    /// How long after expiry a stale response is served when the origin fails or returns a 5xx status.
+    pub stale_if_error: Option<Time>,
+    /// Concurrent misses wait for the first request to fetch the response, at most for this long.
+    pub coalescing_timeout: Option<Time>,
+    /// Whether `PURGE` requests routed to the endpoint remove the entry of the request.
+    pub purge_method: bool,
+    /// Largest body in bytes that is cached, larger responses are streamed through uncached.
+    pub max_size: usize,
+    /// Tags of the app and the endpoint, set when the middleware is built.
+    pub(crate) tags: Vec<String>,
 }
 
 impl Endpoint {
@@ -11,6 +28,128 @@ impl Endpoint {
         Self {
             expires_in,
             vary_headers,
+            key: Key::default(),
+            stale_while_revalidate: None,
+            stale_if_error: None,
+            coalescing_timeout: None,
+            purge_method: false,
+            max_size: DEFAULT_MAX_SIZE,
+            tags: Vec::new(),
         }
     }
+
+    pub fn with_key(mut self, key: Key) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn with_stale_while_revalidate(mut self, window: Time) -> Self {
+        self.stale_while_revalidate = Some(window);
+        self
+    }
+
+    pub fn with_stale_if_error(mut self, window: Time) -> Self {
+        self.stale_if_error = Some(window);
+        self
+    }
+
+    pub fn with_coalescing(mut self, timeout: Time) -> Self {
+        self.coalescing_timeout = Some(timeout);
+        self
+    }
+
+    /// Handle `PURGE` requests, which should be restricted, e.g. by the auth middleware.
+    pub fn with_purge_method(mut self) -> Self {
+        self.purge_method = true;
+        self
+    }
+
+    /// Do not cache responses with a body larger than the given number of bytes.
+    /// The default is 8 MiB
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub(crate) fn with_names(mut self, app: &str, endpoint: &str) -> Self {
+        self.tags = vec![
+            datastore::app_tag(app),
+            datastore::endpoint_tag(app, endpoint),
+        ];
+        self
+    }
+}
+
+/// Query parameters included in the cache key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// The query is ignored.
+    Ignore,
+    /// All parameters, sorted so that their order does not matter.
+    All,
+    /// Only the listed parameters, sorted.
+    Only(Vec<String>),
+}
+
+/// Template of the cache key of an endpoint.
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub method: bool,
+    pub path: bool,
+    pub query: Query,
+    pub headers: Vec<String>,
+    /// Route pattern, e.g. `/users/:id`, and the names of its parameters to include.
+    pub route_params: Option<(String, Vec<String>)>,
+    /// Whether every client gets its own entry, keyed by its IP address.
+    pub per_client: bool,
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Self {
+            method: true,
+            path: true,
+            query: Query::All,
+            headers: Vec::new(),
+            route_params: None,
+            per_client: true,
+        }
+    }
+}
+
+impl Key {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn without_method(mut self) -> Self {
+        self.method = false;
+        self
+    }
+
+    pub fn without_path(mut self) -> Self {
+        self.path = false;
+        self
+    }
+
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = query;
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_route_params(mut self, route: &str, params: Vec<String>) -> Self {
+        self.route_params = Some((route.to_string(), params));
+        self
+    }
+
+    /// Share the entries across all clients, e.g. for public content.
+    pub fn shared(mut self) -> Self {
+        self.per_client = false;
+        self
+    }
 }
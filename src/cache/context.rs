@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 
-use crate::{time::Time, ConfigToContext, Result};
+use crate::{
+    time::{Time, TimeUnit},
+    ConfigToContext, Result,
+};
 
 use super::config;
 
@@ -8,25 +11,50 @@ use super::config;
 pub struct Endpoint {
     pub expires_in: Time,
     pub vary_headers: Box<[String]>,
+    pub key: config::Key,
+    /// Stale-while-revalidate window in seconds.
+    pub stale_while_revalidate: usize,
+    /// Stale-if-error window in seconds.
+    pub stale_if_error: usize,
+    /// How long concurrent misses wait for the first request in seconds, if they are coalesced.
+    pub coalescing_timeout: Option<usize>,
+    pub purge_method: bool,
+    /// Largest body in bytes that is cached.
+    pub max_size: usize,
+    /// Tags of the app and the endpoint.
+    pub tags: Box<[String]>,
 }
 
 impl Endpoint {
-    fn new(expires_in: Time, vary_headers: Box<[String]>) -> Self {
-        Self {
-            expires_in,
-            vary_headers,
-        }
+    /// How long entries are kept after expiry to be served stale.
+    pub fn grace_period(&self) -> usize {
+        self.stale_while_revalidate.max(self.stale_if_error)
     }
 }
 
+fn seconds(window: Option<Time>) -> usize {
+    window
+        .map(|window| window.convert(TimeUnit::Seconds).amount)
+        .unwrap_or_default()
+}
+
 #[async_trait]
 impl ConfigToContext for config::Endpoint {
     type Context = Endpoint;
 
     async fn into_context(self) -> Result<Self::Context> {
-        Ok(Endpoint::new(
-            self.expires_in,
-            self.vary_headers.into_boxed_slice(),
-        ))
+        Ok(Endpoint {
+            expires_in: self.expires_in,
+            vary_headers: self.vary_headers.into_boxed_slice(),
+            key: self.key,
+            stale_while_revalidate: seconds(self.stale_while_revalidate),
+            stale_if_error: seconds(self.stale_if_error),
+            coalescing_timeout: self
+                .coalescing_timeout
+                .map(|timeout| timeout.convert(TimeUnit::Seconds).amount),
+            purge_method: self.purge_method,
+            max_size: self.max_size,
+            tags: self.tags.into_boxed_slice(),
+        })
     }
 }
@@ -2,39 +2,282 @@ use super::{Cache, Datastore, Response};
 use anyhow::Result;
 use async_trait::async_trait;
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::BuildHasher,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Once,
+    },
+    time::Duration,
 };
 
-#[derive(Debug, Default)]
+/// Default limit of the size of the stored entries, 64 MiB.
+const DEFAULT_MAX_SIZE: usize = 64 * 1024 * 1024;
+/// Default number of shards, 16 MiB each by default so that responses of the default
+/// cacheable size of the cache endpoints, 8 MiB, are stored.
+const DEFAULT_SHARDS: usize = 4;
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+/// Estimated size of an entry apart from its key, response, etag and tags.
+const ENTRY_OVERHEAD: usize = 128;
+
+/// Datastore keeping the responses in memory, limited by their size.
+///
+/// The entries are split into shards locked independently, each shard evicts its least
+/// recently used entries once it is full. Entries expired past their grace period are
+/// removed by a background task started with the first saved entry.
+#[derive(Debug, Clone)]
 pub struct InMemoryDatastore {
-    data: Arc<Mutex<HashMap<String, InMemoryValue>>>,
+    shards: Arc<[Mutex<Shard>]>,
+    hasher: RandomState,
+    max_size: usize,
+    sweep_interval: Duration,
+    sweeper: Arc<Once>,
+    counters: Arc<Counters>,
 }
 
-unsafe impl Send for InMemoryDatastore {}
+/// Statistics of an [`InMemoryDatastore`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Fetches of fresh entries.
+    pub hits: u64,
+    /// Fetches of missing, stale or expired entries.
+    pub misses: u64,
+    /// Entries removed to make space for new ones.
+    pub evictions: u64,
+    /// Entries removed by the background task after they expired.
+    pub expirations: u64,
+    /// Entries not stored because they are larger than a shard.
+    pub rejections: u64,
+    pub entries: usize,
+    /// Estimated size of the stored entries in bytes.
+    pub size: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+    rejections: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    entries: HashMap<String, InMemoryValue>,
+    /// Keys by the tick they were last used at, the first one is the least recently used.
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    size: usize,
+}
 
 #[derive(Debug)]
 struct InMemoryValue {
-    value: String,
-    headers: String,
+    cache: Cache,
     etag: Option<String>,
     expiry: usize,
+    grace_period: usize,
+    size: usize,
+    last_used: u64,
+}
+
+impl Default for InMemoryDatastore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SIZE)
+    }
 }
 
-impl From<&InMemoryValue> for Cache {
-    fn from(cache: &InMemoryValue) -> Self {
+impl InMemoryDatastore {
+    pub fn new(max_size: usize) -> Self {
         Self {
-            response: cache.value.clone(),
-            headers: cache.headers.clone(),
+            shards: (0..DEFAULT_SHARDS)
+                .map(|_| Mutex::new(Shard::default()))
+                .collect(),
+            hasher: RandomState::new(),
+            max_size,
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+            sweeper: Arc::new(Once::new()),
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Split the entries into the given number of shards, the size limit is split evenly between them.
+    /// Entries larger than a shard are not stored, the default is 4.
+    pub fn with_shards(mut self, shards: usize) -> Self {
+        self.shards = (0..shards.max(1))
+            .map(|_| Mutex::new(Shard::default()))
+            .collect();
+        self
+    }
+
+    /// How often expired entries are removed.
+    pub fn with_sweep_interval(mut self, interval: Duration) -> Self {
+        self.sweep_interval = interval;
+        self
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            expirations: self.counters.expirations.load(Ordering::Relaxed),
+            rejections: self.counters.rejections.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        for shard in self.shards.iter() {
+            if let Ok(shard) = shard.lock() {
+                stats.entries += shard.entries.len();
+                stats.size += shard.size;
+            }
+        }
+        stats
+    }
+
+    /// Remove all entries, returns how many there were.
+    pub(super) fn clear(&self) -> usize {
+        self.shards
+            .iter()
+            .filter_map(|shard| shard.lock().ok())
+            .map(|mut shard| shard.retain(|_| false))
+            .sum()
+    }
+
+    fn shard(&self, key: &str) -> Result<std::sync::MutexGuard<'_, Shard>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index]
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to lock data for key: {}", key))
+    }
+
+    fn start_sweeper(&self) {
+        self.sweeper.call_once(|| {
+            let shards = Arc::downgrade(&self.shards);
+            let counters = self.counters.clone();
+            let mut interval = tokio::time::interval(self.sweep_interval);
+            tokio::spawn(async move {
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let shards = match shards.upgrade() {
+                        Some(shards) => shards,
+                        None => break,
+                    };
+                    let expired = sweep(&shards, chrono::Utc::now().timestamp() as usize);
+                    counters
+                        .expirations
+                        .fetch_add(expired as u64, Ordering::Relaxed);
+                }
+            });
+        });
+    }
+}
+
+/// Remove the entries expired past their grace period, returns how many there were.
+fn sweep(shards: &[Mutex<Shard>], now: usize) -> usize {
+    shards
+        .iter()
+        .filter_map(|shard| shard.lock().ok())
+        .map(|mut shard| shard.retain(|value| now < value.expiry + value.grace_period))
+        .sum()
+}
+
+impl Shard {
+    /// Insert the entry, evicting the least recently used ones until it fits.
+    /// Returns how many entries were evicted, `None` when the entry is larger than the shard.
+    fn insert(&mut self, key: String, mut value: InMemoryValue, max_size: usize) -> Option<u64> {
+        self.remove(&key);
+        if value.size > max_size {
+            return None;
+        }
+        let mut evicted = 0;
+        while self.size + value.size > max_size {
+            let key = match self.recency.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+            if let Some(evicted_value) = self.entries.remove(&key) {
+                self.size -= evicted_value.size;
+                evicted += 1;
+            }
         }
+        self.tick += 1;
+        value.last_used = self.tick;
+        self.size += value.size;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, value);
+        Some(evicted)
+    }
+
+    /// Mark the entry as the most recently used one.
+    fn touch(&mut self, key: &str) {
+        if let Some(value) = self.entries.get_mut(key) {
+            self.recency.remove(&value.last_used);
+            self.tick += 1;
+            value.last_used = self.tick;
+            self.recency.insert(self.tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<InMemoryValue> {
+        let value = self.entries.remove(key)?;
+        self.recency.remove(&value.last_used);
+        self.size -= value.size;
+        Some(value)
+    }
+
+    /// Keep only the entries matching the predicate, returns how many were removed.
+    fn retain(&mut self, keep: impl Fn(&InMemoryValue) -> bool) -> usize {
+        let removed = self
+            .entries
+            .iter()
+            .filter(|(_, value)| !keep(value))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in removed.iter() {
+            self.remove(key);
+        }
+        removed.len()
     }
 }
 
-impl From<&mut InMemoryValue> for Cache {
-    fn from(cache: &mut InMemoryValue) -> Self {
+impl InMemoryValue {
+    fn new(
+        key: &str,
+        cache: Cache,
+        etag: Option<String>,
+        expiry: usize,
+        grace_period: usize,
+    ) -> Self {
+        let size = ENTRY_OVERHEAD
+            + key.len()
+            + cache.body.len()
+            + cache
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum::<usize>()
+            + cache.tags.iter().map(String::len).sum::<usize>()
+            + etag.as_ref().map_or(0, String::len);
         Self {
-            response: cache.value.clone(),
-            headers: cache.headers.clone(),
+            cache,
+            etag,
+            expiry,
+            grace_period,
+            size,
+            last_used: 0,
+        }
+    }
+
+    fn to_response(&self, now: usize) -> Response {
+        let expires_in = self.expiry.saturating_sub(now);
+        let stale_for = now.saturating_sub(self.expiry);
+        if expires_in > 0 {
+            Response::Hit(self.cache.clone(), expires_in)
+        } else if stale_for < self.grace_period {
+            Response::Stale(self.cache.clone(), stale_for, self.etag.clone())
+        } else {
+            Response::Expired(self.etag.clone())
         }
     }
 }
@@ -42,65 +285,238 @@ impl From<&mut InMemoryValue> for Cache {
 #[async_trait]
 impl Datastore for InMemoryDatastore {
     async fn fetch_cache(&self, key: &str) -> Result<Response> {
-        let data = self
-            .data
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to lock data for key: {}", key))?;
+        let mut shard = self.shard(key)?;
         let now = chrono::Utc::now().timestamp() as usize;
-        let response = if let Some(cache) = data.get(key) {
-            let expires_in = cache.expiry.saturating_sub(now);
-            if expires_in > 0 {
-                Response::Hit(cache.into(), expires_in)
-            } else {
-                Response::Expired(cache.etag.clone())
+        let response = shard
+            .entries
+            .get(key)
+            .map_or(Response::Miss, |value| value.to_response(now));
+        match response {
+            Response::Hit(..) => {
+                shard.touch(key);
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
             }
-        } else {
-            Response::Miss
-        };
+            Response::Stale(..) | Response::Expired(_) => {
+                shard.touch(key);
+                self.counters.misses.fetch_add(1, Ordering::Relaxed);
+            }
+            Response::Miss => {
+                self.counters.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
         Ok(response)
     }
 
     async fn save_cache(
         &self,
         key: &str,
-        value: String,
-        headers: String,
+        cache: Cache,
         etag: Option<String>,
         expires_in: usize,
+        grace_period: usize,
     ) -> Result<()> {
-        let mut data = self
-            .data
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to lock data for key: {}", key))?;
-        data.insert(
-            key.to_string(),
-            InMemoryValue {
-                value,
-                headers,
-                expiry: chrono::Utc::now().timestamp() as usize + expires_in,
-                etag,
-            },
-        );
+        self.start_sweeper();
+        let expiry = chrono::Utc::now().timestamp() as usize + expires_in;
+        let value = InMemoryValue::new(key, cache, etag, expiry, grace_period);
+        let evicted =
+            self.shard(key)?
+                .insert(key.to_string(), value, self.max_size / self.shards.len());
+        match evicted {
+            Some(evicted) => self
+                .counters
+                .evictions
+                .fetch_add(evicted, Ordering::Relaxed),
+            None => self.counters.rejections.fetch_add(1, Ordering::Relaxed),
+        };
         Ok(())
     }
 
-    async fn refresh_cache(&self, key: &str, expires_at: usize) -> Result<Response> {
-        let mut data = self
-            .data
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to lock data for key: {}", key))?;
+    async fn refresh_cache(
+        &self,
+        key: &str,
+        expires_at: usize,
+        grace_period: usize,
+    ) -> Result<Response> {
+        let mut shard = self.shard(key)?;
         let now = chrono::Utc::now().timestamp() as usize;
-        let response = if let Some(cache) = data.get_mut(key) {
-            cache.expiry = expires_at;
-            let expires_in = cache.expiry.saturating_sub(now);
-            if expires_in > 0 {
-                Response::Hit(cache.into(), expires_in)
-            } else {
-                Response::Expired(cache.etag.clone())
+        let response = match shard.entries.get_mut(key) {
+            Some(value) => {
+                value.expiry = expires_at;
+                value.grace_period = grace_period;
+                value.to_response(now)
             }
-        } else {
-            Response::Miss
+            None => Response::Miss,
         };
+        shard.touch(key);
         Ok(response)
     }
+
+    async fn purge(&self, key: &str) -> Result<bool> {
+        Ok(self.shard(key)?.remove(key).is_some())
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<usize> {
+        let mut purged = 0;
+        for shard in self.shards.iter() {
+            let mut shard = shard
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock data for tag: {}", tag))?;
+            purged += shard.retain(|value| !value.cache.tags.iter().any(|value| value == tag));
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{header, HeaderValue, StatusCode};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_binary_response() {
+        let datastore = InMemoryDatastore::default();
+        let cache = Cache::new(
+            StatusCode::NOT_FOUND,
+            vec![(header::CONTENT_TYPE, HeaderValue::from_static("image/png"))],
+            vec![0x89, b'P', b'N', b'G', 0xff, 0x00],
+        );
+        datastore
+            .save_cache("key", cache.clone(), None, 60, 0)
+            .await
+            .unwrap();
+        match datastore.fetch_cache("key").await.unwrap() {
+            Response::Hit(cached, _) => {
+                assert_eq!(cached.status, cache.status);
+                assert_eq!(cached.headers, cache.headers);
+                assert_eq!(cached.body, cache.body);
+            }
+            _ => panic!("expected a cache hit"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grace_period() {
+        let datastore = InMemoryDatastore::default();
+        let cache = Cache::new(StatusCode::OK, vec![], b"stale".to_vec());
+        datastore
+            .save_cache("stale", cache.clone(), Some("etag".to_string()), 0, 60)
+            .await
+            .unwrap();
+        datastore
+            .save_cache("expired", cache, Some("etag".to_string()), 0, 0)
+            .await
+            .unwrap();
+        match datastore.fetch_cache("stale").await.unwrap() {
+            Response::Stale(cached, stale_for, etag) => {
+                assert_eq!(cached.body, b"stale");
+                assert!(stale_for < 60);
+                assert_eq!(etag.as_deref(), Some("etag"));
+            }
+            _ => panic!("expected a stale response"),
+        }
+        match datastore.fetch_cache("expired").await.unwrap() {
+            Response::Expired(etag) => assert_eq!(etag.as_deref(), Some("etag")),
+            _ => panic!("expected an expired response"),
+        }
+        let now = chrono::Utc::now().timestamp() as usize;
+        match datastore
+            .refresh_cache("expired", now + 60, 0)
+            .await
+            .unwrap()
+        {
+            Response::Hit(cached, _) => assert_eq!(cached.body, b"stale"),
+            _ => panic!("expected a cache hit"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_purge() {
+        let datastore = InMemoryDatastore::default();
+        for (key, tags) in [
+            ("a", vec!["app:a".to_string(), "surrogate:x".to_string()]),
+            ("b", vec!["app:a".to_string()]),
+            ("c", vec!["app:c".to_string(), "surrogate:x".to_string()]),
+        ] {
+            let cache = Cache::new(StatusCode::OK, vec![], vec![]).with_tags(tags);
+            datastore.save_cache(key, cache, None, 60, 0).await.unwrap();
+        }
+        assert!(datastore.purge("b").await.unwrap());
+        assert!(!datastore.purge("b").await.unwrap());
+        assert_eq!(datastore.purge_tag("surrogate:x").await.unwrap(), 2);
+        assert_eq!(datastore.purge_tag("app:a").await.unwrap(), 0);
+        assert!(matches!(
+            datastore.fetch_cache("a").await.unwrap(),
+            Response::Miss
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let entry_size = ENTRY_OVERHEAD + 1 + 100;
+        let datastore = InMemoryDatastore::new(entry_size * 2).with_shards(1);
+        let cache = Cache::new(StatusCode::OK, vec![], vec![0; 100]);
+        for key in ["a", "b"] {
+            datastore
+                .save_cache(key, cache.clone(), None, 60, 0)
+                .await
+                .unwrap();
+        }
+        assert!(matches!(
+            datastore.fetch_cache("a").await.unwrap(),
+            Response::Hit(..)
+        ));
+        datastore.save_cache("c", cache, None, 60, 0).await.unwrap();
+        assert!(matches!(
+            datastore.fetch_cache("b").await.unwrap(),
+            Response::Miss
+        ));
+        assert!(matches!(
+            datastore.fetch_cache("a").await.unwrap(),
+            Response::Hit(..)
+        ));
+        let too_large = Cache::new(StatusCode::OK, vec![], vec![0; entry_size * 2]);
+        datastore
+            .save_cache("d", too_large, None, 60, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            datastore.stats(),
+            Stats {
+                hits: 2,
+                misses: 1,
+                evictions: 1,
+                expirations: 0,
+                rejections: 1,
+                entries: 2,
+                size: entry_size * 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep() {
+        let datastore = InMemoryDatastore::default();
+        let cache = Cache::new(StatusCode::OK, vec![], vec![]);
+        datastore
+            .save_cache("fresh", cache.clone(), None, 60, 0)
+            .await
+            .unwrap();
+        datastore
+            .save_cache("stale", cache.clone(), None, 0, 60)
+            .await
+            .unwrap();
+        datastore
+            .save_cache("expired", cache, None, 0, 0)
+            .await
+            .unwrap();
+        let now = chrono::Utc::now().timestamp() as usize;
+        assert_eq!(sweep(&datastore.shards, now), 1);
+        assert_eq!(datastore.stats().entries, 2);
+        assert!(matches!(
+            datastore.fetch_cache("expired").await.unwrap(),
+            Response::Miss
+        ));
+    }
 }
@@ -1,14 +1,20 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use http::{HeaderName, HeaderValue, StatusCode};
 
-pub use memory::InMemoryDatastore;
+pub use memory::{InMemoryDatastore, Stats};
 pub use redis::RedisDatastore;
+pub use tiered::TieredDatastore;
 
 mod memory;
 mod redis;
+mod tiered;
 
 pub enum Response {
+    /// A fresh response which expires in the given number of seconds.
     Hit(Cache, usize),
+    /// A response which expired the given number of seconds ago, still within its grace period.
+    Stale(Cache, usize, Option<String>),
     Expired(Option<String>),
     Miss,
 }
@@ -20,17 +26,134 @@ pub trait Datastore {
     async fn save_cache(
         &self,
         key: &str,
-        value: String,
-        headers: String,
+        cache: Cache,
         etag: Option<String>,
         expires_in: usize,
+        grace_period: usize,
     ) -> Result<()>;
 
-    async fn refresh_cache(&self, key: &str, expires_at: usize) -> Result<Response>;
+    async fn refresh_cache(
+        &self,
+        key: &str,
+        expires_at: usize,
+        grace_period: usize,
+    ) -> Result<Response>;
+
+    /// Lock the key for the request fetching its response from the origin, shared by all gateways
+    /// using the datastore. The lock is released after `timeout` seconds at the latest.
+    /// Returns the token of the lock if it was acquired, datastores local to the gateway do not need one.
+    async fn lock(&self, _key: &str, _timeout: usize) -> Result<Option<String>> {
+        Ok(Some(String::new()))
+    }
+
+    /// Release the lock if it is still held with the token, it may have timed out and been taken
+    /// by another request since.
+    async fn unlock(&self, _key: &str, _token: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Remove the entry stored under the key, returns whether there was one.
+    async fn purge(&self, key: &str) -> Result<bool>;
+
+    /// Remove all entries tagged with the tag, returns how many there were.
+    async fn purge_tag(&self, tag: &str) -> Result<usize>;
 }
 
+/// Tag of all entries of the app.
+pub fn app_tag(app: &str) -> String {
+    format!("app:{}", app)
+}
+
+/// Tag of all entries of the endpoint of the app.
+pub fn endpoint_tag(app: &str, endpoint: &str) -> String {
+    format!("endpoint:{}/{}", app, endpoint)
+}
+
+/// Tag of all entries with the surrogate key set by the origin.
+pub fn surrogate_tag(key: &str) -> String {
+    format!("surrogate:{}", key)
+}
+
+/// A cached response with its original status, headers and raw body.
 #[derive(Clone, Debug)]
 pub struct Cache {
-    pub response: String,
-    pub headers: String,
+    pub status: StatusCode,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub body: Vec<u8>,
+    /// Tags the entry can be purged by.
+    pub tags: Vec<String>,
+}
+
+impl Cache {
+    pub fn new(status: StatusCode, headers: Vec<(HeaderName, HeaderValue)>, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Serialize the headers as `name: value` lines, header values may not contain line breaks.
+    pub(crate) fn encode_headers(&self) -> Vec<u8> {
+        let mut raw = Vec::new();
+        for (name, value) in self.headers.iter() {
+            if !raw.is_empty() {
+                raw.push(b'\n');
+            }
+            raw.extend_from_slice(name.as_str().as_bytes());
+            raw.extend_from_slice(b": ");
+            raw.extend_from_slice(value.as_bytes());
+        }
+        raw
+    }
+
+    /// Parse headers serialized with [`Cache::encode_headers`], skipping invalid lines.
+    pub(crate) fn decode_headers(raw: &[u8]) -> Vec<(HeaderName, HeaderValue)> {
+        raw.split(|byte| *byte == b'\n')
+            .filter_map(|line| {
+                let separator = line.iter().position(|byte| *byte == b':')?;
+                let name = HeaderName::from_bytes(&line[..separator]).ok()?;
+                let value = line[separator + 1..]
+                    .strip_prefix(b" ")
+                    .unwrap_or(&line[separator + 1..]);
+                Some((name, HeaderValue::from_bytes(value).ok()?))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_headers_round_trip() {
+        let cache = Cache::new(
+            StatusCode::NOT_FOUND,
+            vec![
+                (header::CONTENT_TYPE, HeaderValue::from_static("image/png")),
+                (header::SET_COOKIE, HeaderValue::from_static("a=1")),
+                (header::SET_COOKIE, HeaderValue::from_static("b=2; Path=/")),
+                (
+                    header::CONTENT_DISPOSITION,
+                    HeaderValue::from_bytes(b"attachment; filename=\"\xe4.png\"").unwrap(),
+                ),
+            ],
+            vec![0x89, b'P', b'N', b'G', 0xff, 0x00],
+        );
+        assert_eq!(
+            Cache::decode_headers(&cache.encode_headers()),
+            cache.headers
+        );
+        assert_eq!(Cache::decode_headers(b""), vec![]);
+    }
 }
@@ -7,14 +7,56 @@ use bb8_redis::{
     RedisConnectionManager,
 };
 use essentials::debug;
+use http::StatusCode;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Takes the key of the lock and its token, deletes the lock only if it is held with the token.
+const UNLOCK: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
 
+#[derive(Clone)]
 pub struct RedisDatastore {
     pool: Pool<RedisConnectionManager>,
+    distributed_lock: bool,
+    unlock: redis::Script,
+    instance: Arc<str>,
+    locks: Arc<AtomicU64>,
 }
 
 impl RedisDatastore {
     pub fn new(pool: Pool<RedisConnectionManager>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            distributed_lock: false,
+            unlock: redis::Script::new(UNLOCK),
+            instance: crate::utils::instance_id().into(),
+            locks: Arc::default(),
+        }
+    }
+
+    /// Coalesce requests fetching the same response across all gateways sharing the Redis server.
+    pub fn with_distributed_lock(mut self) -> Self {
+        self.distributed_lock = true;
+        self
+    }
+
+    pub(super) async fn publish(&self, channel: &str, message: String) -> Result<()> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .with_context(|| "Failed to get connection from Redis pool".to_string())?;
+        conn.publish(channel, message)
+            .await
+            .map(|_: ()| ())
+            .with_context(|| format!("Failed to publish to channel: {}", channel))
     }
 }
 
@@ -24,49 +66,50 @@ impl Datastore for RedisDatastore {
         let mut conn = self.pool.get().await.with_context(|| {
             format!("Failed to get connection from Redis pool for key: {}", key)
         })?;
-        let (expires_in, exists) = redis::pipe()
+        let (expires_at, exists) = redis::pipe()
             .atomic()
-            .ttl(format!("{key}:expire"))
+            .get(format!("{key}:expire"))
             .exists(format!("{key}:value"))
             .query_async(&mut *conn)
             .await
-            .map(|(expires_in, exists): (isize, bool)| (expires_in, exists))
-            .with_context(|| format!("Failed to fetch cache for key: {}", key))?;
-        debug!(key, expires_in, exists, "Fetched cache");
-        let response = if expires_in < 0 {
-            if exists {
-                Response::Expired(
-                    conn.get(format!("{key}:etag"))
-                        .await
-                        .map(|value: Option<String>| {
-                            value
-                                .and_then(|value| if value.is_empty() { None } else { Some(value) })
-                        })
-                        .with_context(|| "Failed to fetch etag".to_string())?,
+            .map(|(expires_at, exists): (Option<String>, bool)| {
+                (
+                    expires_at.and_then(|value| value.parse::<usize>().ok()),
+                    exists,
                 )
-            } else {
-                Response::Miss
+            })
+            .with_context(|| format!("Failed to fetch cache for key: {}", key))?;
+        debug!(key, expires_at, exists, "Fetched cache");
+        let response = match expires_at {
+            Some(expires_at) => {
+                let now = chrono::Utc::now().timestamp() as usize;
+                redis::pipe()
+                    .atomic()
+                    .get(format!("{key}:status"))
+                    .get(format!("{key}:headers"))
+                    .get(format!("{key}:value"))
+                    .get(format!("{key}:tags"))
+                    .get(format!("{key}:etag"))
+                    .query_async(&mut *conn)
+                    .await
+                    .map(|(status, headers, body, tags, etag): StoredWithEtag| {
+                        match to_cache((status, headers, body, tags)) {
+                            Some(cache) if expires_at > now => {
+                                Response::Hit(cache, expires_at - now)
+                            }
+                            Some(cache) => Response::Stale(cache, now - expires_at, to_etag(etag)),
+                            None => Response::Miss,
+                        }
+                    })
+                    .with_context(|| "Failed to fetch value and headers".to_string())?
             }
-        } else {
-            redis::pipe()
-                .atomic()
-                .get(format!("{key}:value"))
-                .get(format!("{key}:headers"))
-                .query_async(&mut *conn)
-                .await
-                .map(
-                    |response: (Option<String>, Option<String>)| match response {
-                        (Some(value), Some(headers)) => Response::Hit(
-                            Cache {
-                                response: value,
-                                headers,
-                            },
-                            expires_in as usize,
-                        ),
-                        _ => Response::Miss,
-                    },
-                )
-                .with_context(|| "Failed to fetch value and headers".to_string())?
+            None if exists => Response::Expired(
+                conn.get(format!("{key}:etag"))
+                    .await
+                    .map(to_etag)
+                    .with_context(|| "Failed to fetch etag".to_string())?,
+            ),
+            None => Response::Miss,
         };
         Ok(response)
     }
@@ -74,30 +117,40 @@ impl Datastore for RedisDatastore {
     async fn save_cache(
         &self,
         key: &str,
-        value: String,
-        headers: String,
+        cache: Cache,
         etag: Option<String>,
         expires_in: usize,
+        grace_period: usize,
     ) -> Result<()> {
         debug!(
             key = key,
-            value = value,
+            status = cache.status.as_u16(),
+            length = cache.body.len(),
             etag = etag,
             expires_in = expires_in,
+            grace_period = grace_period,
             "Saving cache"
         );
         let mut conn = self.pool.get().await.with_context(|| {
             format!("Failed to get connection from Redis pool for key: {}", key)
         })?;
-        redis::pipe()
-            .atomic()
-            .set_ex(format!("{key}:expire"), "", expires_in as u64)
+        let now = chrono::Utc::now().timestamp() as usize;
+        let expires_at = now + expires_in;
+        let ttl = expires_in + grace_period;
+        let mut pipe = redis::pipe();
+        tag_entry(&mut pipe, key, &cache.tags, now, ttl);
+        pipe.atomic()
+            .set_ex(format!("{key}:expire"), expires_at, ttl as u64)
             .ignore()
             .set(format!("{key}:etag"), etag.unwrap_or_default())
             .ignore()
-            .set(format!("{key}:headers"), headers)
+            .set(format!("{key}:status"), cache.status.as_u16())
             .ignore()
-            .set(format!("{key}:value"), value)
+            .set(format!("{key}:headers"), cache.encode_headers())
+            .ignore()
+            .set(format!("{key}:tags"), cache.tags.join("\n"))
+            .ignore()
+            .set(format!("{key}:value"), cache.body)
             .ignore()
             .query_async(&mut *conn)
             .await
@@ -106,37 +159,187 @@ impl Datastore for RedisDatastore {
         Ok(())
     }
 
-    async fn refresh_cache(&self, key: &str, expires_at: usize) -> Result<Response> {
+    async fn refresh_cache(
+        &self,
+        key: &str,
+        expires_at: usize,
+        grace_period: usize,
+    ) -> Result<Response> {
         let now = chrono::Utc::now().timestamp() as usize;
         let ttl = expires_at.saturating_sub(now);
         let mut conn = self.pool.get().await.with_context(|| {
             format!("Failed to get connection from Redis pool for key: {}", key)
         })?;
-        redis::pipe()
+        let cache = redis::pipe()
             .atomic()
-            .cmd("SET")
-            .arg(format!("{key}:expire"))
-            .arg(0)
-            .arg("EX")
-            .arg(expires_at - now)
-            .arg("NX")
+            .set_ex(
+                format!("{key}:expire"),
+                expires_at,
+                (ttl + grace_period) as u64,
+            )
             .ignore()
-            .get(format!("{key}:value"))
+            .get(format!("{key}:status"))
             .get(format!("{key}:headers"))
+            .get(format!("{key}:value"))
+            .get(format!("{key}:tags"))
             .query_async(&mut *conn)
             .await
-            .map(
-                |response: (Option<String>, Option<String>)| match response {
-                    (Some(value), Some(headers)) => Response::Hit(
-                        Cache {
-                            response: value,
-                            headers,
-                        },
-                        ttl,
-                    ),
-                    _ => Response::Miss,
-                },
-            )
-            .with_context(|| "Failed to refresh cache".to_string())
+            .map(to_cache)
+            .with_context(|| "Failed to refresh cache".to_string())?;
+        let cache = match cache {
+            Some(cache) => cache,
+            None => {
+                return Ok(Response::Miss);
+            }
+        };
+        if !cache.tags.is_empty() {
+            let mut pipe = redis::pipe();
+            tag_entry(&mut pipe, key, &cache.tags, now, ttl + grace_period);
+            pipe.query_async(&mut *conn)
+                .await
+                .map(|_: ()| ())
+                .with_context(|| format!("Failed to refresh tags for key: {}", key))?;
+        }
+        Ok(Response::Hit(cache, ttl))
+    }
+
+    async fn lock(&self, key: &str, timeout: usize) -> Result<Option<String>> {
+        if !self.distributed_lock {
+            return Ok(Some(String::new()));
+        }
+        // Unique to the request, so that only the holder of the lock can release it.
+        let token = format!(
+            "{}-{}",
+            self.instance,
+            self.locks.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        redis::cmd("SET")
+            .arg(format!("{key}:lock"))
+            .arg(&token)
+            .arg("NX")
+            .arg("EX")
+            .arg(timeout.max(1))
+            .query_async(&mut *conn)
+            .await
+            .map(|response: Option<String>| response.map(|_| token))
+            .with_context(|| format!("Failed to lock cache for key: {}", key))
     }
+
+    async fn unlock(&self, key: &str, token: &str) -> Result<()> {
+        if !self.distributed_lock {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        self.unlock
+            .key(format!("{key}:lock"))
+            .arg(token)
+            .invoke_async(&mut *conn)
+            .await
+            .map(|_: usize| ())
+            .with_context(|| format!("Failed to unlock cache for key: {}", key))
+    }
+
+    async fn purge(&self, key: &str) -> Result<bool> {
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        conn.del(entry_keys(key))
+            .await
+            .map(|deleted: usize| deleted > 0)
+            .with_context(|| format!("Failed to purge cache for key: {}", key))
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<usize> {
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for tag: {}", tag)
+        })?;
+        let keys: Vec<String> = conn
+            .zrange(tag_key(tag), 0, -1)
+            .await
+            .with_context(|| format!("Failed to fetch keys for tag: {}", tag))?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for key in keys.iter() {
+            pipe.del(entry_keys(key));
+        }
+        pipe.del(tag_key(tag))
+            .ignore()
+            .query_async(&mut *conn)
+            .await
+            .map(|deleted: Vec<usize>| deleted.into_iter().filter(|deleted| *deleted > 0).count())
+            .with_context(|| format!("Failed to purge cache for tag: {}", tag))
+    }
+}
+
+/// Add the entry to the sets of its tags for its lifetime.
+/// Members are scored by the end of their lifetime and dropped once it is over,
+/// the sets live at least as long as their longest living member.
+fn tag_entry(pipe: &mut redis::Pipeline, key: &str, tags: &[String], now: usize, ttl: usize) {
+    for tag in tags {
+        let tag_key = tag_key(tag);
+        pipe.zadd(&tag_key, key, now + ttl)
+            .ignore()
+            .zrembyscore(&tag_key, "-inf", now)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&tag_key)
+            .arg(ttl)
+            .arg("NX")
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&tag_key)
+            .arg(ttl)
+            .arg("GT")
+            .ignore();
+    }
+}
+
+/// All Redis keys an entry is stored under.
+fn entry_keys(key: &str) -> Vec<String> {
+    ["expire", "etag", "status", "headers", "value", "tags"]
+        .iter()
+        .map(|field| format!("{key}:{field}"))
+        .collect()
+}
+
+/// Sorted set of the keys of the entries tagged with the tag, scored by the end of their lifetime.
+fn tag_key(tag: &str) -> String {
+    format!("cache-tag:{tag}")
+}
+
+/// Status, headers, body and tags of a cached response as stored in Redis.
+type Stored = (
+    Option<u16>,
+    Option<Vec<u8>>,
+    Option<Vec<u8>>,
+    Option<String>,
+);
+
+type StoredWithEtag = (
+    Option<u16>,
+    Option<Vec<u8>>,
+    Option<Vec<u8>>,
+    Option<String>,
+    Option<String>,
+);
+
+fn to_cache((status, headers, body, tags): Stored) -> Option<Cache> {
+    let status = StatusCode::from_u16(status?).ok()?;
+    let tags = tags
+        .unwrap_or_default()
+        .lines()
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some(Cache::new(status, Cache::decode_headers(&headers?), body?).with_tags(tags))
+}
+
+/// Etags are stored as empty strings when the response has none.
+fn to_etag(etag: Option<String>) -> Option<String> {
+    etag.filter(|etag| !etag.is_empty())
 }
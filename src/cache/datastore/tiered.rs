@@ -0,0 +1,254 @@
+use super::{Cache, Datastore, InMemoryDatastore, RedisDatastore, Response};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bb8_redis::redis;
+use essentials::{debug, warn};
+use futures::StreamExt;
+use std::{
+    sync::{Arc, Once, Weak},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// Pub/sub channel the invalidations are published to.
+const CHANNEL: &str = "cache-invalidate";
+/// Default limit of how long an entry is served from the local tier, 60 seconds.
+const DEFAULT_LOCAL_TTL: usize = 60;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Datastore serving hot entries from memory in front of Redis.
+///
+/// Entries missing in the local tier are fetched from Redis and kept locally for at most the local TTL.
+/// Saved and purged entries are invalidated in the local tiers of all gateways sharing the Redis server
+/// through pub/sub, the subscription is started by [`TieredDatastore::connect`], or with the first
+/// request when created with [`TieredDatastore::new`].
+#[derive(Clone)]
+pub struct TieredDatastore {
+    local: Arc<InMemoryDatastore>,
+    remote: RedisDatastore,
+    client: redis::Client,
+    instance: Arc<str>,
+    local_ttl: usize,
+    subscriber: Arc<Once>,
+}
+
+/// Invalidation published to the other gateways.
+enum Invalidation<'a> {
+    Key(&'a str),
+    Tag(&'a str),
+}
+
+impl TieredDatastore {
+    /// The client should connect to the same Redis server as the remote datastore.
+    pub fn new(local: InMemoryDatastore, remote: RedisDatastore, client: redis::Client) -> Self {
+        let instance = crate::utils::instance_id();
+        Self {
+            local: Arc::new(local),
+            remote,
+            client,
+            instance: instance.into(),
+            local_ttl: DEFAULT_LOCAL_TTL,
+            subscriber: Arc::new(Once::new()),
+        }
+    }
+
+    /// Create the datastore once subscribed to the invalidations of the other gateways.
+    pub async fn connect(
+        local: InMemoryDatastore,
+        remote: RedisDatastore,
+        client: redis::Client,
+    ) -> Result<Self> {
+        let datastore = Self::new(local, remote, client);
+        let (subscribed_tx, subscribed_rx) = oneshot::channel();
+        datastore.start_subscriber(Some(subscribed_tx));
+        subscribed_rx
+            .await
+            .with_context(|| "Cache invalidation subscription stopped".to_string())??;
+        Ok(datastore)
+    }
+
+    /// Longest time in seconds an entry is served from the local tier without checking Redis.
+    pub fn with_local_ttl(mut self, local_ttl: usize) -> Self {
+        self.local_ttl = local_ttl;
+        self
+    }
+
+    async fn save_local(&self, key: &str, cache: Cache, expires_in: usize) -> Result<()> {
+        self.local
+            .save_cache(key, cache, None, expires_in.min(self.local_ttl), 0)
+            .await
+    }
+
+    async fn publish(&self, invalidation: Invalidation<'_>) -> Result<()> {
+        let message = match invalidation {
+            Invalidation::Key(key) => format!("{} key {}", self.instance, key),
+            Invalidation::Tag(tag) => format!("{} tag {}", self.instance, tag),
+        };
+        self.remote.publish(CHANNEL, message).await
+    }
+
+    /// Start the subscription unless it is already started,
+    /// the result of the first attempt to subscribe is sent to `subscribed`.
+    fn start_subscriber(&self, mut subscribed: Option<oneshot::Sender<Result<()>>>) {
+        self.subscriber.call_once(|| {
+            let local = Arc::downgrade(&self.local);
+            let client = self.client.clone();
+            let instance = self.instance.clone();
+            tokio::spawn(async move {
+                let mut reconnected = false;
+                loop {
+                    let result =
+                        subscribe(&client, &local, &instance, reconnected, &mut subscribed).await;
+                    if let Err(err) = result {
+                        match subscribed.take() {
+                            Some(subscribed) => {
+                                let _ = subscribed.send(Err(err));
+                            }
+                            None => warn!("Cache invalidation subscription failed: {}", err),
+                        }
+                    }
+                    if local.strong_count() == 0 {
+                        break;
+                    }
+                    reconnected = true;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            });
+        });
+    }
+}
+
+/// Apply the invalidations published by the other gateways to the local tier until the
+/// connection is lost or the datastore is dropped.
+/// Invalidations may have been missed while reconnecting, so the local tier is cleared then.
+async fn subscribe(
+    client: &redis::Client,
+    local: &Weak<InMemoryDatastore>,
+    instance: &str,
+    reconnected: bool,
+    subscribed: &mut Option<oneshot::Sender<Result<()>>>,
+) -> Result<()> {
+    let mut pubsub = client
+        .get_async_pubsub()
+        .await
+        .with_context(|| "Failed to connect to Redis for cache invalidations".to_string())?;
+    pubsub
+        .subscribe(CHANNEL)
+        .await
+        .with_context(|| format!("Failed to subscribe to channel: {}", CHANNEL))?;
+    if reconnected {
+        if let Some(local) = local.upgrade() {
+            local.clear();
+        }
+    }
+    if let Some(subscribed) = subscribed.take() {
+        let _ = subscribed.send(Ok(()));
+    }
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let local = match local.upgrade() {
+            Some(local) => local,
+            None => return Ok(()),
+        };
+        let payload: String = match message.get_payload() {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!("Invalid cache invalidation: {}", err);
+                continue;
+            }
+        };
+        let mut parts = payload.splitn(3, ' ');
+        let (sender, kind, target) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(sender), Some(kind), Some(target)) => (sender, kind, target),
+            _ => {
+                warn!("Invalid cache invalidation: {}", payload);
+                continue;
+            }
+        };
+        if sender == instance {
+            continue;
+        }
+        debug!(kind, target, "Received cache invalidation");
+        let result = match kind {
+            "key" => local.purge(target).await.map(|_| ()),
+            "tag" => local.purge_tag(target).await.map(|_| ()),
+            _ => {
+                warn!("Invalid cache invalidation: {}", payload);
+                continue;
+            }
+        };
+        if let Err(err) = result {
+            warn!("Failed to invalidate local cache: {}", err);
+        }
+    }
+    Err(anyhow::anyhow!("Connection to Redis closed"))
+}
+
+#[async_trait]
+impl Datastore for TieredDatastore {
+    async fn fetch_cache(&self, key: &str) -> Result<Response> {
+        self.start_subscriber(None);
+        if let Response::Hit(cache, expires_in) = self.local.fetch_cache(key).await? {
+            return Ok(Response::Hit(cache, expires_in));
+        }
+        let response = self.remote.fetch_cache(key).await?;
+        if let Response::Hit(cache, expires_in) = &response {
+            self.save_local(key, cache.clone(), *expires_in).await?;
+        }
+        Ok(response)
+    }
+
+    async fn save_cache(
+        &self,
+        key: &str,
+        cache: Cache,
+        etag: Option<String>,
+        expires_in: usize,
+        grace_period: usize,
+    ) -> Result<()> {
+        self.start_subscriber(None);
+        self.remote
+            .save_cache(key, cache.clone(), etag, expires_in, grace_period)
+            .await?;
+        self.save_local(key, cache, expires_in).await?;
+        self.publish(Invalidation::Key(key)).await
+    }
+
+    async fn refresh_cache(
+        &self,
+        key: &str,
+        expires_at: usize,
+        grace_period: usize,
+    ) -> Result<Response> {
+        let response = self
+            .remote
+            .refresh_cache(key, expires_at, grace_period)
+            .await?;
+        if let Response::Hit(cache, expires_in) = &response {
+            self.save_local(key, cache.clone(), *expires_in).await?;
+        }
+        Ok(response)
+    }
+
+    async fn lock(&self, key: &str, timeout: usize) -> Result<Option<String>> {
+        self.remote.lock(key, timeout).await
+    }
+
+    async fn unlock(&self, key: &str, token: &str) -> Result<()> {
+        self.remote.unlock(key, token).await
+    }
+
+    async fn purge(&self, key: &str) -> Result<bool> {
+        let local = self.local.purge(key).await?;
+        let remote = self.remote.purge(key).await?;
+        self.publish(Invalidation::Key(key)).await?;
+        Ok(local || remote)
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<usize> {
+        let local = self.local.purge_tag(tag).await?;
+        let remote = self.remote.purge_tag(tag).await?;
+        self.publish(Invalidation::Tag(tag)).await?;
+        Ok(local.max(remote))
+    }
+}
@@ -0,0 +1,102 @@
+//! Single-flight coalescing of concurrent requests fetching the same response from the origin.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use essentials::warn;
+use tokio::sync::watch;
+
+use super::Datastore;
+
+#[derive(Clone, Default)]
+pub struct Flights(Arc<Mutex<HashMap<String, watch::Receiver<()>>>>);
+
+pub enum Flight {
+    /// This request fetches the response, the waiters are woken when the guard is dropped.
+    Leader(Guard),
+    /// Another request is fetching the response, the receiver is closed once it is done.
+    Waiter(watch::Receiver<()>),
+}
+
+impl Flights {
+    pub fn join(&self, key: &str) -> Flight {
+        let mut flights = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(waiter) = flights.get(key) {
+            return Flight::Waiter(waiter.clone());
+        }
+        let (sender, receiver) = watch::channel(());
+        flights.insert(key.to_string(), receiver);
+        Flight::Leader(Guard {
+            flights: self.clone(),
+            key: key.to_string(),
+            lock: None,
+            _sender: sender,
+        })
+    }
+}
+
+pub struct Guard {
+    flights: Flights,
+    key: String,
+    lock: Option<(Arc<dyn Datastore + Send + Sync + 'static>, String)>,
+    _sender: watch::Sender<()>,
+}
+
+impl Guard {
+    /// Release the datastore lock on the key held with the token when the guard is dropped.
+    pub fn with_lock(
+        mut self,
+        datastore: Arc<dyn Datastore + Send + Sync + 'static>,
+        token: String,
+    ) -> Self {
+        self.lock = Some((datastore, token));
+        self
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.flights
+            .0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .remove(&self.key);
+        if let Some((datastore, token)) = self.lock.take() {
+            let key = std::mem::take(&mut self.key);
+            tokio::spawn(async move {
+                if let Err(error) = datastore.unlock(&key, &token).await {
+                    warn!("Failed to release cache lock: {}", error);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waiters_are_woken() {
+        let flights = Flights::default();
+        let leader = match flights.join("key") {
+            Flight::Leader(guard) => guard,
+            Flight::Waiter(_) => panic!("expected to lead the flight"),
+        };
+        let mut waiter = match flights.join("key") {
+            Flight::Waiter(waiter) => waiter,
+            Flight::Leader(_) => panic!("expected to wait for the flight"),
+        };
+        assert!(matches!(flights.join("other"), Flight::Leader(_)));
+        let waiting = tokio::spawn(async move { waiter.changed().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+        drop(leader);
+        assert!(waiting.await.unwrap().is_err());
+        assert!(matches!(flights.join("key"), Flight::Leader(_)));
+    }
+}
@@ -0,0 +1,109 @@
+use http::Method;
+use pingora_cache::{
+    key::{hash_key, CacheHashKey, CompactCacheKey},
+    VarianceBuilder,
+};
+
+use super::{config::Query, context};
+use crate::{
+    http::{headers, HeaderMapExt, Request},
+    utils::route_params,
+    Ctx,
+};
+
+/// Build the cache key of the request from the key template of its endpoint.
+pub fn build(ctx: &Ctx, request: &Request, endpoint: &context::Endpoint) -> String {
+    let template = &endpoint.key;
+    let (path, query) = request
+        .path
+        .split_once('?')
+        .unwrap_or((request.path.as_str(), ""));
+    let mut primary = format!("{}:{}", ctx.app_id, ctx.endpoint_id);
+    if template.method {
+        // HEAD requests are answered with the entry of the GET request.
+        let method = if request.method == Method::HEAD {
+            Method::GET
+        } else {
+            request.method.clone()
+        };
+        primary.push_str("\nmethod=");
+        primary.push_str(method.as_str());
+    }
+    if template.path {
+        // The exact path the origin receives, so that e.g. `/a/../b` cannot be served as `/b`.
+        primary.push_str("\npath=");
+        primary.push_str(path);
+    }
+    if let Some((route, names)) = &template.route_params {
+        for (name, value) in route_params(route, path) {
+            if names.iter().any(|param| param == name) {
+                primary.push_str(&format!("\nparam:{}={}", name, value));
+            }
+        }
+    }
+    if template.query != Query::Ignore {
+        primary.push_str("\nquery=");
+        primary.push_str(&normalize_query(query, &template.query));
+    }
+    let user_tag = if template.per_client {
+        request
+            .header(&headers::REAL_IP)
+            .and_then(|header| header.to_str().ok())
+            .unwrap_or_default()
+    } else {
+        ""
+    };
+    let mut variance = VarianceBuilder::new();
+    for header in endpoint.vary_headers.iter().chain(template.headers.iter()) {
+        let value = request
+            .header(header)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        variance.add_value(header, value);
+    }
+    CompactCacheKey {
+        primary: hash_key(primary.as_str()),
+        user_tag: user_tag.into(),
+        variance: variance.finalize().map(Box::new),
+    }
+    .combined()
+}
+
+/// Sort the query parameters, keeping only the selected ones.
+/// The parameters are otherwise kept as the origin receives them.
+fn normalize_query(query: &str, selected: &Query) -> String {
+    let mut params = query
+        .split('&')
+        .filter(|param| {
+            let name = param.split_once('=').map_or(*param, |(name, _)| name);
+            match selected {
+                Query::Ignore => false,
+                Query::All => true,
+                Query::Only(names) => names.iter().any(|selected| selected == name),
+            }
+        })
+        .collect::<Vec<_>>();
+    params.sort_unstable();
+    params.join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_query() {
+        assert_eq!(normalize_query("b=2&a=1&c", &Query::All), "a=1&b=2&c");
+        assert_eq!(normalize_query("c=&a=1&c", &Query::All), "a=1&c&c=");
+        assert_eq!(
+            normalize_query(
+                "b=2&utm_source=x&a=1",
+                &Query::Only(vec!["a".to_string(), "b".to_string()])
+            ),
+            "a=1&b=2"
+        );
+        assert_eq!(normalize_query("", &Query::All), "");
+    }
+}
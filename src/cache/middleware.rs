@@ -1,36 +1,54 @@
-use super::{datastore, response::CachedResponseBody, Datastore};
+use super::{
+    datastore::{self, Cache},
+    flight::{Flight, Flights, Guard},
+    policy::{self, CacheControl},
+    Datastore,
+};
 use crate::{
-    gateway::{middleware::Middleware as TMiddleware, next::Next, Result},
-    http::{headers, HeaderMapExt, Request, Response},
+    gateway::{
+        middleware::Middleware as TMiddleware,
+        next::{Detached, Next},
+        Result,
+    },
+    http::{headers, Body, HeaderMapExt, Request, Response},
     time::TimeUnit,
     Ctx,
 };
-use anyhow::Context;
 use async_trait::async_trait;
-use essentials::debug;
-use http::{header, HeaderName, StatusCode};
-use pingora_cache::{
-    key::{hash_key, CacheHashKey, CompactCacheKey},
-    VarianceBuilder,
-};
+use essentials::{debug, warn};
+use futures::{stream, StreamExt};
+use http::{header, HeaderMap, HeaderName, Method, StatusCode};
+use std::{sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+/// How often the datastore is checked for a response fetched by another gateway.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub struct Middleware {
     ctx: super::Context,
-    datastore: Box<dyn Datastore + Sync + 'static>,
+    datastore: Arc<dyn Datastore + Send + Sync + 'static>,
+    flights: Flights,
 }
 
 unsafe impl Send for Middleware {}
 unsafe impl Sync for Middleware {}
 
 impl Middleware {
-    pub(crate) fn new(ctx: super::Context, datastore: Box<dyn Datastore + Sync + 'static>) -> Self {
-        Self { ctx, datastore }
+    pub(crate) fn new(
+        ctx: super::Context,
+        datastore: Box<dyn Datastore + Send + Sync + 'static>,
+    ) -> Self {
+        Self {
+            ctx,
+            datastore: Arc::from(datastore),
+            flights: Flights::default(),
+        }
     }
 }
 
 #[async_trait]
 impl TMiddleware for Middleware {
-    async fn run(&self, ctx: &Ctx, mut request: Request, next: Next<'_>) -> Result<Response> {
+    async fn run(&self, ctx: &Ctx, mut request: Request, mut next: Next<'_>) -> Result<Response> {
         let endpoint = match self
             .ctx
             .get(ctx.app_id)
@@ -41,95 +59,402 @@ impl TMiddleware for Middleware {
                 return next.run(request).await;
             }
         };
-        let ip = request
-            .header(&headers::REAL_IP)
-            .and_then(|header| header.to_str().ok())
-            .unwrap_or("")
-            .to_string()
-            .into_boxed_str();
-        let mut variance = VarianceBuilder::new();
-        for header in endpoint.vary_headers.iter() {
-            let value = request
-                .header(header)
-                .and_then(|value| value.to_str().ok())
-                .unwrap_or_default();
-            variance.add_value(header, value);
+        if endpoint.purge_method && request.method.as_str() == "PURGE" {
+            let mut purged = request.clone();
+            purged.method = Method::GET;
+            let key = super::key::build(ctx, &purged, endpoint);
+            debug!(key = key, "Purging cache");
+            return Ok(Response::new(if self.datastore.purge(&key).await? {
+                StatusCode::OK
+            } else {
+                StatusCode::NOT_FOUND
+            }));
+        }
+        if !policy::is_cacheable_method(&request.method) {
+            return next.run(request).await;
         }
-        let key = CompactCacheKey {
-            primary: hash_key(format!("{}:{}", ctx.app_id, ctx.endpoint_id).as_str()),
-            user_tag: ip,
-            variance: variance.finalize().map(Box::new),
+        let cache_control = CacheControl::parse(request.headers());
+        let conditional = request.header(header::IF_NONE_MATCH).is_some()
+            || request.header(header::IF_MODIFIED_SINCE).is_some();
+        let is_head = request.method == Method::HEAD;
+        let store = Store {
+            datastore: self.datastore.clone(),
+            key: super::key::build(ctx, &request, endpoint),
+            default_ttl: endpoint.expires_in.convert(TimeUnit::Seconds).amount,
+            grace_period: endpoint.grace_period(),
+            tags: endpoint.tags.clone(),
+            authorized: request.header(header::AUTHORIZATION).is_some(),
+            is_head,
+            max_size: endpoint.max_size,
         };
-        let key = key.combined();
-        let ttl = endpoint.expires_in.convert(TimeUnit::Seconds).amount;
-        let etag = {
+        let (etag, cached, stale_for) = {
             use datastore::Response::*;
-            match self.datastore.fetch_cache(key.as_str()).await? {
-                Hit(cached, ttl) => {
-                    let mut response = Response::new(StatusCode::OK);
-                    response.set_body(CachedResponseBody::new(cached.response));
-                    for header_raw in cached.headers.split('\n') {
-                        let mut parts = header_raw.splitn(2, ": ");
-                        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-                            response.insert_header(
-                                TryInto::<HeaderName>::try_into(key).with_context(|| {
-                                    format!("Failed to convert header name: {}", key)
-                                })?,
-                                value,
-                            );
-                        }
+            match store.datastore.fetch_cache(store.key.as_str()).await? {
+                Hit(cached, expires_in) if !cache_control.no_cache => {
+                    return Ok(store.replay(cached, expires_in as isize, "HIT"));
+                }
+                Hit(cached, expires_in) => (
+                    cached
+                        .headers
+                        .iter()
+                        .find(|(name, _)| name == header::ETAG)
+                        .and_then(|(_, value)| value.to_str().ok())
+                        .map(str::to_string),
+                    Some((cached, expires_in as isize)),
+                    None,
+                ),
+                Stale(cached, stale_for, etag)
+                    if !cache_control.no_cache && stale_for < endpoint.stale_while_revalidate =>
+                {
+                    if let Flight::Leader(guard) = self.flights.join(&store.key) {
+                        let mut revalidation = request.clone();
+                        revalidation.method = Method::GET;
+                        let validated = Validated::Cached(cached.clone(), -(stale_for as isize));
+                        store.revalidate(next.detach(), revalidation, etag, validated, guard);
                     }
-                    response.insert_header(header::CACHE_CONTROL, format!("max-age={}", ttl));
-                    return Ok(response);
+                    return Ok(store.replay(cached, -(stale_for as isize), "STALE"));
+                }
+                Stale(cached, stale_for, etag) => {
+                    (etag, Some((cached, -(stale_for as isize))), Some(stale_for))
                 }
-                Expired(etag) => etag,
-                Miss => None,
+                Expired(etag) => (etag, None, None),
+                Miss => (None, None, None),
             }
         };
-        debug!(etag = etag, key = key, "Fetching response from origin");
+        if cache_control.only_if_cached {
+            let mut response = Response::new(StatusCode::GATEWAY_TIMEOUT);
+            response.insert_header(&headers::X_CACHE, "MISS");
+            return Ok(response);
+        }
+        let _flight = match endpoint.coalescing_timeout {
+            Some(timeout) => match self.coalesce(&store, timeout).await? {
+                Coalesced::Cached(response) => {
+                    return Ok(response);
+                }
+                Coalesced::Fetch(flight) => flight,
+            },
+            None => None,
+        };
+        debug!(
+            etag = etag,
+            key = store.key,
+            "Fetching response from origin"
+        );
+        // The client only gets a `304 Not Modified` to its own conditional request.
+        let etag = etag.filter(|_| !conditional);
+        let repeat = match (&etag, &cached) {
+            (Some(_), None) => Some(Validated::Repeat(next.detach(), request.clone())),
+            _ => None,
+        };
         if let Some(etag) = &etag {
-            request.insert_header("If-None-Match", etag.clone());
+            request.insert_header(header::IF_NONE_MATCH, etag.clone());
         }
-        debug!("Fetching response from origin for key: {}", key);
-        let origin_response = next.run(request).await?;
-        debug!("Origin response: {:?}", origin_response);
-        if origin_response.status == StatusCode::NOT_MODIFIED {
-            use datastore::Response::*;
-            if let Hit(data, ttl) = self.datastore.refresh_cache(key.as_str(), ttl).await? {
-                let mut response = Response::new(StatusCode::OK);
-                response.insert_header(header::CACHE_CONTROL, format!("max-age={}", ttl));
-                response.set_body(CachedResponseBody::new(data.response));
-                return Ok(response);
-            };
+        let (response, cached) = match (next.run(request).await, cached, stale_for) {
+            (Ok(response), Some((cached, expires_in)), Some(stale_for))
+                if response.status.is_server_error() && stale_for < endpoint.stale_if_error =>
+            {
+                debug!(status = response.status.as_u16(), "Serving stale response");
+                return Ok(store.replay(cached, expires_in, "STALE"));
+            }
+            (Err(error), Some((cached, expires_in)), Some(stale_for))
+                if stale_for < endpoint.stale_if_error =>
+            {
+                warn!("Serving stale response, origin failed: {}", error);
+                return Ok(store.replay(cached, expires_in, "STALE"));
+            }
+            (response, cached, _) => (response?, cached),
+        };
+        let validated = match (etag, cached) {
+            (Some(_), Some((cached, expires_in))) => Some(Validated::Cached(cached, expires_in)),
+            (Some(_), None) => repeat,
+            (None, _) => None,
+        };
+        store.update(validated, response).await
+    }
+}
+
+impl Middleware {
+    /// Wait for a concurrent request fetching the same response from the origin,
+    /// so that the response is served from the cache once it is stored.
+    async fn coalesce(&self, store: &Store, timeout: usize) -> Result<Coalesced> {
+        let deadline = Instant::now() + Duration::from_secs(timeout as u64);
+        match self.flights.join(&store.key) {
+            Flight::Leader(guard) => {
+                if let Some(token) = store.datastore.lock(&store.key, timeout).await? {
+                    return Ok(Coalesced::Fetch(Some(
+                        guard.with_lock(store.datastore.clone(), token),
+                    )));
+                }
+                // Another gateway is fetching the response.
+                while Instant::now() < deadline {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    if let Some(response) = store.cached().await? {
+                        return Ok(Coalesced::Cached(response));
+                    }
+                }
+                Ok(Coalesced::Fetch(Some(guard)))
+            }
+            Flight::Waiter(mut waiter) => {
+                let _ = tokio::time::timeout_at(deadline, waiter.changed()).await;
+                Ok(match store.cached().await? {
+                    Some(response) => Coalesced::Cached(response),
+                    None => Coalesced::Fetch(None),
+                })
+            }
         }
-        let mut response = Response::new(origin_response.status);
-        for (key, value) in origin_response.headers().iter() {
-            response.insert_header(key, value);
+    }
+}
+
+enum Coalesced {
+    /// The response stored by a concurrent request.
+    Cached(Response),
+    /// The response needs to be fetched from the origin, holding the flight if this request leads it.
+    Fetch(Option<Guard>),
+}
+
+/// Where and how responses to a request are cached.
+#[derive(Clone)]
+struct Store {
+    datastore: Arc<dyn Datastore + Send + Sync + 'static>,
+    key: String,
+    default_ttl: usize,
+    grace_period: usize,
+    tags: Box<[String]>,
+    authorized: bool,
+    is_head: bool,
+    max_size: usize,
+}
+
+/// What the gateway made the request to the origin conditional on,
+/// served when a `304 Not Modified` can't refresh the cached response.
+enum Validated {
+    /// The cached response, `expires_in` is negative when it is stale.
+    Cached(Cache, isize),
+    /// The request without the validator, the cached response is no longer at hand.
+    Repeat(Detached, Request),
+}
+
+/// Body of a response read from the origin to be cached.
+enum Buffered {
+    Complete(Vec<u8>),
+    /// The body is larger than the cacheable size, it is streamed through with the chunks read so far.
+    TooLarge(Body),
+}
+
+impl Store {
+    /// Refresh the cached response in a background task.
+    fn revalidate(
+        &self,
+        next: Detached,
+        mut request: Request,
+        etag: Option<String>,
+        validated: Validated,
+        flight: Guard,
+    ) {
+        let store = Self {
+            is_head: false,
+            ..self.clone()
+        };
+        if let Some(etag) = &etag {
+            request.insert_header(header::IF_NONE_MATCH, etag.clone());
         }
-        response.remove_header(header::ETAG);
-        debug!("Caching response for key: {}", key);
-        let origin_headers = origin_response
+        tokio::spawn(async move {
+            debug!(etag = etag, key = store.key, "Revalidating stale response");
+            let result = match next.run(request).await {
+                Ok(response) => store
+                    .update(etag.is_some().then_some(validated), response)
+                    .await
+                    .map(|_| ()),
+                Err(error) => Err(error),
+            };
+            if let Err(error) = result {
+                warn!("Failed to revalidate stale response: {}", error);
+            }
+            drop(flight);
+        });
+    }
+
+    /// The fresh response from the cache, if there is one.
+    async fn cached(&self) -> Result<Option<Response>> {
+        Ok(match self.datastore.fetch_cache(self.key.as_str()).await? {
+            datastore::Response::Hit(cached, expires_in) => {
+                Some(self.replay(cached, expires_in as isize, "HIT"))
+            }
+            _ => None,
+        })
+    }
+
+    /// Refresh the cached response on `304 Not Modified` or store the response from the origin.
+    async fn update(&self, validated: Option<Validated>, response: Response) -> Result<Response> {
+        debug!("Origin response: {:?}", response);
+        let mut response = match validated {
+            Some(validated) if response.status == StatusCode::NOT_MODIFIED => {
+                if let Some(response) = self.refresh(response.headers()).await? {
+                    return Ok(response);
+                }
+                match validated {
+                    Validated::Cached(cached, expires_in) => {
+                        debug!("Serving the validated response for key: {}", self.key);
+                        return Ok(self.replay(cached, expires_in, "REVALIDATED"));
+                    }
+                    Validated::Repeat(next, request) => {
+                        debug!(
+                            "Repeating the request without validator for key: {}",
+                            self.key
+                        );
+                        next.run(request).await?
+                    }
+                }
+            }
+            _ => response,
+        };
+        // Surrogate keys are only meant for the cache.
+        let surrogate_tags = response
             .headers()
+            .get_all(&headers::SURROGATE_KEY)
             .iter()
-            .map(|(key, value)| key.to_string() + ": " + value.to_str().unwrap_or_default())
-            .reduce(|mut a, b| {
-                a.push('\n');
-                a.push_str(b.as_str());
-                a
-            })
-            .unwrap_or_default();
-        let body = if let Some(len) = origin_response.get_content_length() {
-            origin_response.body().unwrap().read_all(len).await?
-        } else {
-            String::new()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(str::split_whitespace)
+            .map(datastore::surrogate_tag)
+            .collect::<Vec<_>>();
+        response.remove_header(&headers::SURROGATE_KEY);
+        let age = policy::age(response.headers());
+        let lifetime = policy::freshness_lifetime(
+            response.status,
+            response.headers(),
+            self.authorized,
+            self.default_ttl,
+        )
+        .filter(|lifetime| *lifetime > age && !self.is_head);
+        response.insert_header(&headers::X_CACHE, "MISS");
+        let lifetime = match lifetime {
+            Some(lifetime) => lifetime,
+            None => {
+                return Ok(response);
+            }
         };
-
-        debug!("Response body: {}", body);
+        let body = match response.take_stream() {
+            Some(body) => match self.buffer(body).await? {
+                Buffered::Complete(body) => body,
+                Buffered::TooLarge(body) => {
+                    debug!("Response is too large to be cached for key: {}", self.key);
+                    response.set_stream(body);
+                    return Ok(response);
+                }
+            },
+            None => Vec::new(),
+        };
+        debug!("Caching response for key: {}", self.key);
+        let etag = response
+            .header(header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let headers = response
+            .headers()
+            .iter()
+            .filter(|(key, _)| !is_not_stored(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let tags = self.tags.iter().cloned().chain(surrogate_tags).collect();
         self.datastore
-            .save_cache(key.as_str(), body.clone(), origin_headers, etag, ttl)
+            .save_cache(
+                self.key.as_str(),
+                Cache::new(response.status, headers, body.clone()).with_tags(tags),
+                etag,
+                lifetime - age,
+                self.grace_period,
+            )
             .await?;
-        response.set_body(CachedResponseBody::new(body));
-        response.insert_header(header::CACHE_CONTROL, format!("max-age={}", ttl));
+        response.remove_header(header::ETAG);
+        response.set_stream(Body::from_bytes(body));
+        if response.header(header::CACHE_CONTROL).is_none() {
+            response.insert_header(header::CACHE_CONTROL, format!("max-age={}", lifetime));
+        }
         Ok(response)
     }
+
+    /// Refresh the cached response with the freshness of the `304 Not Modified` headers,
+    /// `None` when the response is not cacheable or no longer in the cache.
+    async fn refresh(&self, headers: &HeaderMap) -> Result<Option<Response>> {
+        let lifetime = match policy::freshness_lifetime(
+            StatusCode::OK,
+            headers,
+            self.authorized,
+            self.default_ttl,
+        ) {
+            Some(lifetime) => lifetime,
+            None => {
+                return Ok(None);
+            }
+        };
+        let expires_at = chrono::Utc::now().timestamp() as usize + lifetime;
+        Ok(
+            match self
+                .datastore
+                .refresh_cache(self.key.as_str(), expires_at, self.grace_period)
+                .await?
+            {
+                datastore::Response::Hit(cached, expires_in) => {
+                    Some(self.replay(cached, expires_in as isize, "REVALIDATED"))
+                }
+                _ => None,
+            },
+        )
+    }
+
+    /// Read the body up to the cacheable size.
+    async fn buffer(&self, mut body: Body) -> Result<Buffered> {
+        if body.length().is_some_and(|length| length > self.max_size) {
+            return Ok(Buffered::TooLarge(body));
+        }
+        let mut buffer = Vec::with_capacity(body.length().unwrap_or_default());
+        while let Some(chunk) = body.next_chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            if buffer.len() > self.max_size {
+                let length = body.length();
+                let rest = stream::once(async move { Ok(buffer) }).chain(body);
+                return Ok(Buffered::TooLarge(Body::new(rest, length)));
+            }
+        }
+        Ok(Buffered::Complete(buffer))
+    }
+
+    /// Build the response from the cache with its original status and headers.
+    /// `expires_in` is negative when the response is stale.
+    fn replay(&self, cached: Cache, expires_in: isize, x_cache: &'static str) -> Response {
+        let lifetime = policy::freshness_lifetime(
+            cached.status,
+            &cached.headers.iter().cloned().collect::<HeaderMap>(),
+            false,
+            self.default_ttl,
+        )
+        .unwrap_or(self.default_ttl);
+        let mut response = Response::new(cached.status);
+        for (key, value) in cached.headers {
+            response.headers_mut().append(key, value);
+        }
+        if self.is_head {
+            response.insert_header(header::CONTENT_LENGTH, cached.body.len());
+        } else {
+            response.set_stream(Body::from_bytes(cached.body));
+        }
+        if response.header(header::CACHE_CONTROL).is_none() {
+            response.insert_header(header::CACHE_CONTROL, format!("max-age={}", lifetime));
+        }
+        response.insert_header(
+            header::AGE,
+            (lifetime as isize).saturating_sub(expires_in).max(0),
+        );
+        response.insert_header(&headers::X_CACHE, x_cache);
+        response
+    }
+}
+
+/// The body is stored decoded, its length is set again when it is replayed.
+/// The age is computed when the response is replayed.
+fn is_not_stored(name: &HeaderName) -> bool {
+    name == header::CONTENT_LENGTH
+        || name == header::TRANSFER_ENCODING
+        || name == header::AGE
+        || name == &headers::X_CACHE
 }
@@ -1,12 +1,16 @@
+mod admin;
 mod builder;
 pub mod config;
 mod context;
 pub mod datastore;
+mod flight;
+mod key;
 mod middleware;
-mod response;
+mod policy;
 
 use std::collections::HashMap;
 
+pub use admin::Admin;
 use builder::MiddlewareBuilder;
 use datastore::Datastore;
 pub(crate) use middleware::Middleware;
@@ -48,7 +52,16 @@ impl Builder {
         let config: Config = self
             .0
             .into_iter()
-            .map(|(app, config)| (app, ((), config).into()))
+            .map(|(app, config)| {
+                let config = config
+                    .into_iter()
+                    .map(|(id, endpoint)| {
+                        let endpoint = endpoint.with_names(&app, &id);
+                        (id, endpoint)
+                    })
+                    .collect::<HashMap<_, _>>();
+                (app, ((), config).into())
+            })
             .collect::<HashMap<_, _>>()
             .into();
         MiddlewareBuilder::new(config, datastore)
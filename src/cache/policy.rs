@@ -0,0 +1,167 @@
+//! Storage and freshness rules of a shared cache, following RFC 9111.
+
+use http::{header, HeaderMap, Method, StatusCode};
+
+/// Directives of a `Cache-Control` header relevant to a shared cache.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub public: bool,
+    pub only_if_cached: bool,
+    pub max_age: Option<usize>,
+    pub s_maxage: Option<usize>,
+}
+
+impl CacheControl {
+    pub fn parse(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+        let directives = headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = value.and_then(|value| value.parse::<usize>().ok());
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "private" => cache_control.private = true,
+                "public" => cache_control.public = true,
+                "only-if-cached" => cache_control.only_if_cached = true,
+                "max-age" => cache_control.max_age = seconds,
+                "s-maxage" => cache_control.s_maxage = seconds,
+                _ => {}
+            }
+        }
+        // HTTP/1.0 caches only understand `Pragma: no-cache`.
+        if headers
+            .get(header::PRAGMA)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("no-cache"))
+        {
+            cache_control.no_cache = true;
+        }
+        cache_control
+    }
+}
+
+/// Whether responses to the method may be served from the cache.
+pub fn is_cacheable_method(method: &Method) -> bool {
+    method == Method::GET || method == Method::HEAD
+}
+
+/// Whether the status is heuristically cacheable, so that it may be stored without explicit freshness.
+pub fn is_cacheable_status(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+/// The freshness lifetime of the response in seconds, `None` when the response must not be stored.
+/// The endpoint default is used when the origin does not set one.
+pub fn freshness_lifetime(
+    status: StatusCode,
+    headers: &HeaderMap,
+    authorized: bool,
+    default: usize,
+) -> Option<usize> {
+    let cache_control = CacheControl::parse(headers);
+    if !is_cacheable_status(status)
+        || cache_control.no_store
+        || cache_control.no_cache
+        || cache_control.private
+        || headers.contains_key(header::SET_COOKIE)
+        || (authorized && !cache_control.public && cache_control.s_maxage.is_none())
+    {
+        return None;
+    }
+    let lifetime = cache_control
+        .s_maxage
+        .or(cache_control.max_age)
+        .unwrap_or(default);
+    Some(lifetime).filter(|lifetime| *lifetime > 0)
+}
+
+/// The age of the response when it was received from the origin.
+pub fn age(headers: &HeaderMap) -> usize {
+    headers
+        .get(header::AGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn headers(values: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        values
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let cache_control = CacheControl::parse(&headers(&[(
+            header::CACHE_CONTROL,
+            "public, max-age=60, S-MAXAGE=\"120\", only-if-cached",
+        )]));
+        assert_eq!(
+            cache_control,
+            CacheControl {
+                public: true,
+                only_if_cached: true,
+                max_age: Some(60),
+                s_maxage: Some(120),
+                ..Default::default()
+            }
+        );
+        let cache_control = CacheControl::parse(&headers(&[(header::PRAGMA, "no-cache")]));
+        assert!(cache_control.no_cache);
+    }
+
+    #[test]
+    fn test_freshness_lifetime() {
+        let ok = StatusCode::OK;
+        assert_eq!(
+            freshness_lifetime(ok, &HeaderMap::new(), false, 10),
+            Some(10)
+        );
+        let max_age = headers(&[(header::CACHE_CONTROL, "max-age=60")]);
+        assert_eq!(freshness_lifetime(ok, &max_age, false, 10), Some(60));
+        let s_maxage = headers(&[(header::CACHE_CONTROL, "max-age=60, s-maxage=30")]);
+        assert_eq!(freshness_lifetime(ok, &s_maxage, false, 10), Some(30));
+        assert_eq!(freshness_lifetime(ok, &s_maxage, true, 10), Some(30));
+        assert_eq!(freshness_lifetime(ok, &max_age, true, 10), None);
+        let max_age_zero = headers(&[(header::CACHE_CONTROL, "max-age=0")]);
+        assert_eq!(freshness_lifetime(ok, &max_age_zero, false, 10), None);
+        for value in ["no-store", "private", "no-cache"] {
+            let headers = headers(&[(header::CACHE_CONTROL, value)]);
+            assert_eq!(freshness_lifetime(ok, &headers, false, 10), None);
+        }
+        let cookie = headers(&[(header::SET_COOKIE, "session=1")]);
+        assert_eq!(freshness_lifetime(ok, &cookie, false, 10), None);
+        let error = StatusCode::INTERNAL_SERVER_ERROR;
+        assert_eq!(
+            freshness_lifetime(error, &HeaderMap::new(), false, 10),
+            None
+        );
+        let not_found = StatusCode::NOT_FOUND;
+        assert_eq!(
+            freshness_lifetime(not_found, &HeaderMap::new(), false, 10),
+            Some(10)
+        );
+    }
+}
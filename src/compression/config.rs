@@ -0,0 +1,90 @@
+pub use super::encoder::Encoding;
+
+#[derive(Debug)]
+pub struct App {
+    pub enabled: bool,
+    /// Supported encodings in the order of preference.
+    pub encodings: Vec<Encoding>,
+    pub gzip_level: u32,
+    pub brotli_level: u32,
+    pub zstd_level: i32,
+    /// Responses with a known length below the threshold are not compressed.
+    pub min_size: usize,
+    /// Compressed media types, a type ending with `/` matches all of its subtypes (e.g. `text/`).
+    pub content_types: Vec<String>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            encodings: vec![Encoding::Zstd, Encoding::Brotli, Encoding::Gzip],
+            gzip_level: 6,
+            brotli_level: 4,
+            zstd_level: 3,
+            min_size: 1024,
+            content_types: [
+                "text/",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "application/wasm",
+                "application/manifest+json",
+                "image/svg+xml",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pass responses of the app through without compression.
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn with_encodings(mut self, encodings: Vec<Encoding>) -> Self {
+        self.encodings = encodings;
+        self
+    }
+
+    /// Set the gzip compression level (0-9).
+    pub fn with_gzip_level(mut self, level: u32) -> Self {
+        self.gzip_level = level;
+        self
+    }
+
+    /// Set the brotli compression quality (0-11).
+    pub fn with_brotli_level(mut self, level: u32) -> Self {
+        self.brotli_level = level;
+        self
+    }
+
+    /// Set the zstd compression level (1-22).
+    pub fn with_zstd_level(mut self, level: i32) -> Self {
+        self.zstd_level = level;
+        self
+    }
+
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn with_content_types(mut self, content_types: Vec<String>) -> Self {
+        self.content_types = content_types;
+        self
+    }
+
+    pub fn add_content_type(mut self, content_type: &str) -> Self {
+        self.content_types.push(content_type.to_string());
+        self
+    }
+}
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+
+use crate::{ConfigToContext, Result};
+
+use super::{config, encoder::Encoding};
+
+#[derive(Debug)]
+pub struct App {
+    pub enabled: bool,
+    pub encodings: Box<[Encoding]>,
+    pub gzip_level: u32,
+    pub brotli_level: u32,
+    pub zstd_level: i32,
+    pub min_size: usize,
+    pub content_types: Box<[Box<str>]>,
+}
+
+impl App {
+    pub fn level(&self, encoding: Encoding) -> i32 {
+        match encoding {
+            Encoding::Gzip => self.gzip_level as i32,
+            Encoding::Brotli => self.brotli_level as i32,
+            Encoding::Zstd => self.zstd_level,
+        }
+    }
+
+    /// Whether responses of the given `Content-Type` are compressed.
+    pub fn is_compressible(&self, content_type: &str) -> bool {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.content_types.iter().any(|content_type| {
+            if content_type.ends_with('/') {
+                media_type.starts_with(content_type.as_ref())
+            } else {
+                media_type == content_type.as_ref()
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl ConfigToContext for config::App {
+    type Context = App;
+
+    async fn into_context(self) -> Result<Self::Context> {
+        Ok(App {
+            enabled: self.enabled,
+            encodings: self.encodings.into_boxed_slice(),
+            gzip_level: self.gzip_level,
+            brotli_level: self.brotli_level,
+            zstd_level: self.zstd_level,
+            min_size: self.min_size,
+            content_types: self
+                .content_types
+                .into_iter()
+                .map(|content_type| content_type.to_ascii_lowercase().into_boxed_str())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_is_compressible() {
+        let app = config::App::new().into_context().await.unwrap();
+        assert!(app.is_compressible("text/html; charset=utf-8"));
+        assert!(app.is_compressible("Application/JSON"));
+        assert!(!app.is_compressible("image/png"));
+        assert!(!app.is_compressible("application/json-seq"));
+    }
+}
@@ -0,0 +1,212 @@
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::http::Transform;
+
+/// Content coding supported by the compression middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl Encoding {
+    /// The name of the encoding in `Accept-Encoding` and `Content-Encoding` headers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+}
+
+/// Pick the encoding with the highest quality in the `Accept-Encoding` header.
+/// Ties are resolved by the order of the supported encodings.
+pub fn negotiate(accept_encoding: &str, supported: &[Encoding]) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in supported {
+        let quality = quality(accept_encoding, encoding.name());
+        if quality > 0.0 && !best.is_some_and(|(_, best)| quality <= best) {
+            best = Some((*encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+fn quality(accept_encoding: &str, name: &str) -> f32 {
+    let mut wildcard = 0.0;
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let coding = params.next().unwrap_or_default().trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|quality| quality.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if coding.eq_ignore_ascii_case(name) {
+            return quality;
+        }
+        if coding == "*" {
+            wildcard = quality;
+        }
+    }
+    wildcard
+}
+
+/// Compressed output shared with the encoder writing it.
+#[derive(Debug, Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Inner {
+    Gzip(GzEncoder<Output>),
+    Brotli(Box<brotli::CompressorWriter<Output>>),
+    Zstd(zstd::stream::write::Encoder<'static, Output>),
+}
+
+/// Streaming encoder of a body, returning the compressed output produced by each chunk.
+pub struct Encoder {
+    /// `None` once the stream is finished.
+    inner: Option<Inner>,
+    output: Output,
+}
+
+impl std::fmt::Debug for Encoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Encoder").finish_non_exhaustive()
+    }
+}
+
+impl Encoder {
+    pub fn new(encoding: Encoding, level: i32) -> io::Result<Self> {
+        let output = Output::default();
+        let inner = match encoding {
+            Encoding::Gzip => Inner::Gzip(GzEncoder::new(
+                output.clone(),
+                Compression::new(level.clamp(0, 9) as u32),
+            )),
+            Encoding::Brotli => Inner::Brotli(Box::new(brotli::CompressorWriter::new(
+                output.clone(),
+                4096,
+                level.clamp(0, 11) as u32,
+                22,
+            ))),
+            Encoding::Zstd => Inner::Zstd(zstd::stream::write::Encoder::new(
+                output.clone(),
+                level.clamp(1, 22),
+            )?),
+        };
+        Ok(Self {
+            inner: Some(inner),
+            output,
+        })
+    }
+}
+
+impl Transform for Encoder {
+    fn transform(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self.inner.as_mut() {
+            Some(Inner::Gzip(encoder)) => encoder.write_all(&chunk)?,
+            Some(Inner::Brotli(encoder)) => encoder.write_all(&chunk)?,
+            Some(Inner::Zstd(encoder)) => encoder.write_all(&chunk)?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "encoder is already finished",
+                ));
+            }
+        }
+        Ok(self.output.take())
+    }
+
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        match self.inner.take() {
+            Some(Inner::Gzip(encoder)) => {
+                encoder.finish()?;
+            }
+            Some(Inner::Brotli(encoder)) => {
+                encoder.into_inner();
+            }
+            Some(Inner::Zstd(encoder)) => {
+                encoder.finish()?;
+            }
+            None => {}
+        }
+        Ok(self.output.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use std::io::Read;
+
+    use super::*;
+
+    fn compress(encoding: Encoding, data: &[u8]) -> Vec<u8> {
+        let mut encoder = Encoder::new(encoding, 5).unwrap();
+        let mut output = Vec::new();
+        for chunk in data.chunks(1000) {
+            output.extend(encoder.transform(chunk.to_vec()).unwrap());
+        }
+        output.extend(encoder.finish().unwrap());
+        output
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = "hello world ".repeat(1000).into_bytes();
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&compress(Encoding::Gzip, &data)[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let mut decoded = Vec::new();
+        brotli::Decompressor::new(&compress(Encoding::Brotli, &data)[..], 4096)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+
+        let decoded = zstd::decode_all(&compress(Encoding::Zstd, &data)[..]).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let supported = [Encoding::Zstd, Encoding::Brotli, Encoding::Gzip];
+        assert_eq!(
+            negotiate("gzip, deflate, br", &supported),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(
+            negotiate("gzip;q=1.0, br;q=0.5", &supported),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(negotiate("*", &supported), Some(Encoding::Zstd));
+        assert_eq!(negotiate("*;q=0, gzip", &supported), Some(Encoding::Gzip));
+        assert_eq!(negotiate("identity", &supported), None);
+        assert_eq!(negotiate("gzip;q=0", &supported), None);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    gateway::{middleware::Middleware as TMiddleware, next::Next, Result},
+    http::{Body, HeaderMapExt, Request, Response},
+    Ctx,
+};
+use async_trait::async_trait;
+use http::{header, Method, StatusCode};
+
+use super::encoder::Encoder;
+
+/// Bodies with a known length up to the limit are compressed in advance to keep `Content-Length`.
+const BUFFER_LIMIT: usize = 256 * 1024;
+
+#[derive(Debug)]
+pub struct Middleware(super::Context);
+
+impl Middleware {
+    pub(crate) fn new(ctx: super::Context) -> Self {
+        Self(ctx)
+    }
+}
+
+#[async_trait]
+impl TMiddleware for Middleware {
+    async fn run(&self, ctx: &Ctx, request: Request, next: Next<'_>) -> Result<Response> {
+        let config = match self.0.get(ctx.app_id) {
+            Some(config) if config.global().enabled => config.global(),
+            _ => {
+                return next.run(request).await;
+            }
+        };
+        let encoding = request
+            .header(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| super::encoder::negotiate(value, &config.encodings));
+        let is_head = request.method == Method::HEAD;
+        let mut response = next.run(request).await?;
+        if !is_eligible(&response)
+            || !response
+                .header(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| config.is_compressible(value))
+        {
+            return Ok(response);
+        }
+        let length = response.get_content_length();
+        if length.is_some_and(|length| length < config.min_size) {
+            return Ok(response);
+        }
+        add_vary(&mut response);
+        let encoding = match encoding {
+            Some(encoding) if !is_head => encoding,
+            _ => {
+                return Ok(response);
+            }
+        };
+        let body = match response.take_stream() {
+            Some(body) => body,
+            None => {
+                return Ok(response);
+            }
+        };
+        let encoder = Encoder::new(encoding, config.level(encoding))?;
+        response.insert_header(header::CONTENT_ENCODING, encoding.name());
+        response.remove_header(header::ACCEPT_RANGES);
+        weaken_etag(&mut response);
+        let body = body.transform(encoder);
+        if length.is_some_and(|length| length <= BUFFER_LIMIT) {
+            let compressed = body.collect(usize::MAX).await?;
+            response.set_stream(Body::from_bytes(compressed));
+        } else {
+            response.set_stream(body);
+        }
+        Ok(response)
+    }
+}
+
+/// Whether the response may be compressed regardless of its content type and size.
+fn is_eligible(response: &Response) -> bool {
+    response.status.is_success()
+        && response.status != StatusCode::NO_CONTENT
+        && response.status != StatusCode::PARTIAL_CONTENT
+        && response.header(header::CONTENT_RANGE).is_none()
+        // Chunked bodies are decoded when they are streamed, other codings are not.
+        && response.header(header::TRANSFER_ENCODING).is_none_or(|value| {
+            value.to_str().is_ok_and(|value| {
+                value
+                    .split(',')
+                    .all(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+            })
+        })
+        && response
+            .header(header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .is_none_or(|value| value.eq_ignore_ascii_case("identity"))
+        && !response
+            .header(header::CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+            })
+}
+
+fn add_vary(response: &mut Response) {
+    let vary = response
+        .header(header::VARY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    match vary {
+        Some(vary)
+            if vary.split(',').any(|name| {
+                let name = name.trim();
+                name == "*" || name.eq_ignore_ascii_case("accept-encoding")
+            }) => {}
+        Some(vary) => response.insert_header(header::VARY, format!("{}, Accept-Encoding", vary)),
+        None => response.insert_header(header::VARY, "Accept-Encoding"),
+    }
+}
+
+/// The compressed representation is not byte-for-byte identical to the original one.
+fn weaken_etag(response: &mut Response) {
+    let etag = response
+        .header(header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .map(|etag| format!("W/{}", etag));
+    if let Some(etag) = etag {
+        response.insert_header(header::ETAG, etag);
+    }
+}
@@ -0,0 +1,52 @@
+mod builder;
+pub mod config;
+mod context;
+mod encoder;
+mod middleware;
+
+use std::collections::HashMap;
+
+pub use builder::MiddlewareBuilder;
+pub(crate) use middleware::Middleware;
+
+use crate::{MiddlewareConfig, MiddlewareCtx};
+
+type Config = MiddlewareConfig<config::App, ()>;
+type Context = MiddlewareCtx<context::App, ()>;
+
+/// Compression of responses of the configured apps, negotiated with `Accept-Encoding`.
+#[derive(Debug, Default)]
+pub struct Builder(HashMap<String, config::App>);
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_app(mut self, app: &str, config: config::App) -> Self {
+        self.0.insert(app.to_string(), config);
+        self
+    }
+
+    pub fn build(self) -> MiddlewareBuilder {
+        let config: Config = self
+            .0
+            .into_iter()
+            .map(|(app, config)| (app, (config, HashMap::new()).into()))
+            .collect::<HashMap<_, _>>()
+            .into();
+        MiddlewareBuilder::new(config)
+    }
+}
+
+impl From<HashMap<String, config::App>> for Builder {
+    fn from(apps: HashMap<String, config::App>) -> Self {
+        Self(apps)
+    }
+}
+
+impl FromIterator<(String, config::App)> for Builder {
+    fn from_iter<T: IntoIterator<Item = (String, config::App)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
@@ -0,0 +1,38 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::{Result, Service};
+
+use super::Datastore;
+
+pub struct MiddlewareBuilder {
+    config: super::Config,
+    datastore: Arc<dyn Datastore + Send + Sync + 'static>,
+}
+
+impl MiddlewareBuilder {
+    pub fn new(
+        config: impl Into<super::Config>,
+        datastore: impl Datastore + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            config: config.into(),
+            datastore: Arc::new(datastore),
+        }
+    }
+}
+
+#[async_trait]
+impl crate::MiddlewareBuilder for MiddlewareBuilder {
+    async fn build(
+        self: Box<Self>,
+        ids: &[String],
+        routers: &HashMap<String, Vec<String>>,
+    ) -> Result<Service> {
+        Ok(Box::new(super::Middleware::new(
+            self.config.into_context(ids, routers).await?,
+            self.datastore,
+        )))
+    }
+}
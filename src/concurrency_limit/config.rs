@@ -0,0 +1,70 @@
+use crate::{
+    rate_limit::config::{Key, MissingKey},
+    utils::time::Time,
+};
+
+/// Limits of the requests in flight, the limits of an app apply to all its endpoints
+/// in addition to the limits of the endpoint.
+#[derive(Debug, Default)]
+pub struct Limits {
+    pub limits: Vec<Limit>,
+}
+
+impl Limits {
+    pub fn new(limits: Vec<Limit>) -> Self {
+        Self { limits }
+    }
+}
+
+#[derive(Debug)]
+pub struct Limit {
+    /// Maximum of the requests in flight at once.
+    pub max: usize,
+    pub scope: Scope,
+    /// Requests over the limit wait in the queue instead of being rejected right away.
+    pub queue: Option<Queue>,
+    /// What to do with requests missing the key of a limit with the key scope.
+    pub missing_key: MissingKey,
+}
+
+impl Limit {
+    pub fn new(max: usize, scope: Scope) -> Self {
+        Self {
+            max,
+            scope,
+            queue: None,
+            missing_key: MissingKey::default(),
+        }
+    }
+
+    pub fn with_queue(mut self, size: usize, timeout: Time) -> Self {
+        self.queue = Some(Queue { size, timeout });
+        self
+    }
+
+    pub fn with_missing_key(mut self, policy: MissingKey) -> Self {
+        self.missing_key = policy;
+        self
+    }
+}
+
+/// Requests counted together against a limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// All requests of the app, rejected with `503 Service Unavailable`.
+    App,
+    /// All requests of the endpoint, rejected with `503 Service Unavailable`.
+    Endpoint,
+    /// Requests of the endpoint with the same key, e.g. from the same client,
+    /// rejected with `429 Too Many Requests`. Requests missing the key are handled by
+    /// the missing key policy of the limit.
+    Key(Key),
+}
+
+/// Bounded FIFO queue of the requests waiting for a request in flight to finish.
+#[derive(Debug)]
+pub struct Queue {
+    pub size: usize,
+    /// How long a request waits before it is rejected.
+    pub timeout: Time,
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::{rate_limit::config::MissingKey, utils::time::TimeUnit, ConfigToContext, Result};
+
+use super::{config, Scope};
+
+#[derive(Debug)]
+pub struct Limits {
+    pub limits: Box<[Limit]>,
+}
+
+#[derive(Debug)]
+pub struct Limit {
+    pub max: usize,
+    pub scope: Scope,
+    pub queue: Option<Queue>,
+    pub missing_key: MissingKey,
+}
+
+#[derive(Debug)]
+pub struct Queue {
+    pub size: usize,
+    pub timeout: Duration,
+}
+
+#[async_trait]
+impl ConfigToContext for config::Limits {
+    type Context = Limits;
+
+    async fn into_context(self) -> Result<Self::Context> {
+        Ok(Limits {
+            limits: self
+                .limits
+                .into_iter()
+                .map(|limit| Limit {
+                    max: limit.max,
+                    scope: limit.scope,
+                    queue: limit.queue.map(|queue| Queue {
+                        size: queue.size,
+                        timeout: Duration::from_secs(
+                            queue.timeout.convert(TimeUnit::Seconds).amount as u64,
+                        ),
+                    }),
+                    missing_key: limit.missing_key,
+                })
+                .collect(),
+        })
+    }
+}
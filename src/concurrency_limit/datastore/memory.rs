@@ -0,0 +1,68 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+
+use super::Datastore;
+
+/// Datastore counting the requests in flight in memory, limits are not shared between gateways.
+#[derive(Debug, Default)]
+pub struct InMemoryDatastore {
+    permits: Mutex<HashMap<String, usize>>,
+}
+
+impl InMemoryDatastore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Datastore for InMemoryDatastore {
+    async fn acquire(&self, key: &str, max: usize) -> Result<Option<String>> {
+        let mut permits = self
+            .permits
+            .lock()
+            .map_err(|_| anyhow!("Failed to acquire lock for key: {}", key))?;
+        let taken = permits.entry(key.to_string()).or_default();
+        if *taken >= max {
+            return Ok(None);
+        }
+        *taken += 1;
+        Ok(Some(String::new()))
+    }
+
+    async fn release(&self, key: &str, _permit: &str) -> Result<()> {
+        let mut permits = self
+            .permits
+            .lock()
+            .map_err(|_| anyhow!("Failed to acquire lock for key: {}", key))?;
+        if let Some(taken) = permits.get_mut(key) {
+            *taken = taken.saturating_sub(1);
+            if *taken == 0 {
+                permits.remove(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_permits() {
+        let datastore = InMemoryDatastore::new();
+        let permit = datastore.acquire("key", 2).await.unwrap().unwrap();
+        assert!(datastore.acquire("key", 2).await.unwrap().is_some());
+        assert!(datastore.acquire("key", 2).await.unwrap().is_none());
+        assert!(datastore.acquire("other", 2).await.unwrap().is_some());
+        datastore.release("key", &permit).await.unwrap();
+        assert!(datastore.acquire("key", 2).await.unwrap().is_some());
+        datastore.release("key", &permit).await.unwrap();
+        datastore.release("key", &permit).await.unwrap();
+        assert_eq!(datastore.permits.lock().unwrap().get("key"), None);
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+mod memory;
+mod redis;
+
+pub use memory::InMemoryDatastore;
+pub use redis::RedisDatastore;
+
+/// Counting semaphores of the requests in flight.
+#[async_trait]
+pub trait Datastore {
+    /// Take a permit of the semaphore under the key if fewer than `max` are taken,
+    /// returns the id of the permit to release it with.
+    async fn acquire(&self, key: &str, max: usize) -> Result<Option<String>>;
+
+    /// Keep the permit from being released after a lease while it is held.
+    async fn renew(&self, _key: &str, _permit: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// How often held permits are renewed, `None` when they are held until released.
+    fn renewal(&self) -> Option<Duration> {
+        None
+    }
+
+    async fn release(&self, key: &str, permit: &str) -> Result<()>;
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bb8_redis::{
+    bb8::Pool,
+    redis::{self, AsyncCommands},
+    RedisConnectionManager,
+};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::Datastore;
+
+/// Default lease of a permit, 5 minutes.
+const DEFAULT_LEASE: Duration = Duration::from_secs(300);
+
+/// Takes the key of the semaphore, the current timestamp and the lease in milliseconds,
+/// the maximum of the permits and the id of the new permit, returns whether it was taken.
+const ACQUIRE: &str = r"
+local now = tonumber(ARGV[1])
+local lease = tonumber(ARGV[2])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - lease)
+if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[3]) then
+    return 0
+end
+redis.call('ZADD', KEYS[1], now, ARGV[4])
+redis.call('PEXPIRE', KEYS[1], lease)
+return 1
+";
+
+/// Takes the key of the semaphore, the current timestamp and the lease in milliseconds
+/// and the id of the permit, moves the permit to the current timestamp if it is still held.
+const RENEW: &str = r"
+redis.call('ZADD', KEYS[1], 'XX', ARGV[1], ARGV[3])
+redis.call('PEXPIRE', KEYS[1], ARGV[2])
+";
+
+/// Datastore keeping the semaphores in Redis, shared by all gateways using the server.
+///
+/// Permits are kept in a sorted set by the time they were taken or last renewed. Held permits are
+/// renewed several times per lease, permits older than the lease are released, so gateways which
+/// stopped without releasing their permits do not block the semaphore.
+pub struct RedisDatastore {
+    pool: Pool<RedisConnectionManager>,
+    lease: Duration,
+    acquire: redis::Script,
+    renew: redis::Script,
+    instance: String,
+    permits: AtomicU64,
+}
+
+impl RedisDatastore {
+    pub fn new(pool: Pool<RedisConnectionManager>) -> Self {
+        Self {
+            pool,
+            lease: DEFAULT_LEASE,
+            acquire: redis::Script::new(ACQUIRE),
+            renew: redis::Script::new(RENEW),
+            instance: crate::utils::instance_id(),
+            permits: AtomicU64::new(0),
+        }
+    }
+
+    /// Time after which a permit is released when it is no longer renewed.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+}
+
+#[async_trait]
+impl Datastore for RedisDatastore {
+    async fn acquire(&self, key: &str, max: usize) -> Result<Option<String>> {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let permit = format!(
+            "{}-{}",
+            self.instance,
+            self.permits.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        self.acquire
+            .key(key)
+            .arg(now)
+            .arg(self.lease.as_millis() as u64)
+            .arg(max)
+            .arg(&permit)
+            .invoke_async(&mut *conn)
+            .await
+            .map(|acquired: bool| acquired.then_some(permit))
+            .with_context(|| format!("Failed to acquire permit for key: {}", key))
+    }
+
+    async fn renew(&self, key: &str, permit: &str) -> Result<()> {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        self.renew
+            .key(key)
+            .arg(now)
+            .arg(self.lease.as_millis() as u64)
+            .arg(permit)
+            .invoke_async::<_, ()>(&mut *conn)
+            .await
+            .with_context(|| format!("Failed to renew permit for key: {}", key))
+    }
+
+    fn renewal(&self) -> Option<Duration> {
+        Some(self.lease / 3)
+    }
+
+    async fn release(&self, key: &str, permit: &str) -> Result<()> {
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        conn.zrem(key, permit)
+            .await
+            .map(|_: usize| ())
+            .with_context(|| format!("Failed to release permit for key: {}", key))
+    }
+}
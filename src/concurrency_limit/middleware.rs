@@ -0,0 +1,352 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use super::{
+    context,
+    queue::{Queues, Ticket},
+    Datastore, Scope,
+};
+use crate::{
+    gateway::{middleware::Middleware as TMiddleware, next::Next, Result},
+    http::{response::ResponseBody, stream::WriteHalf, Request, Response},
+    rate_limit::{config::MissingKey, key},
+    Ctx,
+};
+use async_trait::async_trait;
+use essentials::warn;
+use http::StatusCode;
+use tokio::{
+    io,
+    task::JoinHandle,
+    time::{interval_at, timeout_at, Instant},
+};
+
+/// How often the head of a queue checks for permits released by other gateways.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Middleware {
+    ctx: super::Context,
+    semaphores: Semaphores,
+}
+
+/// Semaphores of the datastore with the queues of the requests waiting for their permits.
+#[derive(Clone)]
+struct Semaphores {
+    datastore: Arc<dyn Datastore + Send + Sync + 'static>,
+    queues: Queues,
+}
+
+/// Permits held by a request, released when it is dropped.
+struct Permits {
+    semaphores: Semaphores,
+    permits: Vec<(String, String)>,
+    renewal: Option<JoinHandle<()>>,
+}
+
+impl Debug for Permits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Permits")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
+
+/// Body of a response holding the permits of the request until it is sent or dropped,
+/// so that streamed responses count against the limits until they are complete.
+#[derive(Debug)]
+struct PermitBody {
+    body: Box<dyn ResponseBody + Send + Sync + 'static>,
+    _permits: Permits,
+}
+
+#[async_trait]
+impl ResponseBody for PermitBody {
+    async fn read_all(self: Box<Self>, len: usize) -> io::Result<String> {
+        self.body.read_all(len).await
+    }
+
+    async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.body.read_chunk().await
+    }
+
+    async fn copy_to<'a>(
+        &mut self,
+        writer: &'a mut WriteHalf,
+        length: Option<usize>,
+    ) -> io::Result<()> {
+        self.body.copy_to(writer, length).await
+    }
+}
+
+impl Middleware {
+    pub(crate) fn new(
+        ctx: super::Context,
+        datastore: Arc<dyn Datastore + Send + Sync + 'static>,
+    ) -> Self {
+        Self {
+            ctx,
+            semaphores: Semaphores {
+                datastore,
+                queues: Queues::default(),
+            },
+        }
+    }
+
+    /// Key of the semaphore of the limit, `None` when the request is not limited.
+    /// Requests missing the key are handled by the policy of the limit.
+    fn key(
+        ctx: &Ctx,
+        limit: &context::Limit,
+        request: &Request,
+    ) -> std::result::Result<Option<String>, StatusCode> {
+        let key = match &limit.scope {
+            Scope::App => {
+                return Ok(Some(format!("concurrency--{}", ctx.app_id)));
+            }
+            Scope::Endpoint => {
+                return Ok(Some(format!(
+                    "concurrency--{}--{}",
+                    ctx.app_id, ctx.endpoint_id
+                )));
+            }
+            Scope::Key(key) => key,
+        };
+        let value = match key::extract(key, request) {
+            Some(value) => value,
+            None => match limit.missing_key {
+                MissingKey::Shared => String::new(),
+                MissingKey::Skip => return Ok(None),
+                MissingKey::Reject(status) => return Err(status),
+            },
+        };
+        Ok(Some(format!(
+            "concurrency--{}--{}--{}",
+            ctx.app_id, ctx.endpoint_id, value
+        )))
+    }
+
+    fn rejected(limit: &context::Limit) -> Response {
+        match limit.scope {
+            Scope::Key(_) => Response::new(StatusCode::TOO_MANY_REQUESTS),
+            Scope::App | Scope::Endpoint => Response::new(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+}
+
+impl Semaphores {
+    /// Take a permit, waiting in the queue of the limit when there is none left.
+    /// Requests do not take a permit past the requests already waiting in the queue.
+    async fn acquire(&self, key: &str, limit: &context::Limit) -> Result<Option<String>> {
+        let queued = limit.queue.is_some() && self.queues.waiting(key) > 0;
+        if !queued {
+            if let Some(permit) = self.datastore.acquire(key, limit.max).await? {
+                return Ok(Some(permit));
+            }
+        }
+        let queue = match &limit.queue {
+            Some(queue) => queue,
+            None => {
+                return Ok(None);
+            }
+        };
+        let ticket = match self.queues.enter(key, queue.size) {
+            Some(ticket) => ticket,
+            None => {
+                return Ok(None);
+            }
+        };
+        let deadline = Instant::now() + queue.timeout;
+        match timeout_at(deadline, self.wait(key, limit.max, &ticket)).await {
+            Ok(permit) => permit,
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn wait(&self, key: &str, max: usize, ticket: &Ticket) -> Result<Option<String>> {
+        let _head = ticket.head().await;
+        loop {
+            if let Some(permit) = self.datastore.acquire(key, max).await? {
+                return Ok(Some(permit));
+            }
+            ticket.released(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Permits {
+    /// Renew the permits in the background while they are held.
+    fn renew(&mut self) {
+        let Some(period) = self.semaphores.datastore.renewal() else {
+            return;
+        };
+        if self.permits.is_empty() {
+            return;
+        }
+        let datastore = self.semaphores.datastore.clone();
+        let permits = self.permits.clone();
+        self.renewal = Some(tokio::spawn(async move {
+            let mut interval = interval_at(Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                for (key, permit) in permits.iter() {
+                    if let Err(error) = datastore.renew(key, permit).await {
+                        warn!("Failed to renew concurrency permit: {}", error);
+                    }
+                }
+            }
+        }));
+    }
+}
+
+impl Drop for Permits {
+    fn drop(&mut self) {
+        if let Some(renewal) = self.renewal.take() {
+            renewal.abort();
+        }
+        if self.permits.is_empty() {
+            return;
+        }
+        let semaphores = self.semaphores.clone();
+        let permits = std::mem::take(&mut self.permits);
+        tokio::spawn(async move {
+            for (key, permit) in permits {
+                if let Err(error) = semaphores.datastore.release(&key, &permit).await {
+                    warn!("Failed to release concurrency permit: {}", error);
+                }
+                semaphores.queues.notify(&key);
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl TMiddleware for Middleware {
+    async fn run(&self, ctx: &Ctx, request: Request, next: Next<'_>) -> Result<Response> {
+        let config = match self.ctx.get(ctx.app_id) {
+            Some(config) => config,
+            None => {
+                return next.run(request).await;
+            }
+        };
+        let mut limits = Vec::new();
+        for limit in config.global().limits.iter().chain(
+            config
+                .get(ctx.endpoint_id)
+                .map(|limits| limits.limits.iter())
+                .into_iter()
+                .flatten(),
+        ) {
+            match Self::key(ctx, limit, &request) {
+                Ok(Some(key)) => limits.push((key, limit)),
+                Ok(None) => {}
+                Err(status) => {
+                    return Ok(Response::new(status));
+                }
+            }
+        }
+        let mut permits = Permits {
+            semaphores: self.semaphores.clone(),
+            permits: Vec::with_capacity(limits.len()),
+            renewal: None,
+        };
+        for (key, limit) in limits {
+            match self.semaphores.acquire(&key, limit).await? {
+                Some(permit) => permits.permits.push((key, permit)),
+                None => {
+                    return Ok(Self::rejected(limit));
+                }
+            }
+        }
+        permits.renew();
+        let mut response = next.run(request).await?;
+        if let Some(body) = response.take_body() {
+            response.set_body(PermitBody {
+                body,
+                _permits: permits,
+            });
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concurrency_limit::datastore::InMemoryDatastore;
+    use crate::{rate_limit::config::Key, Peer};
+    use http::Method;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_key_follows_missing_key_policy() {
+        let ctx = Ctx {
+            app_id: 1,
+            endpoint_id: 2,
+            peer: Peer::default(),
+        };
+        let request = Request::new("/".to_string(), Method::GET);
+        let limit = |missing_key| context::Limit {
+            max: 1,
+            scope: Scope::Key(Key::Header("X-Username".to_string())),
+            queue: None,
+            missing_key,
+        };
+        assert_eq!(
+            Middleware::key(&ctx, &limit(MissingKey::Shared), &request),
+            Ok(Some("concurrency--1--2--".to_string()))
+        );
+        assert_eq!(
+            Middleware::key(&ctx, &limit(MissingKey::Skip), &request),
+            Ok(None)
+        );
+        assert_eq!(
+            Middleware::key(
+                &ctx,
+                &limit(MissingKey::Reject(StatusCode::BAD_REQUEST)),
+                &request
+            ),
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_gets_released_permit_before_new_arrival() {
+        let semaphores = Semaphores {
+            datastore: Arc::new(InMemoryDatastore::new()),
+            queues: Queues::default(),
+        };
+        let limit = Arc::new(context::Limit {
+            max: 1,
+            scope: Scope::Endpoint,
+            queue: Some(context::Queue {
+                size: 2,
+                timeout: Duration::from_secs(5),
+            }),
+            missing_key: MissingKey::default(),
+        });
+        let permit = semaphores.acquire("key", &limit).await.unwrap().unwrap();
+        let queued = tokio::spawn({
+            let (semaphores, limit) = (semaphores.clone(), limit.clone());
+            async move { semaphores.acquire("key", &limit).await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        // Released without notifying the queue, as by another gateway.
+        semaphores.datastore.release("key", &permit).await.unwrap();
+        let arrival = tokio::spawn({
+            let (semaphores, limit) = (semaphores.clone(), limit.clone());
+            async move { semaphores.acquire("key", &limit).await.unwrap() }
+        });
+        let queued = tokio::time::timeout(Duration::from_secs(1), queued)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(queued.is_some());
+        assert!(!arrival.is_finished());
+        semaphores
+            .datastore
+            .release("key", &queued.unwrap())
+            .await
+            .unwrap();
+        semaphores.queues.notify("key");
+        assert!(arrival.await.unwrap().is_some());
+    }
+}
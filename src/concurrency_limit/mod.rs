@@ -0,0 +1,62 @@
+mod builder;
+pub mod config;
+mod context;
+pub mod datastore;
+mod middleware;
+mod queue;
+
+use std::collections::HashMap;
+
+use config::*;
+use datastore::Datastore;
+pub(crate) use middleware::Middleware;
+
+use crate::{MiddlewareConfig, MiddlewareCtx};
+use builder::MiddlewareBuilder;
+
+type Config = MiddlewareConfig<config::Limits, config::Limits>;
+type Context = MiddlewareCtx<context::Limits, context::Limits>;
+
+#[derive(Debug, Default)]
+pub struct Builder(HashMap<String, (config::Limits, EndpointBuilder)>);
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_app(mut self, app: &str, root: config::Limits, endpoints: EndpointBuilder) -> Self {
+        self.0.insert(app.to_string(), (root, endpoints));
+        self
+    }
+
+    pub fn build(self, datastore: impl Datastore + Send + Sync + 'static) -> MiddlewareBuilder {
+        let config: Config = self
+            .0
+            .into_iter()
+            .map(|(app, (root, endpoints))| (app, (root, endpoints.0).into()))
+            .collect::<HashMap<_, _>>()
+            .into();
+        MiddlewareBuilder::new(config, datastore)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EndpointBuilder(HashMap<String, config::Limits>);
+
+impl EndpointBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_endpoint(mut self, endpoint: &str, limits: config::Limits) -> Self {
+        self.0.insert(endpoint.to_string(), limits);
+        self
+    }
+}
+
+impl From<HashMap<String, config::Limits>> for EndpointBuilder {
+    fn from(limits: HashMap<String, config::Limits>) -> Self {
+        Self(limits)
+    }
+}
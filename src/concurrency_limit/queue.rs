@@ -0,0 +1,117 @@
+//! Bounded FIFO queues of the requests waiting for a permit of a semaphore.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard, Notify};
+
+#[derive(Clone, Default)]
+pub struct Queues(Arc<Mutex<HashMap<String, Arc<Queue>>>>);
+
+#[derive(Default)]
+struct Queue {
+    /// Held by the request at the head of the queue, the lock is fair so the requests
+    /// get to the head in the order they entered the queue.
+    head: AsyncMutex<()>,
+    /// Requests in the queue, only changed with the queues locked.
+    waiting: AtomicUsize,
+    released: Notify,
+}
+
+/// Place of a request in the queue, the request leaves the queue when the ticket is dropped.
+pub struct Ticket {
+    queues: Queues,
+    key: String,
+    queue: Arc<Queue>,
+}
+
+impl Queues {
+    /// Enter the queue of the key, returns `None` when it is full.
+    pub fn enter(&self, key: &str, size: usize) -> Option<Ticket> {
+        let mut queues = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        let queue = queues.entry(key.to_string()).or_default();
+        if queue.waiting.load(Ordering::Relaxed) >= size {
+            return None;
+        }
+        queue.waiting.fetch_add(1, Ordering::Relaxed);
+        Some(Ticket {
+            queues: self.clone(),
+            key: key.to_string(),
+            queue: queue.clone(),
+        })
+    }
+
+    /// Requests waiting in the queue of the key.
+    pub fn waiting(&self, key: &str) -> usize {
+        let queues = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        queues
+            .get(key)
+            .map_or(0, |queue| queue.waiting.load(Ordering::Relaxed))
+    }
+
+    /// Wake the head of the queue of the key, a permit was released.
+    pub fn notify(&self, key: &str) {
+        let queues = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(queue) = queues.get(key) {
+            queue.released.notify_one();
+        }
+    }
+}
+
+impl Ticket {
+    /// Wait until all requests which entered the queue before are done waiting.
+    pub async fn head(&self) -> MutexGuard<'_, ()> {
+        self.queue.head.lock().await
+    }
+
+    /// Wait until a permit is released by this gateway or the interval passes,
+    /// permits released by other gateways are not notified.
+    pub async fn released(&self, interval: Duration) {
+        let _ = tokio::time::timeout(interval, self.queue.released.notified()).await;
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut queues = self
+            .queues
+            .0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if self.queue.waiting.fetch_sub(1, Ordering::Relaxed) == 1 {
+            queues.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_is_bounded_and_fifo() {
+        let queues = Queues::default();
+        let first = queues.enter("key", 2).unwrap();
+        let second = queues.enter("key", 2).unwrap();
+        assert!(queues.enter("key", 2).is_none());
+        assert_eq!(queues.waiting("key"), 2);
+        assert!(queues.enter("other", 2).is_some());
+        let head = first.head().await;
+        let waiting = tokio::spawn(async move {
+            let _head = second.head().await;
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+        drop(head);
+        waiting.await.unwrap();
+        assert!(queues.enter("key", 2).is_some());
+        drop(first);
+        assert!(queues.0.lock().unwrap().get("key").is_none());
+    }
+}
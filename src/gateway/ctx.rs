@@ -1,8 +1,12 @@
-use std::{collections::HashMap, fmt::Debug};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::{collections::HashMap, fmt::Debug, net::SocketAddr};
 
 use async_trait::async_trait;
 use futures::future::join_all;
 
+#[cfg(feature = "tls")]
+use crate::ClientCertificate;
 use crate::Result;
 
 pub type Id = usize;
@@ -11,6 +15,29 @@ pub type Id = usize;
 pub struct Ctx {
     pub app_id: Id,
     pub endpoint_id: Id,
+    pub peer: Peer,
+}
+
+/// Information about the downstream connection a request was received on.
+#[derive(Debug, Clone, Default)]
+pub struct Peer {
+    /// Address of the client.
+    /// When the listener accepts PROXY protocol headers, this is the address announced by the proxy.
+    pub remote_addr: Option<SocketAddr>,
+    /// Address the client connected to.
+    pub local_addr: Option<SocketAddr>,
+    /// Whether the addresses were taken from a PROXY protocol header.
+    pub proxied: bool,
+    /// Whether the connection is encrypted with TLS.
+    #[cfg(feature = "tls")]
+    pub secure: bool,
+    /// The verified certificate the client authenticated with on a TLS connection.
+    #[cfg(feature = "tls")]
+    pub client_certificate: Option<Arc<ClientCertificate>>,
+    /// The SNI host name the client sent on a TLS connection, lowercased.
+    /// The client certificate was verified by the certificate authorities of this host.
+    #[cfg(feature = "tls")]
+    pub server_name: Option<String>,
 }
 
 #[async_trait]
@@ -6,10 +6,31 @@ pub mod tls;
 
 use std::sync::Arc;
 
-use crate::{Middleware, ReadHalf, WriteHalf};
+use tokio::{io, net::TcpStream};
+
+use crate::{io::proxy::ReadProxyHeader, Middleware, Peer, ReadHalf, WriteHalf};
 
 pub use service::EntryPoint;
 
 pub type MiddlewaresItem = Arc<dyn Middleware + Send + Sync + 'static>;
 
 pub type Middlewares<'a> = Box<dyn Iterator<Item = MiddlewaresItem> + Send + Sync + 'a>;
+
+/// Resolve the downstream peer of an accepted connection.
+/// When `proxy_protocol` is enabled, the PROXY protocol header is consumed from the stream first.
+pub(crate) async fn accept_peer(stream: &mut TcpStream, proxy_protocol: bool) -> io::Result<Peer> {
+    let mut peer = Peer {
+        remote_addr: stream.peer_addr().ok(),
+        local_addr: stream.local_addr().ok(),
+        ..Default::default()
+    };
+    if proxy_protocol {
+        let header = stream.read_proxy_header().await?;
+        if let Some(source) = header.source {
+            peer.remote_addr = Some(source);
+            peer.local_addr = header.destination;
+            peer.proxied = true;
+        }
+    }
+    Ok(peer)
+}
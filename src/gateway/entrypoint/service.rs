@@ -1,13 +1,13 @@
 use crate::{
-    http::{HeaderMapExt, Request, Response, WriteResponse},
+    http::{headers, request::RequestBody, HeaderMapExt, Request, Response, WriteResponse},
     server::app::GenerateKey,
     utils::{Also, AsyncAndThen},
-    Ctx, Id, Next, Origin, ReadRequest, RouterService, Service,
+    Ctx, Id, Next, Origin, Peer, ReadRequest, RouterService, Service,
 };
 use anyhow::Result;
 use essentials::{debug, error, info, warn};
 use http::{header, StatusCode};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 use tokio::io::{self, AsyncWriteExt, BufReader};
 
 use super::{Middlewares, MiddlewaresItem, ReadHalf, WriteHalf};
@@ -17,6 +17,8 @@ pub struct EntryPoint {
     generate_peer_key: Box<GenerateKey>,
     peers: HashMap<String, (Id, RouterService)>,
     middlewares: Vec<MiddlewaresItem>,
+    #[cfg(feature = "tls")]
+    hsts: HashMap<Id, String>,
 }
 
 unsafe impl Sync for EntryPoint {}
@@ -38,15 +40,33 @@ impl EntryPoint {
                 .map(|(id, (k, v))| (k, (id as Id, v)))
                 .collect(),
             middlewares: middlewares.into_iter().map(Arc::from).collect(),
+            #[cfg(feature = "tls")]
+            hsts: HashMap::new(),
         }
     }
 
+    /// Send the `Strict-Transport-Security` header returned for the app with responses over TLS.
+    #[cfg(feature = "tls")]
+    pub(crate) fn with_hsts(mut self, hsts: impl Fn(&str) -> Option<String>) -> Self {
+        self.hsts = self
+            .peers
+            .iter()
+            .filter_map(|(app, (id, _))| Some((*id, hsts(app)?)))
+            .collect();
+        self
+    }
+
+    /// The name of the app the request belongs to.
+    #[cfg(feature = "tls")]
+    pub(crate) fn app(&self, request: &Request) -> Option<String> {
+        (self.generate_peer_key)(request).map(|(app, _)| app)
+    }
+
     pub async fn next(
-        &self,
+        self: &Arc<Self>,
         context: &Ctx,
         request: Request,
-        left_rx: ReadHalf,
-        left_remains: Vec<u8>,
+        body: RequestBody,
         mut it: Middlewares<'_>,
     ) -> Result<Response> {
         match it.next() {
@@ -55,8 +75,7 @@ impl EntryPoint {
                 let next = Next {
                     entrypoint: self,
                     context,
-                    left_rx,
-                    left_remains,
+                    body,
                     it: Box::new(it),
                 };
                 middleware
@@ -67,7 +86,7 @@ impl EntryPoint {
             None => {
                 debug!(origin = self.origin.name(), request = ?request, "-->");
                 self.origin
-                    .connect(context, request, left_rx, left_remains)
+                    .connect(context, request, body)
                     .await
                     .also(|r| debug!(origin = self.origin.name(), response = ?r, "<--"))
             }
@@ -76,11 +95,12 @@ impl EntryPoint {
 
     pub(crate) async fn safe_handle(
         self: &Arc<EntryPoint>,
-        ip: Option<SocketAddr>,
+        peer: Peer,
         rx: ReadHalf,
         mut tx: WriteHalf,
     ) {
-        match self.handle(rx, &mut tx).await {
+        let ip = peer.remote_addr;
+        match self.handle(peer, rx, &mut tx).await {
             Ok(_) => {
                 info!(ip = ?ip, "Connection closed");
             }
@@ -98,16 +118,39 @@ impl EntryPoint {
         }
     }
 
-    async fn handle(&self, mut left_rx: ReadHalf, left_tx: &mut WriteHalf) -> io::Result<()> {
+    async fn handle(
+        self: &Arc<Self>,
+        peer: Peer,
+        mut left_rx: ReadHalf,
+        left_tx: &mut WriteHalf,
+    ) -> io::Result<()> {
         debug!(target: "entrypoint", stage = "request", "0 - init");
         let mut request_reader = BufReader::new(&mut left_rx);
         let request = request_reader.read_request().await?;
         debug!(target: "entrypoint", stage = "request", data = ?request, "1 - parsed request header");
         let left_remains = request_reader.buffer().to_vec();
         debug!(target: "entrypoint", stage = "request", data = ?left_remains, "2 - collected request body (remains from buffer)");
-        match self.handle_request(request, left_rx, left_remains).await {
+        self.respond(request, peer, left_rx, left_remains, left_tx)
+            .await
+    }
+
+    /// Handle a request whose head has already been read from the connection.
+    pub(crate) async fn respond(
+        self: &Arc<Self>,
+        request: Request,
+        peer: Peer,
+        left_rx: ReadHalf,
+        left_remains: Vec<u8>,
+        left_tx: &mut WriteHalf,
+    ) -> io::Result<()> {
+        match self
+            .handle_request(request, peer, left_rx, left_remains)
+            .await
+        {
             Ok(mut response) => {
-                response.insert_header(header::CONNECTION, "close");
+                if response.status != StatusCode::SWITCHING_PROTOCOLS {
+                    response.insert_header(header::CONNECTION, "close");
+                }
                 left_tx
                     .write_response(&response)
                     .await
@@ -137,9 +180,10 @@ impl EntryPoint {
         }
     }
 
-    async fn handle_request(
-        &self,
+    pub(crate) async fn handle_request(
+        self: &Arc<Self>,
         mut request: Request,
+        peer: Peer,
         left_rx: ReadHalf,
         left_remains: Vec<u8>,
     ) -> Result<Response> {
@@ -171,13 +215,47 @@ impl EntryPoint {
             }
         };
         debug!("Endpoint ID: {}", endpoint_id);
+        if let Some(addr) = peer.remote_addr.filter(|_| peer.proxied) {
+            let ip = addr.ip().to_string();
+            let forwarded_for = match request
+                .header(&headers::FORWARDED_FOR)
+                .and_then(|header| header.to_str().ok())
+            {
+                Some(forwarded_for) => format!("{}, {}", forwarded_for, ip),
+                None => ip.clone(),
+            };
+            request.insert_header(&headers::FORWARDED_FOR, forwarded_for);
+            request.insert_header(&headers::REAL_IP, ip);
+        }
         let context = Ctx {
             app_id: *app_id,
             endpoint_id,
+            peer,
         };
         debug!("Context: {:?}", context);
         let it = Box::new(self.middlewares.iter().cloned());
-        self.next(&context, request, left_rx, left_remains, it)
-            .await
+        let response = self
+            .next(
+                &context,
+                request,
+                RequestBody::new(left_rx, left_remains),
+                it,
+            )
+            .await?;
+        #[cfg(feature = "tls")]
+        let response = self.add_hsts(&context, response);
+        Ok(response)
+    }
+
+    #[cfg(feature = "tls")]
+    fn add_hsts(&self, context: &Ctx, mut response: Response) -> Response {
+        if let Some(hsts) = self
+            .hsts
+            .get(&context.app_id)
+            .filter(|_| context.peer.secure)
+        {
+            response.insert_header(header::STRICT_TRANSPORT_SECURITY, hsts.as_str());
+        }
+        response
     }
 }
@@ -16,8 +16,8 @@ impl TcpServer {
     }
 }
 
-pub fn build(entrypoint: EntryPoint, host: IpAddr, port: u16) -> TcpServer {
-    let handler = EntryPointHandler::new(entrypoint);
+pub fn build(entrypoint: EntryPoint, host: IpAddr, port: u16, proxy_protocol: bool) -> TcpServer {
+    let handler = EntryPointHandler::new(entrypoint, proxy_protocol);
     TcpServer {
         app: HttpServer::new(SocketAddr::new(host, port), handler),
     }
@@ -1,28 +1,36 @@
 use async_trait::async_trait;
-use essentials::info;
+use essentials::{error, info};
 use std::sync::Arc;
 use tokio::net::TcpStream;
 
-use crate::{http::stream::Split, EntryPoint, Handler};
+use crate::{gateway::entrypoint::accept_peer, http::stream::Split, EntryPoint, Handler};
 
 pub struct EntryPointHandler {
     entrypoint: Arc<EntryPoint>,
+    proxy_protocol: bool,
 }
 
 impl EntryPointHandler {
-    pub fn new(entrypoint: EntryPoint) -> Self {
+    pub fn new(entrypoint: EntryPoint, proxy_protocol: bool) -> Self {
         Self {
             entrypoint: Arc::new(entrypoint),
+            proxy_protocol,
         }
     }
 }
 
 #[async_trait]
 impl Handler for EntryPointHandler {
-    async fn handle(&self, left: TcpStream) {
-        let ip = left.peer_addr().ok();
-        info!(ip = ?ip, "Connection received");
+    async fn handle(&self, mut left: TcpStream) {
+        let peer = match accept_peer(&mut left, self.proxy_protocol).await {
+            Ok(peer) => peer,
+            Err(err) => {
+                error!(ip = ?left.peer_addr().ok(), "Failed to read PROXY protocol header: {}", err);
+                return;
+            }
+        };
+        info!(ip = ?peer.remote_addr, "Connection received");
         let (left_rx, left_tx) = left.to_split();
-        self.entrypoint.safe_handle(ip, left_rx, left_tx).await
+        self.entrypoint.safe_handle(peer, left_rx, left_tx).await
     }
 }
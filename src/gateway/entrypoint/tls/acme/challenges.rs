@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use tokio_rustls::rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+
+use crate::CertStore;
+
+pub(crate) const HTTP_PATH_PREFIX: &str = "/.well-known/acme-challenge/";
+pub(crate) const TLS_ALPN_PROTOCOL: &[u8] = b"acme-tls/1";
+
+/// Pending ACME challenges which are answered by the listeners.
+#[derive(Debug, Default)]
+pub struct Challenges {
+    http: RwLock<HashMap<String, String>>,
+    tls_alpn: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl Challenges {
+    /// The key authorization for an HTTP-01 challenge request path.
+    pub fn http(&self, path: &str) -> Option<String> {
+        let token = path.strip_prefix(HTTP_PATH_PREFIX)?;
+        self.http.read().ok()?.get(token).cloned()
+    }
+
+    pub(crate) fn add_http(&self, token: String, key_authorization: String) {
+        if let Ok(mut http) = self.http.write() {
+            http.insert(token, key_authorization);
+        }
+    }
+
+    pub(crate) fn remove_http(&self, token: &str) {
+        if let Ok(mut http) = self.http.write() {
+            http.remove(token);
+        }
+    }
+
+    pub(crate) fn add_tls_alpn(&self, domain: String, key: Arc<CertifiedKey>) {
+        if let Ok(mut tls_alpn) = self.tls_alpn.write() {
+            tls_alpn.insert(domain, key);
+        }
+    }
+
+    pub(crate) fn remove_tls_alpn(&self, domain: &str) {
+        if let Ok(mut tls_alpn) = self.tls_alpn.write() {
+            tls_alpn.remove(domain);
+        }
+    }
+
+    fn tls_alpn(&self, domain: &str) -> Option<Arc<CertifiedKey>> {
+        self.tls_alpn.read().ok()?.get(domain).cloned()
+    }
+}
+
+/// Resolves certificates from the store, answering TLS-ALPN-01 challenges with the challenge certificate.
+#[derive(Debug)]
+pub(crate) struct Resolver {
+    pub store: Arc<CertStore>,
+    pub challenges: Arc<Challenges>,
+}
+
+impl ResolvesServerCert for Resolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|protocol| protocol == TLS_ALPN_PROTOCOL));
+        if is_challenge {
+            return self.challenges.tls_alpn(client_hello.server_name()?);
+        }
+        self.store.resolve(client_hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_http() {
+        let challenges = Challenges::default();
+        challenges.add_http("token".to_string(), "token.thumbprint".to_string());
+        assert_eq!(
+            challenges.http("/.well-known/acme-challenge/token"),
+            Some("token.thumbprint".to_string())
+        );
+        assert_eq!(challenges.http("/.well-known/acme-challenge/other"), None);
+        assert_eq!(challenges.http("/token"), None);
+        challenges.remove_http("token");
+        assert_eq!(challenges.http("/.well-known/acme-challenge/token"), None);
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{anyhow, bail, Context, Result};
+use aws_lc_rs::{
+    digest::{digest, SHA256},
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use essentials::debug;
+use http::{header, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::sleep;
+
+const REPLAY_NONCE: &str = "replay-nonce";
+const JOSE_JSON: &str = "application/jose+json";
+const PEM_CHAIN: &str = "application/pem-certificate-chain";
+const POLL_ATTEMPTS: usize = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The account key used to sign requests to the ACME server (ES256).
+pub(crate) struct AccountKey {
+    pair: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl AccountKey {
+    /// Load the key from a PKCS#8 DER document.
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        Ok(Self {
+            pair: EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8)
+                .map_err(|err| anyhow!("Invalid ACME account key: {}", err))?,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Base64url encoded coordinates of the public key.
+    fn coordinates(&self) -> (String, String) {
+        // Uncompressed point: 0x04 || x || y
+        let point = self.pair.public_key().as_ref();
+        (
+            URL_SAFE_NO_PAD.encode(&point[1..33]),
+            URL_SAFE_NO_PAD.encode(&point[33..65]),
+        )
+    }
+
+    fn jwk(&self) -> Value {
+        let (x, y) = self.coordinates();
+        json!({ "crv": "P-256", "kty": "EC", "x": x, "y": y })
+    }
+
+    /// The JWK thumbprint as defined by RFC 7638.
+    fn thumbprint(&self) -> String {
+        let (x, y) = self.coordinates();
+        let jwk = format!(r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#, x, y);
+        URL_SAFE_NO_PAD.encode(digest(&SHA256, jwk.as_bytes()))
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<String> {
+        let signature = self
+            .pair
+            .sign(&self.rng, data)
+            .map_err(|err| anyhow!("Failed to sign ACME request: {}", err))?;
+        Ok(URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+}
+
+/// A state of an order or an authorization.
+pub(crate) struct Resource {
+    pub url: String,
+    pub body: Value,
+}
+
+impl Resource {
+    pub fn status(&self) -> &str {
+        self.body["status"].as_str().unwrap_or_default()
+    }
+}
+
+/// A minimal ACME (RFC 8555) client.
+pub(crate) struct Client {
+    http: reqwest::Client,
+    key: AccountKey,
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl Client {
+    pub async fn new(http: reqwest::Client, directory: &str, key: AccountKey) -> Result<Self> {
+        let directory: Value = serde_json::from_slice(
+            &http
+                .get(directory)
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch ACME directory {}", directory))?
+                .bytes()
+                .await?,
+        )?;
+        let url = |name: &str| {
+            directory[name]
+                .as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| anyhow!("ACME directory is missing {}", name))
+        };
+        Ok(Self {
+            new_nonce: url("newNonce")?,
+            new_account: url("newAccount")?,
+            new_order: url("newOrder")?,
+            http,
+            key,
+            kid: None,
+            nonce: None,
+        })
+    }
+
+    pub fn key_authorization(&self, token: &str) -> String {
+        format!("{}.{}", token, self.key.thumbprint())
+    }
+
+    /// Register the account (or look up the existing account of the key).
+    pub async fn register(&mut self, contact: &[String]) -> Result<()> {
+        let contact = contact
+            .iter()
+            .map(|email| format!("mailto:{}", email))
+            .collect::<Vec<_>>();
+        let url = self.new_account.clone();
+        let response = self
+            .post(
+                &url,
+                Some(json!({ "termsOfServiceAgreed": true, "contact": contact })),
+            )
+            .await?;
+        self.kid = Some(
+            response
+                .location
+                .ok_or_else(|| anyhow!("ACME account has no location"))?,
+        );
+        Ok(())
+    }
+
+    pub async fn new_order(&mut self, domains: &[String]) -> Result<Resource> {
+        let identifiers = domains
+            .iter()
+            .map(|domain| json!({ "type": "dns", "value": domain }))
+            .collect::<Vec<_>>();
+        let url = self.new_order.clone();
+        let response = self
+            .post(&url, Some(json!({ "identifiers": identifiers })))
+            .await?;
+        Ok(Resource {
+            url: response
+                .location
+                .ok_or_else(|| anyhow!("ACME order has no location"))?,
+            body: response.json()?,
+        })
+    }
+
+    pub async fn fetch(&mut self, url: &str) -> Result<Resource> {
+        let response = self.post(url, None).await?;
+        Ok(Resource {
+            url: url.to_string(),
+            body: response.json()?,
+        })
+    }
+
+    /// Tell the server the challenge is ready to be validated.
+    pub async fn respond(&mut self, url: &str) -> Result<()> {
+        self.post(url, Some(json!({}))).await?;
+        Ok(())
+    }
+
+    pub async fn finalize(&mut self, order: &Resource, csr: &[u8]) -> Result<()> {
+        let url = order.body["finalize"]
+            .as_str()
+            .ok_or_else(|| anyhow!("ACME order has no finalize URL"))?
+            .to_string();
+        self.post(&url, Some(json!({ "csr": URL_SAFE_NO_PAD.encode(csr) })))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn certificate(&mut self, order: &Resource) -> Result<String> {
+        let url = order.body["certificate"]
+            .as_str()
+            .ok_or_else(|| anyhow!("ACME order has no certificate URL"))?
+            .to_string();
+        let response = self.post(&url, None).await?;
+        Ok(String::from_utf8(response.body)?)
+    }
+
+    /// Poll the resource until it leaves the pending/processing states.
+    pub async fn poll(&mut self, url: &str) -> Result<Resource> {
+        for _ in 0..POLL_ATTEMPTS {
+            let resource = self.fetch(url).await?;
+            match resource.status() {
+                "pending" | "processing" => sleep(POLL_INTERVAL).await,
+                _ => return Ok(resource),
+            }
+        }
+        bail!("Timed out waiting for ACME resource {}", url)
+    }
+
+    /// Send a signed request. Without a payload, a POST-as-GET request is sent.
+    async fn post(&mut self, url: &str, payload: Option<Value>) -> Result<AcmeResponse> {
+        let payload = payload
+            .map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string()))
+            .unwrap_or_default();
+        for retry in [true, false] {
+            let nonce = self.nonce().await?;
+            let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.key.jwk(),
+            }
+            let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+            let signature = self
+                .key
+                .sign(format!("{}.{}", protected, payload).as_bytes())?;
+            let response = self
+                .http
+                .post(url)
+                .header(header::CONTENT_TYPE, JOSE_JSON)
+                .header(header::ACCEPT, format!("application/json, {}", PEM_CHAIN))
+                .body(
+                    json!({ "protected": protected, "payload": payload, "signature": signature })
+                        .to_string(),
+                )
+                .send()
+                .await
+                .with_context(|| format!("Failed to send ACME request to {}", url))?;
+            self.nonce = response
+                .headers()
+                .get(REPLAY_NONCE)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string);
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string);
+            let body = response.bytes().await?.to_vec();
+            if status.is_success() {
+                return Ok(AcmeResponse { location, body });
+            }
+            let problem: Value = serde_json::from_slice(&body).unwrap_or_default();
+            if retry
+                && status == StatusCode::BAD_REQUEST
+                && problem["type"] == "urn:ietf:params:acme:error:badNonce"
+            {
+                debug!("Retrying ACME request with a fresh nonce");
+                continue;
+            }
+            bail!(
+                "ACME request to {} failed with {}: {}",
+                url,
+                status,
+                problem
+            );
+        }
+        unreachable!()
+    }
+
+    async fn nonce(&mut self) -> Result<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        self.http
+            .head(&self.new_nonce)
+            .send()
+            .await
+            .with_context(|| "Failed to fetch ACME nonce".to_string())?
+            .headers()
+            .get(REPLAY_NONCE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+            .ok_or_else(|| anyhow!("ACME server did not provide a nonce"))
+    }
+}
+
+struct AcmeResponse {
+    location: Option<String>,
+    body: Vec<u8>,
+}
+
+impl AcmeResponse {
+    fn json(&self) -> Result<Value> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
@@ -0,0 +1,107 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    time::{Time, TimeUnit},
+    CertStore,
+};
+
+pub const LETS_ENCRYPT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+pub const LETS_ENCRYPT_STAGING: &str = "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+/// The type of challenge used to prove control over a domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Challenge {
+    /// Answered on the plaintext HTTP listener.
+    Http01,
+    /// Answered on the TLS listener.
+    TlsAlpn01,
+}
+
+#[derive(Debug)]
+pub struct AcmeConfig {
+    pub directory: String,
+    pub storage: PathBuf,
+    pub contact: Vec<String>,
+    pub certificates: Vec<Vec<String>>,
+    pub challenge: Challenge,
+    pub renew_before: Time,
+    pub check_interval: Time,
+    pub root_certificate: Option<String>,
+    pub accept_invalid_certs: bool,
+    pub store: Option<Arc<CertStore>>,
+}
+
+impl AcmeConfig {
+    /// Issued certificates and the account key are persisted in the `storage` directory.
+    pub fn new(directory: String, storage: PathBuf) -> Self {
+        Self {
+            directory,
+            storage,
+            contact: Vec::new(),
+            certificates: Vec::new(),
+            challenge: Challenge::Http01,
+            renew_before: Time {
+                amount: 30,
+                unit: TimeUnit::Days,
+            },
+            check_interval: Time {
+                amount: 12,
+                unit: TimeUnit::Hours,
+            },
+            root_certificate: None,
+            accept_invalid_certs: false,
+            store: None,
+        }
+    }
+
+    pub fn with_contact(mut self, email: String) -> Self {
+        self.contact.push(email);
+        self
+    }
+
+    /// Request a certificate valid for all the given domain names.
+    pub fn add_certificate(mut self, domains: Vec<String>) -> Self {
+        self.certificates.push(domains);
+        self
+    }
+
+    pub fn with_challenge(mut self, challenge: Challenge) -> Self {
+        self.challenge = challenge;
+        self
+    }
+
+    /// Renew certificates which expire in less than the given time.
+    /// The default is 30 days
+    pub fn with_renew_before(mut self, renew_before: Time) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// How often certificates are checked for renewal.
+    /// The default is 12 hours
+    pub fn with_check_interval(mut self, check_interval: Time) -> Self {
+        self.check_interval = check_interval;
+        self
+    }
+
+    /// Trust the given PEM encoded root certificate when talking to the ACME server.
+    pub fn with_root_certificate(mut self, pem: String) -> Self {
+        self.root_certificate = Some(pem);
+        self
+    }
+
+    /// Do not verify the certificate of the ACME server.
+    /// Only meant for local test servers such as Pebble.
+    pub fn with_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// Issue the certificates into an existing store, e.g. one also holding certificates
+    /// loaded from disk with [`CertStore::load_dir`].
+    /// The default is a new empty store
+    pub fn with_store(mut self, store: Arc<CertStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::{anyhow, bail, Context, Result};
+use aws_lc_rs::digest::{digest, SHA256};
+use essentials::{error, info};
+use rcgen::{CertificateParams, CustomExtension, KeyPair};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::sleep;
+use tokio_rustls::rustls::{
+    crypto::aws_lc_rs::sign::any_supported_type,
+    pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+    sign::CertifiedKey,
+};
+use x509_parser::parse_x509_certificate;
+
+use super::{
+    client::{AccountKey, Client},
+    AcmeConfig, Challenge, Challenges,
+};
+use crate::{load_certified_key, time::TimeUnit, CertStore};
+
+const ACCOUNT_KEY: &str = "account.key";
+
+/// Issues certificates into the store and renews them ahead of expiry.
+pub struct AcmeManager {
+    config: AcmeConfig,
+    store: Arc<CertStore>,
+    challenges: Arc<Challenges>,
+}
+
+impl AcmeManager {
+    pub(crate) fn new(
+        config: AcmeConfig,
+        store: Arc<CertStore>,
+        challenges: Arc<Challenges>,
+    ) -> Self {
+        Self {
+            config,
+            store,
+            challenges,
+        }
+    }
+
+    pub(crate) fn challenges(&self) -> Arc<Challenges> {
+        self.challenges.clone()
+    }
+
+    pub async fn run(self) {
+        if let Err(err) = fs::create_dir_all(&self.config.storage) {
+            error!(storage = ?self.config.storage, "Failed to create ACME storage: {}", err);
+            return;
+        }
+        self.load_stored();
+        let interval = Duration::from_secs(
+            self.config.check_interval.convert(TimeUnit::Seconds).amount as u64,
+        );
+        loop {
+            self.renew().await;
+            sleep(interval).await;
+        }
+    }
+
+    /// Add the certificates issued before to the store.
+    /// The store may be shared, so only the configured certificates are added to it.
+    fn load_stored(&self) {
+        for domains in self.config.certificates.iter() {
+            let (cert_path, key_path) = self.paths(domains);
+            if !cert_path.is_file() {
+                continue;
+            }
+            let result = load_certified_key(&cert_path, &key_path)
+                .and_then(|key| self.store.insert_certificate(Arc::new(key)));
+            if let Err(err) = result {
+                error!(?domains, "Failed to load stored certificate: {:#}", err);
+            }
+        }
+    }
+
+    async fn renew(&self) {
+        let mut client = None;
+        for domains in self.config.certificates.iter() {
+            let (cert_path, key_path) = self.paths(domains);
+            match self.expires_soon(&cert_path) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(err) => error!(?domains, "Failed to read stored certificate: {:#}", err),
+            }
+            if client.is_none() {
+                match self.client().await {
+                    Ok(created) => client = Some(created),
+                    Err(err) => {
+                        error!("Failed to create ACME account: {:#}", err);
+                        return;
+                    }
+                }
+            }
+            let Some(client) = client.as_mut() else {
+                return;
+            };
+            info!(?domains, "Requesting certificate");
+            let result = async {
+                let (chain, key) = self.issue(client, domains).await?;
+                write_atomic(&cert_path, &chain, 0o644)?;
+                write_private(&key_path, &key)?;
+                self.store
+                    .insert_certificate(Arc::new(load_certified_key(&cert_path, &key_path)?))
+            }
+            .await;
+            match result {
+                Ok(()) => info!(?domains, "Certificate issued"),
+                Err(err) => error!(?domains, "Failed to issue certificate: {:#}", err),
+            }
+        }
+    }
+
+    fn paths(&self, domains: &[String]) -> (PathBuf, PathBuf) {
+        let stem = domains
+            .first()
+            .map(|domain| domain.replace('*', "_"))
+            .unwrap_or_default();
+        (
+            self.config.storage.join(format!("{}.crt", stem)),
+            self.config.storage.join(format!("{}.key", stem)),
+        )
+    }
+
+    fn expires_soon(&self, cert_path: &Path) -> Result<bool> {
+        if !cert_path.is_file() {
+            return Ok(true);
+        }
+        let cert = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+            .next()
+            .ok_or_else(|| anyhow!("No certificate found in {:?}", cert_path))??;
+        let (_, cert) = parse_x509_certificate(cert.as_ref())
+            .map_err(|err| anyhow!("Failed to parse certificate: {}", err))?;
+        let not_after = cert.validity().not_after.timestamp();
+        let renew_at =
+            not_after - self.config.renew_before.convert(TimeUnit::Seconds).amount as i64;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        Ok(now >= renew_at)
+    }
+
+    async fn client(&self) -> Result<Client> {
+        let mut http = reqwest::Client::builder()
+            .danger_accept_invalid_certs(self.config.accept_invalid_certs);
+        if let Some(pem) = self.config.root_certificate.as_ref() {
+            http = http.add_root_certificate(reqwest::Certificate::from_pem(pem.as_bytes())?);
+        }
+        let mut client =
+            Client::new(http.build()?, &self.config.directory, self.account_key()?).await?;
+        client.register(&self.config.contact).await?;
+        Ok(client)
+    }
+
+    fn account_key(&self) -> Result<AccountKey> {
+        let path = self.config.storage.join(ACCOUNT_KEY);
+        let key_pair = if path.is_file() {
+            KeyPair::from_pem(&fs::read_to_string(&path)?)?
+        } else {
+            let key_pair = KeyPair::generate()?;
+            write_private(&path, &key_pair.serialize_pem())?;
+            key_pair
+        };
+        AccountKey::from_pkcs8(&key_pair.serialize_der())
+    }
+
+    /// Run an order for the domains, returning the PEM encoded chain and private key.
+    async fn issue(&self, client: &mut Client, domains: &[String]) -> Result<(String, String)> {
+        let order = client.new_order(domains).await?;
+        let authorizations = order.body["authorizations"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for authorization in authorizations {
+            let url = authorization
+                .as_str()
+                .ok_or_else(|| anyhow!("Invalid ACME authorization URL"))?;
+            self.authorize(client, url).await?;
+        }
+        let order = client.poll(&order.url).await?;
+        if order.status() != "ready" {
+            bail!("ACME order is not ready: {}", order.body);
+        }
+        let key_pair = KeyPair::generate()?;
+        let csr = CertificateParams::new(domains.to_vec())?.serialize_request(&key_pair)?;
+        client.finalize(&order, csr.der()).await?;
+        let order = client.poll(&order.url).await?;
+        if order.status() != "valid" {
+            bail!("ACME order was not issued: {}", order.body);
+        }
+        Ok((client.certificate(&order).await?, key_pair.serialize_pem()))
+    }
+
+    async fn authorize(&self, client: &mut Client, url: &str) -> Result<()> {
+        let authorization = client.fetch(url).await?;
+        if authorization.status() == "valid" {
+            return Ok(());
+        }
+        let domain = authorization.body["identifier"]["value"]
+            .as_str()
+            .ok_or_else(|| anyhow!("ACME authorization has no identifier"))?
+            .to_string();
+        let kind = match self.config.challenge {
+            Challenge::Http01 => "http-01",
+            Challenge::TlsAlpn01 => "tls-alpn-01",
+        };
+        let challenge = authorization.body["challenges"]
+            .as_array()
+            .and_then(|challenges| {
+                challenges
+                    .iter()
+                    .find(|challenge| challenge["type"] == kind)
+            })
+            .ok_or_else(|| anyhow!("ACME server does not offer {} for {}", kind, domain))?;
+        let (token, challenge_url) = match (challenge["token"].as_str(), challenge["url"].as_str())
+        {
+            (Some(token), Some(url)) => (token.to_string(), url.to_string()),
+            _ => bail!("Invalid ACME challenge for {}", domain),
+        };
+        let key_authorization = client.key_authorization(&token);
+        match self.config.challenge {
+            Challenge::Http01 => self.challenges.add_http(token.clone(), key_authorization),
+            Challenge::TlsAlpn01 => self.challenges.add_tls_alpn(
+                domain.clone(),
+                Arc::new(challenge_certificate(&domain, &key_authorization)?),
+            ),
+        }
+        let result = async {
+            client.respond(&challenge_url).await?;
+            client.poll(url).await
+        }
+        .await;
+        match self.config.challenge {
+            Challenge::Http01 => self.challenges.remove_http(&token),
+            Challenge::TlsAlpn01 => self.challenges.remove_tls_alpn(&domain),
+        }
+        let authorization = result?;
+        if authorization.status() != "valid" {
+            bail!(
+                "ACME authorization for {} failed: {}",
+                domain,
+                authorization.body
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Write a private key readable only by the owner.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    write_atomic(path, contents, 0o600)
+}
+
+/// Write a file with the given permissions on unix.
+/// The file is written to a temporary file first, so that it is never readable by others
+/// and a crash does not leave a truncated file behind.
+fn write_atomic(path: &Path, contents: &str, mode: u32) -> Result<()> {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    let tmp_path = path.with_file_name(file_name);
+    // A leftover of a crash may have been created with other permissions.
+    let _ = fs::remove_file(&tmp_path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+    let result = options
+        .open(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result.with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Self-signed certificate answering a TLS-ALPN-01 challenge (RFC 8737).
+fn challenge_certificate(domain: &str, key_authorization: &str) -> Result<CertifiedKey> {
+    let mut params = CertificateParams::new(vec![domain.to_string()])?;
+    params.custom_extensions = vec![CustomExtension::new_acme_identifier(
+        digest(&SHA256, key_authorization.as_bytes()).as_ref(),
+    )];
+    let key_pair = KeyPair::generate()?;
+    let cert = params.self_signed(&key_pair)?;
+    let key = any_supported_type(&PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+        key_pair.serialize_der(),
+    )))
+    .with_context(|| "Unsupported challenge key".to_string())?;
+    Ok(CertifiedKey::new(vec![cert.der().clone()], key))
+}
@@ -0,0 +1,31 @@
+mod challenges;
+mod client;
+mod config;
+mod manager;
+
+use std::sync::Arc;
+use tokio_rustls::{rustls, TlsAcceptor};
+
+pub use challenges::Challenges;
+pub(crate) use challenges::{HTTP_PATH_PREFIX, TLS_ALPN_PROTOCOL};
+pub use config::{AcmeConfig, Challenge, LETS_ENCRYPT, LETS_ENCRYPT_STAGING};
+pub use manager::AcmeManager;
+
+/// Create the manager issuing certificates and the TLS acceptor serving them.
+pub(crate) fn setup(mut config: AcmeConfig) -> (AcmeManager, TlsAcceptor) {
+    let store = config.store.take().unwrap_or_default();
+    let challenges = Arc::new(Challenges::default());
+    let mut tls_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::new(challenges::Resolver {
+            store: store.clone(),
+            challenges: challenges.clone(),
+        }));
+    tls_config.alpn_protocols = vec![b"http/1.1".to_vec(), TLS_ALPN_PROTOCOL.to_vec()];
+    #[cfg(feature = "http2")]
+    tls_config.alpn_protocols.insert(0, b"h2".to_vec());
+    (
+        AcmeManager::new(config, store, challenges),
+        TlsAcceptor::from(Arc::new(tls_config)),
+    )
+}
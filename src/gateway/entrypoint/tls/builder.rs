@@ -1,11 +1,13 @@
 use anyhow::{bail, Result};
-use std::net::{IpAddr, SocketAddr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 use tokio::sync::{mpsc, oneshot};
-use tokio_rustls::TlsAcceptor;
 
 use crate::{EntryPoint, HttpServer};
 
-use super::{handler::EntryPointHandler, redirect::RedirectHandler};
+use super::{client_auth::Acceptor, handler::EntryPointHandler, redirect::RedirectHandler};
 
 pub struct TlsServer {
     pub tls: HttpServer<EntryPointHandler>,
@@ -49,13 +51,19 @@ pub fn build(
     host: IpAddr,
     http_port: u16,
     https_port: u16,
-    acceptor: TlsAcceptor,
+    acceptor: Acceptor,
+    tls_proxy_protocol: bool,
+    redirect: RedirectHandler,
 ) -> TlsServer {
+    let entrypoint = Arc::new(entrypoint);
     TlsServer {
         tls: HttpServer::new(
             SocketAddr::new(host, https_port),
-            EntryPointHandler::new(entrypoint, acceptor),
+            EntryPointHandler::new(entrypoint.clone(), acceptor, tls_proxy_protocol),
+        ),
+        tcp: HttpServer::new(
+            SocketAddr::new(host, http_port),
+            redirect.with_entrypoint(entrypoint),
         ),
-        tcp: HttpServer::new(SocketAddr::new(host, http_port), RedirectHandler::new()),
     }
 }
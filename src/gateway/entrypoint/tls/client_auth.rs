@@ -0,0 +1,270 @@
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fs::File, io::BufReader, net::IpAddr, path::Path, sync::Arc};
+use tokio::{io, net::TcpStream};
+use tokio_rustls::{
+    rustls::{
+        pki_types::CertificateDer,
+        server::{self, danger::ClientCertVerifier, ClientHello, WebPkiClientVerifier},
+        RootCertStore, ServerConfig, ServerConnection,
+    },
+    server::TlsStream,
+    LazyConfigAcceptor, TlsAcceptor,
+};
+use x509_parser::{extensions::GeneralName, parse_x509_certificate};
+
+#[cfg(feature = "acme")]
+use super::acme;
+#[cfg(feature = "http2")]
+use super::http2;
+
+/// Certificate authorities trusted to issue client certificates.
+#[derive(Debug, Clone)]
+pub struct ClientCa {
+    roots: Arc<RootCertStore>,
+    optional: bool,
+}
+
+impl ClientCa {
+    pub fn new(roots: RootCertStore) -> Self {
+        Self {
+            roots: Arc::new(roots),
+            optional: false,
+        }
+    }
+
+    /// Load the trusted certificate authorities from a PEM bundle.
+    pub fn from_pem(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut BufReader::new(
+            File::open(path).with_context(|| format!("Failed to open {:?}", path))?,
+        )) {
+            let cert =
+                cert.with_context(|| format!("Failed to parse certificates from {:?}", path))?;
+            roots
+                .add(cert)
+                .with_context(|| format!("Invalid CA certificate in {:?}", path))?;
+        }
+        Ok(Self::new(roots))
+    }
+
+    /// Accept clients without a certificate as well.
+    /// Certificates presented by the client are still verified.
+    /// The default is false
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    fn verifier(&self, config: &ServerConfig) -> Result<Arc<dyn ClientCertVerifier>> {
+        let builder = WebPkiClientVerifier::builder_with_provider(
+            self.roots.clone(),
+            config.crypto_provider().clone(),
+        );
+        let builder = match self.optional {
+            true => builder.allow_unauthenticated(),
+            false => builder,
+        };
+        builder
+            .build()
+            .map_err(|err| anyhow!("Failed to create client certificate verifier: {}", err))
+    }
+}
+
+/// Client certificate verification of a TLS listener.
+///
+/// Certificate authorities may be configured for the whole listener and per SNI host name.
+/// Host names may contain a wildcard in the left-most label (e.g. `*.example.com`).
+/// Connections to hosts without any configuration are accepted without a client certificate.
+#[derive(Debug, Clone, Default)]
+pub struct ClientAuth {
+    default: Option<ClientCa>,
+    hosts: HashMap<String, ClientCa>,
+}
+
+impl ClientAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify client certificates on every host without its own configuration.
+    pub fn with_default(mut self, ca: ClientCa) -> Self {
+        self.default = Some(ca);
+        self
+    }
+
+    /// Verify client certificates of connections to the given SNI host name.
+    pub fn add_host(mut self, host: &str, ca: ClientCa) -> Self {
+        self.hosts.insert(host.to_lowercase(), ca);
+        self
+    }
+}
+
+/// A TLS acceptor selecting the client certificate verification by the SNI name of the client.
+pub(crate) struct Acceptor {
+    base: Arc<ServerConfig>,
+    default: Option<Arc<ServerConfig>>,
+    hosts: HashMap<String, Arc<ServerConfig>>,
+}
+
+impl Acceptor {
+    pub fn new(acceptor: TlsAcceptor, client_auth: ClientAuth) -> Result<Self> {
+        #[cfg(feature = "http2")]
+        let acceptor = http2::with_alpn(acceptor);
+        let base = acceptor.config().clone();
+        let default = client_auth
+            .default
+            .map(|ca| with_verifier(&base, &ca))
+            .transpose()?;
+        let hosts = client_auth
+            .hosts
+            .into_iter()
+            .map(|(host, ca)| Ok((host, with_verifier(&base, &ca)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(Self {
+            base,
+            default,
+            hosts,
+        })
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        if self.default.is_none() && self.hosts.is_empty() {
+            return TlsAcceptor::from(self.base.clone()).accept(stream).await;
+        }
+        let start = LazyConfigAcceptor::new(server::Acceptor::default(), stream).await?;
+        let config = self.select(&start.client_hello());
+        start.into_stream(config).await
+    }
+
+    fn select(&self, client_hello: &ClientHello) -> Arc<ServerConfig> {
+        #[cfg(feature = "acme")]
+        if client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == acme::TLS_ALPN_PROTOCOL))
+        {
+            // The ACME server never presents a client certificate.
+            return self.base.clone();
+        }
+        let found = client_hello
+            .server_name()
+            .map(str::to_lowercase)
+            .and_then(|name| {
+                self.hosts.get(&name).or_else(|| {
+                    let (_, parent) = name.split_once('.')?;
+                    self.hosts.get(&format!("*.{}", parent))
+                })
+            });
+        found
+            .or(self.default.as_ref())
+            .unwrap_or(&self.base)
+            .clone()
+    }
+}
+
+/// Copy the server configuration, replacing its client certificate verifier.
+fn with_verifier(base: &ServerConfig, ca: &ClientCa) -> Result<Arc<ServerConfig>> {
+    let mut config = ServerConfig::builder_with_provider(base.crypto_provider().clone())
+        .with_safe_default_protocol_versions()?
+        .with_client_cert_verifier(ca.verifier(base)?)
+        .with_cert_resolver(base.cert_resolver.clone());
+    config.alpn_protocols = base.alpn_protocols.clone();
+    config.ignore_client_order = base.ignore_client_order;
+    config.max_fragment_size = base.max_fragment_size;
+    config.session_storage = base.session_storage.clone();
+    config.ticketer = base.ticketer.clone();
+    config.send_tls13_tickets = base.send_tls13_tickets;
+    config.key_log = base.key_log.clone();
+    Ok(Arc::new(config))
+}
+
+/// The verified certificate presented by the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// Distinguished name of the subject, e.g. `CN=client, O=Example`.
+    pub subject: String,
+    /// Common name of the subject.
+    pub common_name: Option<String>,
+    /// Subject alternative names (DNS names, e-mail addresses, URIs and IP addresses).
+    pub san: Vec<String>,
+    /// Hex encoded SHA-256 fingerprint of the DER encoded certificate.
+    pub fingerprint: String,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &CertificateDer) -> Result<Self> {
+        let (_, cert) = parse_x509_certificate(der.as_ref())
+            .map_err(|err| anyhow!("Failed to parse client certificate: {}", err))?;
+        let san = cert
+            .subject_alternative_name()
+            .map_err(|err| anyhow!("Invalid subject alternative names: {}", err))?
+            .map(|san| {
+                san.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(name)
+                        | GeneralName::RFC822Name(name)
+                        | GeneralName::URI(name) => Some(name.to_string()),
+                        GeneralName::IPAddress(ip) => match ip.len() {
+                            4 => Some(IpAddr::from(<[u8; 4]>::try_from(*ip).ok()?).to_string()),
+                            16 => Some(IpAddr::from(<[u8; 16]>::try_from(*ip).ok()?).to_string()),
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .find_map(|name| name.as_str().ok())
+            .map(ToString::to_string);
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            common_name,
+            san,
+            fingerprint: Sha256::digest(der.as_ref())
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        })
+    }
+
+    /// The end-entity certificate the client authenticated with, if any.
+    pub(crate) fn from_connection(connection: &ServerConnection) -> Option<Self> {
+        let cert = connection.peer_certificates()?.first()?;
+        Self::from_der(cert).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rcgen::{CertificateParams, DnType, KeyPair, SanType};
+
+    use super::*;
+
+    #[test]
+    fn test_from_der() {
+        let mut params = CertificateParams::new(vec!["client.example.com".to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, "client");
+        params.subject_alt_names.push(SanType::Rfc822Name(
+            "client@example.com".try_into().unwrap(),
+        ));
+        params
+            .subject_alt_names
+            .push(SanType::IpAddress("10.0.0.1".parse().unwrap()));
+        let cert = params.self_signed(&KeyPair::generate().unwrap()).unwrap();
+        let certificate = ClientCertificate::from_der(cert.der()).unwrap();
+        assert_eq!(certificate.subject, "CN=client");
+        assert_eq!(certificate.common_name.as_deref(), Some("client"));
+        assert_eq!(
+            certificate.san,
+            ["client.example.com", "client@example.com", "10.0.0.1"]
+        );
+        assert_eq!(certificate.fingerprint.len(), 64);
+    }
+}
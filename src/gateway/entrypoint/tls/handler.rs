@@ -2,36 +2,68 @@ use async_trait::async_trait;
 use essentials::{error, info};
 use std::sync::Arc;
 use tokio::net::TcpStream;
-use tokio_rustls::TlsAcceptor;
 
-use crate::{http::stream::Split, EntryPoint, Handler};
+use crate::{gateway::entrypoint::accept_peer, http::stream::Split, EntryPoint, Handler, Peer};
+
+use super::{client_auth::Acceptor, ClientCertificate};
+
+#[cfg(feature = "acme")]
+use super::acme;
+#[cfg(feature = "http2")]
+use super::http2;
 
 pub struct EntryPointHandler {
     entrypoint: Arc<EntryPoint>,
-    acceptor: TlsAcceptor,
+    acceptor: Acceptor,
+    proxy_protocol: bool,
 }
 
 impl EntryPointHandler {
-    pub fn new(entrypoint: EntryPoint, acceptor: TlsAcceptor) -> Self {
+    pub fn new(entrypoint: Arc<EntryPoint>, acceptor: Acceptor, proxy_protocol: bool) -> Self {
         Self {
-            entrypoint: Arc::new(entrypoint),
+            entrypoint,
             acceptor,
+            proxy_protocol,
         }
     }
 }
 
 #[async_trait]
 impl Handler for EntryPointHandler {
-    async fn handle(&self, left: TcpStream) {
-        let ip = left.peer_addr().ok();
+    async fn handle(&self, mut left: TcpStream) {
+        let peer = match accept_peer(&mut left, self.proxy_protocol).await {
+            Ok(peer) => peer,
+            Err(err) => {
+                error!(ip = ?left.peer_addr().ok(), "Failed to read PROXY protocol header: {}", err);
+                return;
+            }
+        };
+        let ip = peer.remote_addr;
         info!(ip = ?ip, "Connection received");
-        let (left_rx, left_tx) = match self.acceptor.accept(left).await {
-            Ok(stream) => stream.to_split(),
+        let stream = match self.acceptor.accept(left).await {
+            Ok(stream) => stream,
             Err(err) => {
                 error!(ip = ?ip, "Failed to accept TLS connection: {}", err);
                 return;
             }
         };
-        self.entrypoint.safe_handle(ip, left_rx, left_tx).await
+        let connection = stream.get_ref().1;
+        let peer = Peer {
+            secure: true,
+            client_certificate: ClientCertificate::from_connection(connection).map(Arc::new),
+            server_name: connection.server_name().map(str::to_lowercase),
+            ..peer
+        };
+        #[cfg(feature = "http2")]
+        if connection.alpn_protocol() == Some(http2::ALPN) {
+            return http2::serve(self.entrypoint.clone(), peer, stream).await;
+        }
+        #[cfg(feature = "acme")]
+        if connection.alpn_protocol() == Some(acme::TLS_ALPN_PROTOCOL) {
+            info!(ip = ?ip, "Answered ACME TLS-ALPN-01 challenge");
+            return;
+        }
+        let (left_rx, left_tx) = stream.to_split();
+        self.entrypoint.safe_handle(peer, left_rx, left_tx).await
     }
 }
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use bytes::Bytes;
+use essentials::{debug, error, info};
+use h2::{server::SendResponse, Reason, RecvStream, SendStream};
+use http::{header, HeaderMap, StatusCode};
+use std::sync::Arc;
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+use crate::{
+    http::{
+        chunked::{self, Chunk},
+        grpc,
+        http2::{remove_connection_headers, send_data},
+        stream, HeaderMapExt,
+    },
+    EntryPoint, Peer, Request, Response,
+};
+
+pub(crate) const ALPN: &[u8] = b"h2";
+const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+enum Framing {
+    Length(usize),
+    Chunked,
+    Close,
+}
+
+/// Advertise `h2` and `http/1.1` unless the acceptor already configures its own ALPN protocols.
+pub(crate) fn with_alpn(acceptor: TlsAcceptor) -> TlsAcceptor {
+    if !acceptor.config().alpn_protocols.is_empty() {
+        return acceptor;
+    }
+    let mut config = (**acceptor.config()).clone();
+    config.alpn_protocols = vec![ALPN.to_vec(), ALPN_HTTP1.to_vec()];
+    TlsAcceptor::from(Arc::new(config))
+}
+
+/// Serve an HTTP/2 connection.
+/// Every stream is mapped onto the same middleware pipeline as HTTP/1.1 requests.
+pub(crate) async fn serve(entrypoint: Arc<EntryPoint>, peer: Peer, stream: TlsStream<TcpStream>) {
+    let ip = peer.remote_addr;
+    let mut connection = match h2::server::handshake(stream).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            error!(ip = ?ip, "Failed to perform HTTP/2 handshake: {}", err);
+            return;
+        }
+    };
+    while let Some(result) = connection.accept().await {
+        match result {
+            Ok((request, respond)) => {
+                let entrypoint = entrypoint.clone();
+                let peer = peer.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle(&entrypoint, peer, request, respond).await {
+                        error!("{}", err);
+                    }
+                });
+            }
+            Err(err) => {
+                error!(ip = ?ip, "HTTP/2 connection error: {}", err);
+                break;
+            }
+        }
+    }
+    info!(ip = ?ip, "Connection closed");
+}
+
+async fn handle(
+    entrypoint: &EntryPoint,
+    peer: Peer,
+    request: http::Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+) -> Result<()> {
+    let (parts, body) = request.into_parts();
+    let mut request = Request::new(
+        parts
+            .uri
+            .path_and_query()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "/".to_string()),
+        parts.method,
+    );
+    *request.headers_mut() = parts.headers;
+    if let Some(authority) = parts.uri.authority() {
+        request.insert_header(header::HOST, authority.as_str());
+    }
+    let chunked_request = !body.is_end_stream() && request.get_content_length().is_none();
+    if chunked_request {
+        request.insert_header(header::TRANSFER_ENCODING, "chunked");
+    }
+    let is_grpc = request.is_grpc();
+    debug!(request = ?request, "HTTP/2 request received");
+    let (local, remote) = io::duplex(BUFFER_SIZE);
+    let (local_rx, mut local_tx) = io::split(local);
+    let (left_rx, mut left_tx) = stream::tls::split(remote);
+    tokio::spawn(async move {
+        if let Err(err) = forward_request_body(body, &mut local_tx, chunked_request).await {
+            error!(?err, "failed forwarding HTTP/2 request body");
+        }
+    });
+    let mut response = match entrypoint
+        .handle_request(request, peer, left_rx, Vec::new())
+        .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            error!("{}", err);
+            Response::new(StatusCode::BAD_GATEWAY)
+        }
+    };
+    if is_grpc {
+        response = grpc::translate(response);
+    }
+    let length = response.get_content_length();
+    let framing = if response
+        .header(header::TRANSFER_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        Framing::Chunked
+    } else {
+        match length {
+            Some(length) => Framing::Length(length),
+            None => Framing::Close,
+        }
+    };
+    remove_connection_headers(response.headers_mut());
+    let mut head = http::Response::new(());
+    *head.status_mut() = response.status;
+    *head.headers_mut() = response.headers().clone();
+    let mut body = match response.body() {
+        Some(body) if length != Some(0) => body,
+        _ => {
+            respond.send_response(head, true)?;
+            return Ok(());
+        }
+    };
+    let mut send = respond.send_response(head, false)?;
+    let copy = tokio::spawn(async move {
+        let result = body.copy_to(&mut left_tx, length).await;
+        left_tx.shutdown().await?;
+        result
+    });
+    // The reader is dropped once forwarded, so the copy can not block on a full buffer.
+    let forwarded = forward_response_body(local_rx, &mut send, framing).await;
+    let copied = match copy.await {
+        Ok(result) => result.map_err(anyhow::Error::from),
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = copied {
+        error!(?err, "failed copying HTTP/2 response body");
+        send.send_reset(Reason::INTERNAL_ERROR);
+        return Ok(());
+    }
+    match forwarded? {
+        Some(trailers) => send.send_trailers(trailers)?,
+        None => send.send_data(Bytes::new(), true)?,
+    }
+    Ok(())
+}
+
+async fn forward_request_body<W>(mut body: RecvStream, writer: &mut W, chunked: bool) -> Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    while let Some(data) = body.data().await {
+        let data = data?;
+        body.flow_control().release_capacity(data.len())?;
+        if chunked {
+            chunked::write_chunk(writer, &data).await?;
+        } else {
+            writer.write_all(&data).await?;
+        }
+    }
+    if chunked {
+        let trailers = body.trailers().await?.unwrap_or_default();
+        chunked::write_last_chunk(writer, &trailers).await?;
+    }
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// Forward the response body without ending the stream, returns the trailers to end it with.
+async fn forward_response_body<R>(
+    reader: R,
+    send: &mut SendStream<Bytes>,
+    framing: Framing,
+) -> Result<Option<HeaderMap>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut remaining = match framing {
+        Framing::Chunked => {
+            let mut reader = BufReader::new(reader);
+            loop {
+                match chunked::read_chunk(&mut reader).await? {
+                    Chunk::Data(data) => send_data(send, data.into()).await?,
+                    Chunk::End(trailers) => {
+                        return Ok(Some(trailers).filter(|trailers| !trailers.is_empty()));
+                    }
+                }
+            }
+        }
+        Framing::Length(length) => Some(length),
+        Framing::Close => None,
+    };
+    let mut reader = reader;
+    let mut buf = vec![0_u8; BUFFER_SIZE];
+    while remaining != Some(0) {
+        let limit = remaining.map_or(buf.len(), |remaining| remaining.min(buf.len()));
+        let read = reader.read(&mut buf[..limit]).await?;
+        if read == 0 {
+            break;
+        }
+        remaining = remaining.map(|remaining| remaining - read);
+        send_data(send, Bytes::copy_from_slice(&buf[..read])).await?;
+    }
+    Ok(None)
+}
@@ -1,7 +1,18 @@
+#[cfg(feature = "acme")]
+pub mod acme;
 mod builder;
+mod client_auth;
 mod handler;
+#[cfg(feature = "http2")]
+mod http2;
 mod redirect;
 mod resolver;
+mod store;
 
 pub use builder::{build, TlsServer};
+pub(crate) use client_auth::Acceptor;
+pub use client_auth::{ClientAuth, ClientCa, ClientCertificate};
+pub(crate) use redirect::RedirectHandler;
+pub use redirect::{Hsts, Redirect, RedirectConfig, RedirectPolicy};
 pub use resolver::EmptyResolver;
+pub use store::{load_certified_key, CertStore};
@@ -1,35 +1,225 @@
 use async_trait::async_trait;
 use essentials::{error, info, warn};
-use http::header;
-use std::{io, net::SocketAddr};
+use http::{header, StatusCode};
+use std::{collections::HashMap, io, sync::Arc};
 use tokio::{
     io::{AsyncWriteExt, BufReader},
-    net::{
-        tcp::{OwnedReadHalf, OwnedWriteHalf},
-        TcpStream,
-    },
+    net::TcpStream,
 };
 
 use crate::{
-    http::{stream::Split, HeaderMapExt},
-    Handler, ReadRequest,
+    gateway::entrypoint::accept_peer,
+    http::{stream, HeaderMapExt},
+    time::{Time, TimeUnit},
+    EntryPoint, Handler, Peer, ReadHalf, ReadRequest, WriteHalf,
 };
 
+#[cfg(feature = "acme")]
+use super::acme::{Challenges, HTTP_PATH_PREFIX};
+
+/// What to do with requests received on the plain HTTP port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redirect {
+    /// Serve the request over plain HTTP.
+    Serve,
+    /// Redirect to HTTPS with `301 Moved Permanently`.
+    MovedPermanently,
+    /// Redirect to HTTPS with `308 Permanent Redirect`, preserving the method and body.
+    PermanentRedirect,
+}
+
+/// `Strict-Transport-Security` header sent with responses over TLS.
+#[derive(Debug)]
+pub struct Hsts {
+    pub max_age: Time,
+    pub include_subdomains: bool,
+    pub preload: bool,
+}
+
+impl Hsts {
+    pub fn new(max_age: Time) -> Self {
+        Self {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+
+    pub fn include_subdomains(mut self) -> Self {
+        self.include_subdomains = true;
+        self
+    }
+
+    pub fn preload(mut self) -> Self {
+        self.preload = true;
+        self
+    }
+
+    fn header(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age.convert(TimeUnit::Seconds).amount);
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
+
+/// Handling of plain HTTP requests of an app.
+#[derive(Debug)]
+pub struct RedirectPolicy {
+    redirect: Redirect,
+    hsts: Option<Hsts>,
+    target_host: Option<String>,
+    target_port: Option<u16>,
+}
+
+impl RedirectPolicy {
+    pub fn new(redirect: Redirect) -> Self {
+        Self {
+            redirect,
+            hsts: None,
+            target_host: None,
+            target_port: None,
+        }
+    }
+
+    pub fn with_hsts(mut self, hsts: Hsts) -> Self {
+        self.hsts = Some(hsts);
+        self
+    }
+
+    /// Redirect to the given host instead of the host requested by the client.
+    pub fn with_target_host(mut self, host: &str) -> Self {
+        self.target_host = Some(host.to_string());
+        self
+    }
+
+    /// Redirect to the given port instead of the port requested by the client.
+    /// The port is omitted from the location when it is 443.
+    pub fn with_target_port(mut self, port: u16) -> Self {
+        self.target_port = Some(port);
+        self
+    }
+
+    fn location(&self, host: &str, path: &str) -> String {
+        if self.target_host.is_none() && self.target_port.is_none() {
+            return format!("https://{}{}", host, path);
+        }
+        let name = self
+            .target_host
+            .as_deref()
+            .unwrap_or_else(|| without_port(host));
+        match self.target_port {
+            Some(port) if port != 443 => format!("https://{}:{}{}", name, port, path),
+            _ => format!("https://{}{}", name, path),
+        }
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self::new(Redirect::MovedPermanently)
+    }
+}
+
+/// Handling of requests received on the plain HTTP port when TLS is enabled.
+///
+/// By default, all requests are redirected to HTTPS with `301 Moved Permanently`.
+/// Requests to exempt paths are always served over plain HTTP.
+#[derive(Debug, Default)]
+pub struct RedirectConfig {
+    default: RedirectPolicy,
+    apps: HashMap<String, RedirectPolicy>,
+    exempt_paths: Vec<String>,
+}
+
+impl RedirectConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the policy of apps without their own policy and of requests not matching any app.
+    pub fn with_default(mut self, policy: RedirectPolicy) -> Self {
+        self.default = policy;
+        self
+    }
+
+    pub fn add_app(mut self, app: &str, policy: RedirectPolicy) -> Self {
+        self.apps.insert(app.to_string(), policy);
+        self
+    }
+
+    /// Serve requests whose path starts with the given prefix over plain HTTP (e.g. health checks).
+    /// The prefix matches whole path segments, `/health` matches `/health/live` but not `/healthz`.
+    pub fn add_exempt_path(mut self, prefix: &str) -> Self {
+        self.exempt_paths.push(prefix.to_string());
+        self
+    }
+
+    pub(crate) fn policy(&self, app: Option<&str>) -> &RedirectPolicy {
+        app.and_then(|app| self.apps.get(app))
+            .unwrap_or(&self.default)
+    }
+
+    /// The `Strict-Transport-Security` header of the app, if any.
+    pub(crate) fn hsts(&self, app: &str) -> Option<String> {
+        self.policy(Some(app)).hsts.as_ref().map(Hsts::header)
+    }
+
+    fn is_exempt(&self, path: &str) -> bool {
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        self.exempt_paths.iter().any(|prefix| {
+            path.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/')
+            })
+        })
+    }
+}
+
+fn without_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
 #[derive(Default)]
-pub struct RedirectHandler;
+pub struct RedirectHandler {
+    proxy_protocol: bool,
+    config: RedirectConfig,
+    entrypoint: Option<Arc<EntryPoint>>,
+    #[cfg(feature = "acme")]
+    challenges: Option<Arc<Challenges>>,
+}
 
 impl RedirectHandler {
-    pub fn new() -> Self {
-        Self
+    pub fn new(proxy_protocol: bool, config: RedirectConfig) -> Self {
+        Self {
+            proxy_protocol,
+            config,
+            ..Default::default()
+        }
     }
 
-    async fn safe_handle(
-        &self,
-        ip: Option<SocketAddr>,
-        left_rx: OwnedReadHalf,
-        mut left_tx: OwnedWriteHalf,
-    ) {
-        match self.handle(left_rx, &mut left_tx).await {
+    /// Serve requests which are not redirected through the entrypoint.
+    pub fn with_entrypoint(mut self, entrypoint: Arc<EntryPoint>) -> Self {
+        self.entrypoint = Some(entrypoint);
+        self
+    }
+
+    /// Answer pending ACME HTTP-01 challenges instead of redirecting them.
+    #[cfg(feature = "acme")]
+    pub fn with_acme_challenges(mut self, challenges: Arc<Challenges>) -> Self {
+        self.challenges = Some(challenges);
+        self
+    }
+
+    async fn safe_handle(&self, peer: Peer, left_rx: ReadHalf, mut left_tx: WriteHalf) {
+        let ip = peer.remote_addr;
+        match self.handle(peer, left_rx, &mut left_tx).await {
             Ok(_) => {
                 info!(ip = ?ip, "Connection closed");
             }
@@ -49,41 +239,147 @@ impl RedirectHandler {
 
     async fn handle(
         &self,
-        mut left_rx: OwnedReadHalf,
-        left_tx: &mut OwnedWriteHalf,
+        peer: Peer,
+        mut left_rx: ReadHalf,
+        left_tx: &mut WriteHalf,
     ) -> io::Result<()> {
         let mut request_reader = BufReader::new(&mut left_rx);
         let request = request_reader.read_request().await?;
+        let left_remains = request_reader.buffer().to_vec();
+        #[cfg(feature = "acme")]
+        if request.path.starts_with(HTTP_PATH_PREFIX) {
+            return match self
+                .challenges
+                .as_ref()
+                .and_then(|challenges| challenges.http(&request.path))
+            {
+                Some(key_authorization) => left_tx
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            key_authorization.len(),
+                            key_authorization
+                        )
+                        .as_bytes(),
+                    )
+                    .await,
+                None => write_status(left_tx, StatusCode::NOT_FOUND).await,
+            };
+        }
+        let app = self
+            .entrypoint
+            .as_ref()
+            .and_then(|entrypoint| entrypoint.app(&request));
+        let policy = self.config.policy(app.as_deref());
+        if policy.redirect == Redirect::Serve || self.config.is_exempt(&request.path) {
+            return match self.entrypoint.as_ref() {
+                Some(entrypoint) => {
+                    entrypoint
+                        .respond(request, peer, left_rx, left_remains, left_tx)
+                        .await
+                }
+                None => write_status(left_tx, StatusCode::NOT_FOUND).await,
+            };
+        }
         let host = match request.header(header::HOST).and_then(|h| h.to_str().ok()) {
             Some(host) => host,
             None => {
-                return left_tx
-                    .write_all(
-                        b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
-                    )
-                    .await;
+                return write_status(left_tx, StatusCode::BAD_REQUEST).await;
             }
         };
-        left_tx
-            .write_all(
-                format!(
-                    "HTTP/1.1 301 Moved Permanently\r\nLocation: https://{}{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
-                    host,
-                    request.path
-                )
-                .as_bytes(),
-            )
-            .await?;
-        Ok(())
+        let status = match policy.redirect {
+            Redirect::PermanentRedirect => StatusCode::PERMANENT_REDIRECT,
+            _ => StatusCode::MOVED_PERMANENTLY,
+        };
+        redirect(left_tx, status, &policy.location(host, &request.path)).await
     }
 }
 
+async fn write_status(left_tx: &mut WriteHalf, status: StatusCode) -> io::Result<()> {
+    left_tx
+        .write_all(
+            format!(
+                "HTTP/1.1 {} {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status.as_u16(),
+                status.canonical_reason().unwrap_or_default()
+            )
+            .as_bytes(),
+        )
+        .await
+}
+
+async fn redirect(left_tx: &mut WriteHalf, status: StatusCode, location: &str) -> io::Result<()> {
+    left_tx
+        .write_all(
+            format!(
+                "HTTP/1.1 {} {}\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status.as_u16(),
+                status.canonical_reason().unwrap_or_default(),
+                location
+            )
+            .as_bytes(),
+        )
+        .await
+}
+
 #[async_trait]
 impl Handler for RedirectHandler {
-    async fn handle(&self, left: TcpStream) {
-        let ip = left.peer_addr().ok();
-        info!(ip = ?ip, "Connection received");
-        let (left_rx, left_tx) = left.to_split();
-        self.safe_handle(ip, left_rx, left_tx).await
+    async fn handle(&self, mut left: TcpStream) {
+        let peer = match accept_peer(&mut left, self.proxy_protocol).await {
+            Ok(peer) => peer,
+            Err(err) => {
+                error!(ip = ?left.peer_addr().ok(), "Failed to read PROXY protocol header: {}", err);
+                return;
+            }
+        };
+        info!(ip = ?peer.remote_addr, "Connection received");
+        let (left_rx, left_tx) = stream::tls::split(left);
+        self.safe_handle(peer, left_rx, left_tx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let policy = RedirectPolicy::default();
+        assert_eq!(policy.location("app:8080", "/a"), "https://app:8080/a");
+        let policy = RedirectPolicy::default().with_target_port(8443);
+        assert_eq!(policy.location("app:8080", "/a"), "https://app:8443/a");
+        let policy = RedirectPolicy::default().with_target_port(443);
+        assert_eq!(policy.location("[::1]:80", "/a"), "https://[::1]/a");
+        let policy = RedirectPolicy::default().with_target_host("secure.example.com");
+        assert_eq!(
+            policy.location("app", "/a?b=c"),
+            "https://secure.example.com/a?b=c"
+        );
+    }
+
+    #[test]
+    fn test_is_exempt() {
+        let config = RedirectConfig::new()
+            .add_exempt_path("/health")
+            .add_exempt_path("/.well-known/");
+        assert!(config.is_exempt("/health"));
+        assert!(config.is_exempt("/health/live"));
+        assert!(config.is_exempt("/health?verbose"));
+        assert!(config.is_exempt("/.well-known/security.txt"));
+        assert!(!config.is_exempt("/healthz"));
+        assert!(!config.is_exempt("/health-admin"));
+        assert!(!config.is_exempt("/.well-known"));
+    }
+
+    #[test]
+    fn test_hsts() {
+        let hsts = Hsts::new(Time {
+            amount: 1,
+            unit: TimeUnit::Days,
+        })
+        .include_subdomains();
+        assert_eq!(hsts.header(), "max-age=86400; includeSubDomains");
     }
 }
@@ -0,0 +1,338 @@
+use anyhow::{anyhow, Context, Result};
+use essentials::{debug, error, info};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::{
+    crypto::aws_lc_rs::sign::any_supported_type,
+    pki_types::CertificateDer,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use x509_parser::{extensions::GeneralName, parse_x509_certificate};
+
+const DEFAULT_NAME: &str = "default";
+const CERT_EXTENSIONS: [&str; 2] = ["crt", "pem"];
+const KEY_EXTENSION: &str = "key";
+
+/// A certificate resolver selecting certificates by the SNI name of the client.
+///
+/// Names may contain a wildcard in the left-most label (e.g. `*.example.com`).
+/// When no certificate matches, the default certificate is used (if any).
+#[derive(Debug, Default)]
+pub struct CertStore {
+    certs: RwLock<Certs>,
+}
+
+#[derive(Debug, Default)]
+struct Certs {
+    names: HashMap<String, Arc<CertifiedKey>>,
+    /// Set with [`CertStore::set_default`], kept across reloads.
+    default: Option<Arc<CertifiedKey>>,
+    /// Loaded from the `default` pair of the directory, replaced on reload.
+    loaded_default: Option<Arc<CertifiedKey>>,
+}
+
+impl CertStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a store with certificates loaded from the given directory.
+    /// See [`CertStore::load_dir`] for the expected layout.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let store = Self::new();
+        store.load_dir(dir)?;
+        Ok(store)
+    }
+
+    /// Add a certificate for the given server name, replacing the previous one.
+    pub fn insert(&self, name: &str, key: Arc<CertifiedKey>) {
+        self.write().names.insert(name.to_lowercase(), key);
+    }
+
+    /// Add a certificate for all DNS names it is valid for.
+    pub fn insert_certificate(&self, key: Arc<CertifiedKey>) -> Result<()> {
+        let names = server_names(&key)?;
+        let mut certs = self.write();
+        for name in names {
+            certs.names.insert(name, key.clone());
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Arc<CertifiedKey>> {
+        self.write().names.remove(&name.to_lowercase())
+    }
+
+    /// Set the certificate used when no other certificate matches the requested name.
+    /// A `default` pair loaded from a directory takes precedence over it.
+    pub fn set_default(&self, key: Option<Arc<CertifiedKey>>) {
+        self.write().default = key;
+    }
+
+    /// Replace all certificates with the PEM files found in the given directory.
+    ///
+    /// Every certificate chain (`<name>.crt` or `<name>.pem`) must be accompanied by its private key
+    /// (`<name>.key`). Certificates are served for the DNS names listed in their subject
+    /// alternative names. The pair named `default` is used as the fallback certificate,
+    /// the one set with [`CertStore::set_default`] is used once the pair is removed.
+    pub fn load_dir(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let mut certs = Certs::default();
+        for path in cert_files(dir)? {
+            let key_path = path.with_extension(KEY_EXTENSION);
+            let key = Arc::new(load_certified_key(&path, &key_path)?);
+            if path.file_stem().is_some_and(|stem| stem == DEFAULT_NAME) {
+                certs.loaded_default = Some(key);
+                continue;
+            }
+            for name in server_names(&key)? {
+                certs.names.insert(name, key.clone());
+            }
+        }
+        info!(
+            dir = ?dir,
+            certificates = certs.names.len(),
+            "Loaded TLS certificates"
+        );
+        let mut current = self.write();
+        certs.default = current.default.take();
+        *current = certs;
+        Ok(())
+    }
+
+    /// Reload the certificates from the directory whenever its content changes.
+    /// Failed reloads are logged and the previously loaded certificates are kept.
+    pub fn watch(self: &Arc<Self>, dir: impl Into<PathBuf>, interval: Duration) -> JoinHandle<()> {
+        let store = self.clone();
+        let dir = dir.into();
+        tokio::spawn(async move {
+            let mut snapshot = modifications(&dir).ok();
+            let mut interval = tokio::time::interval(interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let current = match modifications(&dir) {
+                    Ok(current) => Some(current),
+                    Err(err) => {
+                        error!(dir = ?dir, "Failed to scan certificate directory: {}", err);
+                        continue;
+                    }
+                };
+                if current == snapshot {
+                    continue;
+                }
+                debug!(dir = ?dir, "Certificate directory changed");
+                match store.load_dir(&dir) {
+                    Ok(()) => snapshot = current,
+                    Err(err) => error!(dir = ?dir, "Failed to reload certificates: {:#}", err),
+                }
+            }
+        })
+    }
+
+    fn lookup(&self, server_name: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        let certs = self.certs.read().ok()?;
+        let found = server_name.map(str::to_lowercase).and_then(|name| {
+            certs.names.get(&name).cloned().or_else(|| {
+                let (_, parent) = name.split_once('.')?;
+                certs.names.get(&format!("*.{}", parent)).cloned()
+            })
+        });
+        found.or_else(|| {
+            certs
+                .loaded_default
+                .as_ref()
+                .or(certs.default.as_ref())
+                .cloned()
+        })
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Certs> {
+        self.certs
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.lookup(client_hello.server_name())
+    }
+}
+
+/// Load a certificate chain and its private key from PEM files.
+pub fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let chain = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(cert_path).with_context(|| format!("Failed to open {:?}", cert_path))?,
+    ))
+    .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+    .with_context(|| format!("Failed to parse certificates from {:?}", cert_path))?;
+    if chain.is_empty() {
+        return Err(anyhow!("No certificate found in {:?}", cert_path));
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(
+        File::open(key_path).with_context(|| format!("Failed to open {:?}", key_path))?,
+    ))
+    .with_context(|| format!("Failed to parse private key from {:?}", key_path))?
+    .ok_or_else(|| anyhow!("No private key found in {:?}", key_path))?;
+    let key = any_supported_type(&key)
+        .map_err(|err| anyhow!("Unsupported private key in {:?}: {}", key_path, err))?;
+    Ok(CertifiedKey::new(chain, key))
+}
+
+/// DNS names the end-entity certificate is valid for.
+fn server_names(key: &CertifiedKey) -> Result<Vec<String>> {
+    let cert = key
+        .end_entity_cert()
+        .map_err(|err| anyhow!("Missing end-entity certificate: {}", err))?;
+    let (_, cert) = parse_x509_certificate(cert.as_ref())
+        .map_err(|err| anyhow!("Failed to parse certificate: {}", err))?;
+    let mut names = cert
+        .subject_alternative_name()
+        .map_err(|err| anyhow!("Invalid subject alternative names: {}", err))?
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name) => Some(name.to_lowercase()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if names.is_empty() {
+        names.extend(
+            cert.subject()
+                .iter_common_name()
+                .filter_map(|name| name.as_str().ok())
+                .map(str::to_lowercase),
+        );
+    }
+    Ok(names)
+}
+
+fn cert_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read certificate directory {:?}", dir))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| CERT_EXTENSIONS.contains(&extension))
+                && path.with_extension(KEY_EXTENSION).is_file()
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+fn modifications(dir: &Path) -> Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| -> Result<_> {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            Ok((entry.path(), metadata.modified()?, metadata.len()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rcgen::generate_simple_self_signed;
+    use std::env;
+
+    use super::*;
+
+    fn write_pair(dir: &Path, stem: &str, names: &[&str]) {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            generate_simple_self_signed(names.iter().map(ToString::to_string).collect::<Vec<_>>())
+                .unwrap();
+        fs::write(dir.join(format!("{}.crt", stem)), cert.pem()).unwrap();
+        fs::write(dir.join(format!("{}.key", stem)), key_pair.serialize_pem()).unwrap();
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "gateway-cert-store-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names_of(key: Option<Arc<CertifiedKey>>) -> Vec<String> {
+        server_names(&key.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let dir = temp_dir("lookup");
+        write_pair(&dir, "example", &["example.com"]);
+        write_pair(&dir, "wildcard", &["*.example.com"]);
+        write_pair(&dir, "default", &["fallback.test"]);
+        let store = CertStore::from_dir(&dir).unwrap();
+        assert_eq!(names_of(store.lookup(Some("Example.com"))), ["example.com"]);
+        assert_eq!(
+            names_of(store.lookup(Some("api.example.com"))),
+            ["*.example.com"]
+        );
+        assert_eq!(
+            names_of(store.lookup(Some("a.b.example.com"))),
+            ["fallback.test"]
+        );
+        assert_eq!(names_of(store.lookup(None)), ["fallback.test"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = temp_dir("reload");
+        write_pair(&dir, "example", &["example.com"]);
+        let store = CertStore::from_dir(&dir).unwrap();
+        assert!(store.lookup(Some("other.com")).is_none());
+        write_pair(&dir, "other", &["other.com"]);
+        fs::remove_file(dir.join("example.crt")).unwrap();
+        store.load_dir(&dir).unwrap();
+        assert_eq!(names_of(store.lookup(Some("other.com"))), ["other.com"]);
+        assert!(store.lookup(Some("example.com")).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_default() {
+        let dir = temp_dir("reload-default");
+        write_pair(&dir, "default", &["fallback.test"]);
+        write_pair(&dir, "configured", &["configured.test"]);
+        let store = CertStore::from_dir(&dir).unwrap();
+        assert_eq!(names_of(store.lookup(None)), ["fallback.test"]);
+        fs::remove_file(dir.join("default.crt")).unwrap();
+        store.load_dir(&dir).unwrap();
+        assert!(store.lookup(None).is_none());
+        let configured =
+            load_certified_key(&dir.join("configured.crt"), &dir.join("configured.key")).unwrap();
+        store.set_default(Some(Arc::new(configured)));
+        write_pair(&dir, "default", &["fallback.test"]);
+        store.load_dir(&dir).unwrap();
+        assert_eq!(names_of(store.lookup(None)), ["fallback.test"]);
+        fs::remove_file(dir.join("default.crt")).unwrap();
+        store.load_dir(&dir).unwrap();
+        assert_eq!(names_of(store.lookup(None)), ["configured.test"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,17 +1,19 @@
+use std::sync::Arc;
+
 use super::{
-    entrypoint::{EntryPoint, Middlewares},
+    entrypoint::{EntryPoint, Middlewares, MiddlewaresItem},
     Result,
 };
 use crate::{
-    http::{stream::ReadHalf, Request, Response},
+    http::{request::RequestBody, Body, HeaderMapExt, Request, Response},
     Ctx,
 };
 
 pub struct Next<'a> {
-    pub entrypoint: &'a EntryPoint,
+    pub entrypoint: &'a Arc<EntryPoint>,
     pub context: &'a Ctx,
-    pub left_rx: ReadHalf,
-    pub left_remains: Vec<u8>,
+    /// Body of the request, which middlewares may read, buffer or replace.
+    pub body: RequestBody,
     pub it: Middlewares<'a>,
 }
 
@@ -19,15 +21,48 @@ unsafe impl Send for Next<'_> {}
 unsafe impl Sync for Next<'_> {}
 
 impl Next<'_> {
-    pub async fn run(self, request: Request) -> Result<Response> {
+    /// Run the rest of the middlewares and the origin with the body attached to the request.
+    pub async fn run(self, mut request: Request) -> Result<Response> {
+        if let RequestBody::Stream(body) = &self.body {
+            if !request.is_upgrade() {
+                request.set_body_length(body.length());
+            }
+        }
         self.entrypoint
-            .next(
-                self.context,
-                request,
-                self.left_rx,
-                self.left_remains,
-                self.it,
-            )
+            .next(self.context, request, self.body, self.it)
             .await
     }
+
+    /// Copy the rest of the middlewares and the origin, so that another request
+    /// can be sent through them after the response, e.g. from a background task.
+    pub fn detach(&mut self) -> Detached {
+        let middlewares = self.it.by_ref().collect::<Vec<_>>();
+        self.it = Box::new(middlewares.clone().into_iter());
+        Detached {
+            entrypoint: self.entrypoint.clone(),
+            context: self.context.clone(),
+            middlewares,
+        }
+    }
+}
+
+/// The rest of the middlewares and the origin, detached from the client connection.
+pub struct Detached {
+    entrypoint: Arc<EntryPoint>,
+    context: Ctx,
+    middlewares: Vec<MiddlewaresItem>,
+}
+
+impl Detached {
+    /// Run the rest of the middlewares and the origin with a request without a body.
+    pub async fn run(self, request: Request) -> Result<Response> {
+        Next {
+            entrypoint: &self.entrypoint,
+            context: &self.context,
+            body: RequestBody::Stream(Body::empty()),
+            it: Box::new(self.middlewares.into_iter()),
+        }
+        .run(request)
+        .await
+    }
 }
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub struct Root {
+    pub path: PathBuf,
+    pub index: Option<String>,
+    pub fallback: Option<String>,
+    pub precompressed: bool,
+}
+
+impl Root {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            index: Some("index.html".to_string()),
+            fallback: None,
+            precompressed: false,
+        }
+    }
+
+    /// Serve the given file when a directory is requested (`index.html` by default).
+    pub fn with_index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_string());
+        self
+    }
+
+    /// Respond with `404 Not Found` when a directory is requested.
+    pub fn without_index(mut self) -> Self {
+        self.index = None;
+        self
+    }
+
+    /// Serve the given file of the root instead of `404 Not Found`,
+    /// e.g. the `index.html` of a single-page application.
+    pub fn with_fallback(mut self, file: &str) -> Self {
+        self.fallback = Some(file.to_string());
+        self
+    }
+
+    /// Serve `.br` and `.gz` siblings of files to clients accepting the encoding.
+    pub fn with_precompressed(mut self) -> Self {
+        self.precompressed = true;
+        self
+    }
+}
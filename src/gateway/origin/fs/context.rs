@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use crate::{ConfigToContext, Result};
+use anyhow::Context;
+use async_trait::async_trait;
+use tokio::fs;
+
+use super::config;
+
+#[derive(Debug)]
+pub struct Root {
+    /// Canonical path of the root directory.
+    pub path: PathBuf,
+    pub index: Option<Box<str>>,
+    pub fallback: Option<PathBuf>,
+    pub precompressed: bool,
+}
+
+#[async_trait]
+impl ConfigToContext for config::Root {
+    type Context = Root;
+
+    async fn into_context(self) -> Result<Self::Context> {
+        let path = fs::canonicalize(&self.path)
+            .await
+            .with_context(|| format!("Failed to resolve static files root: {:?}", self.path))?;
+        let fallback = self
+            .fallback
+            .map(|file| path.join(file.trim_start_matches('/')));
+        Ok(Root {
+            path,
+            index: self.index.into_context().await?,
+            fallback,
+            precompressed: self.precompressed,
+        })
+    }
+}
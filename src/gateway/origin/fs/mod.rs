@@ -0,0 +1,53 @@
+mod builder;
+pub mod config;
+mod context;
+mod mime;
+mod origin;
+mod range;
+mod response;
+
+use builder::FsOriginBuilder;
+use origin::Origin;
+
+use crate::{MiddlewareConfig, MiddlewareCtx};
+use std::collections::HashMap;
+
+type Context = MiddlewareCtx<context::Root, ()>;
+type Config = MiddlewareConfig<config::Root, ()>;
+
+/// Origin serving static files from a directory of each app.
+#[derive(Debug, Default)]
+pub struct Builder(HashMap<String, config::Root>);
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_root(mut self, app: &str, root: config::Root) -> Self {
+        self.0.insert(app.to_string(), root);
+        self
+    }
+
+    pub fn build(self) -> FsOriginBuilder {
+        let config: Config = self
+            .0
+            .into_iter()
+            .map(|(app, config)| (app, (config, HashMap::new()).into()))
+            .collect::<HashMap<_, _>>()
+            .into();
+        FsOriginBuilder::new(config)
+    }
+}
+
+impl From<HashMap<String, config::Root>> for Builder {
+    fn from(roots: HashMap<String, config::Root>) -> Self {
+        Self(roots)
+    }
+}
+
+impl FromIterator<(String, config::Root)> for Builder {
+    fn from_iter<T: IntoIterator<Item = (String, config::Root)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
@@ -0,0 +1,319 @@
+use super::{
+    context::Root,
+    mime,
+    range::{self, ByteRange, Ranges},
+    response::FileResponse,
+};
+use crate::{
+    http::{request::RequestBody, HeaderMapExt, Request, Response},
+    Ctx, OriginServer, Result,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use essentials::debug;
+use http::{header, Method, StatusCode};
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::fs::{self, File};
+
+pub struct Origin(pub super::Context);
+
+#[async_trait]
+impl OriginServer for Origin {
+    async fn connect(
+        &self,
+        context: &Ctx,
+        request: Request,
+        _body: RequestBody,
+    ) -> Result<Response> {
+        let root = match self.0.get(context.app_id) {
+            Some(root) => root.global(),
+            None => {
+                return Ok(Response::new(StatusCode::NOT_FOUND));
+            }
+        };
+        if request.method != Method::GET && request.method != Method::HEAD {
+            let mut response = Response::new(StatusCode::METHOD_NOT_ALLOWED);
+            response.insert_header(header::ALLOW, "GET, HEAD");
+            return Ok(response);
+        }
+        let path = match sanitize(root, &request.path) {
+            Some(path) => path,
+            None => {
+                return Ok(Response::new(StatusCode::NOT_FOUND));
+            }
+        };
+        let path = match resolve(root, &path).await {
+            Some(path) => path,
+            None => match root.fallback.as_ref() {
+                Some(fallback) if is_file(fallback).await => fallback.clone(),
+                _ => {
+                    return Ok(Response::new(StatusCode::NOT_FOUND));
+                }
+            },
+        };
+        debug!(?path, "Serving static file");
+        serve(root, &request, &path).await
+    }
+}
+
+/// Join the decoded request path to the root.
+/// Returns `None` for paths trying to escape the root.
+fn sanitize(root: &Root, path: &str) -> Option<PathBuf> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path)?;
+    let mut sanitized = root.path.clone();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment if segment.contains(['\\', '\0']) => return None,
+            segment => sanitized.push(segment),
+        }
+    }
+    Some(sanitized)
+}
+
+/// Resolve the path to a file under the root, serving the index of directories.
+/// Returns `None` when the file does not exist or a symbolic link points outside of the root.
+async fn resolve(root: &Root, path: &Path) -> Option<PathBuf> {
+    let mut resolved = canonicalize(root, path).await?;
+    if fs::metadata(&resolved).await.ok()?.is_dir() {
+        resolved = canonicalize(root, &resolved.join(root.index.as_deref()?)).await?;
+    }
+    is_file(&resolved).await.then_some(resolved)
+}
+
+/// Canonicalize the path, following symbolic links, as long as it stays under the root.
+async fn canonicalize(root: &Root, path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path)
+        .await
+        .ok()
+        .filter(|path| path.starts_with(&root.path))
+}
+
+async fn is_file(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_file())
+}
+
+fn percent_decode(path: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+async fn serve(root: &Root, request: &Request, path: &Path) -> Result<Response> {
+    let content_type = mime::content_type(path);
+    let (path, encoding) = if root.precompressed {
+        precompressed(root, request, path).await
+    } else {
+        (path.to_path_buf(), None)
+    };
+    let file = File::open(&path)
+        .await
+        .with_context(|| format!("Failed to open file: {:?}", path))?;
+    let metadata = file
+        .metadata()
+        .await
+        .with_context(|| format!("Failed to read file metadata: {:?}", path))?;
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = etag(size, modified, encoding);
+    let last_modified = modified.map(http_date);
+
+    let mut response = Response::new(StatusCode::OK);
+    response.insert_header(header::ETAG, etag.as_str());
+    if let Some(last_modified) = last_modified.as_deref() {
+        response.insert_header(header::LAST_MODIFIED, last_modified);
+    }
+    if root.precompressed {
+        response.insert_header(header::VARY, "Accept-Encoding");
+    }
+    if is_not_modified(request, &etag, modified) {
+        response.status = StatusCode::NOT_MODIFIED;
+        return Ok(response);
+    }
+    response.insert_header(header::ACCEPT_RANGES, "bytes");
+    if let Some(encoding) = encoding {
+        response.insert_header(header::CONTENT_ENCODING, encoding);
+    }
+    let ranges = match request
+        .header(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| if_range_matches(request, &etag, last_modified.as_deref()))
+    {
+        Some(value) => range::parse(value, size),
+        None => Ranges::Ignored,
+    };
+    let body = match ranges {
+        Ranges::Unsatisfiable => {
+            response.status = StatusCode::RANGE_NOT_SATISFIABLE;
+            response.insert_header(header::CONTENT_RANGE, format!("bytes */{}", size));
+            return Ok(response);
+        }
+        Ranges::Satisfiable(ranges) if ranges.len() == 1 => {
+            response.status = StatusCode::PARTIAL_CONTENT;
+            response.insert_header(header::CONTENT_TYPE, content_type);
+            response.insert_header(header::CONTENT_RANGE, ranges[0].content_range(size));
+            FileResponse::new(file, ranges[0])
+        }
+        Ranges::Satisfiable(ranges) => {
+            let boundary = boundary();
+            response.status = StatusCode::PARTIAL_CONTENT;
+            response.insert_header(
+                header::CONTENT_TYPE,
+                format!("multipart/byteranges; boundary={}", boundary),
+            );
+            FileResponse::multipart(file, &ranges, size, content_type, &boundary)
+        }
+        Ranges::Ignored => {
+            response.insert_header(header::CONTENT_TYPE, content_type);
+            FileResponse::new(
+                file,
+                ByteRange {
+                    start: 0,
+                    length: size,
+                },
+            )
+        }
+    };
+    response.insert_header(header::CONTENT_LENGTH, body.content_length().to_string());
+    if request.method != Method::HEAD {
+        response.set_body(body);
+    }
+    Ok(response)
+}
+
+/// Find a precompressed sibling of the file in an encoding accepted by the client.
+async fn precompressed(
+    root: &Root,
+    request: &Request,
+    path: &Path,
+) -> (PathBuf, Option<&'static str>) {
+    let accepted = request
+        .header(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+        if !accepts(accepted, encoding) {
+            continue;
+        }
+        let mut sibling = path.as_os_str().to_owned();
+        sibling.push(".");
+        sibling.push(extension);
+        if let Some(sibling) = canonicalize(root, Path::new(&sibling)).await {
+            if is_file(&sibling).await {
+                return (sibling, Some(encoding));
+            }
+        }
+    }
+    (path.to_path_buf(), None)
+}
+
+fn accepts(accept_encoding: &str, encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        (name.eq_ignore_ascii_case(encoding) || name == "*")
+            && !params.any(|param| {
+                matches!(
+                    param.trim().strip_prefix("q=").map(str::parse::<f32>),
+                    Some(Ok(quality)) if quality <= 0.0
+                )
+            })
+    })
+}
+
+fn etag(size: u64, modified: Option<SystemTime>, encoding: Option<&str>) -> String {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_nanos())
+        .unwrap_or_default();
+    match encoding {
+        Some(encoding) => format!("\"{:x}-{:x}-{}\"", size, modified, encoding),
+        None => format!("\"{:x}-{:x}\"", size, modified),
+    }
+}
+
+fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn is_not_modified(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = request
+        .header(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+    let since = request
+        .header(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    match (since, modified) {
+        (Some(since), Some(modified)) => {
+            DateTime::<Utc>::from(modified).timestamp() <= since.timestamp()
+        }
+        _ => false,
+    }
+}
+
+/// Whether the `If-Range` condition of the request holds, so that its ranges are served.
+fn if_range_matches(request: &Request, etag: &str, last_modified: Option<&str>) -> bool {
+    match request
+        .header(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(condition) => condition == etag || Some(condition) == last_modified,
+        None => true,
+    }
+}
+
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_nanos())
+        .unwrap_or_default();
+    format!("{:032x}", nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/a%20b/%2e%2E").as_deref(), Some("/a b/.."));
+        assert_eq!(percent_decode("/a%2"), None);
+        assert_eq!(percent_decode("/a%zz"), None);
+    }
+
+    #[test]
+    fn test_accepts() {
+        assert!(accepts("gzip, deflate, br", "br"));
+        assert!(accepts("*", "gzip"));
+        assert!(!accepts("gzip;q=0, br", "gzip"));
+        assert!(!accepts("deflate", "gzip"));
+    }
+}
@@ -0,0 +1,133 @@
+/// Maximum number of ranges of a single request, more are ignored and the whole file is served.
+const MAX_RANGES: usize = 16;
+
+/// A satisfiable range of bytes of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub length: u64,
+}
+
+impl ByteRange {
+    /// The value of the `Content-Range` header of the range.
+    pub fn content_range(&self, size: u64) -> String {
+        format!(
+            "bytes {}-{}/{}",
+            self.start,
+            self.start + self.length - 1,
+            size
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Ranges {
+    /// The header is invalid or not supported, the whole file is served.
+    Ignored,
+    Satisfiable(Vec<ByteRange>),
+    Unsatisfiable,
+}
+
+/// Parse the `Range` header of a request for a file of the given size.
+pub fn parse(header: &str, size: u64) -> Ranges {
+    let specs = match header.split_once('=') {
+        Some((unit, specs)) if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+        _ => return Ranges::Ignored,
+    };
+    let specs = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect::<Vec<_>>();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return Ranges::Ignored;
+    }
+    let mut ranges = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let (start, end) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            None => return Ranges::Ignored,
+        };
+        let range = match (start.trim(), end.trim()) {
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(suffix) if suffix > 0 && size > 0 => {
+                    let length = suffix.min(size);
+                    Some(ByteRange {
+                        start: size - length,
+                        length,
+                    })
+                }
+                Ok(_) => None,
+                Err(_) => return Ranges::Ignored,
+            },
+            (start, end) => {
+                let start = match start.parse::<u64>() {
+                    Ok(start) => start,
+                    Err(_) => return Ranges::Ignored,
+                };
+                let end = match end {
+                    "" => u64::MAX,
+                    end => match end.parse::<u64>() {
+                        Ok(end) if end >= start => end,
+                        _ => return Ranges::Ignored,
+                    },
+                };
+                (start < size).then(|| ByteRange {
+                    start,
+                    length: end.min(size - 1) - start + 1,
+                })
+            }
+        };
+        ranges.extend(range);
+    }
+    if ranges.is_empty() {
+        Ranges::Unsatisfiable
+    } else {
+        Ranges::Satisfiable(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn range(start: u64, length: u64) -> ByteRange {
+        ByteRange { start, length }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("bytes=0-4", 10),
+            Ranges::Satisfiable(vec![range(0, 5)])
+        );
+        assert_eq!(
+            parse("bytes=5-", 10),
+            Ranges::Satisfiable(vec![range(5, 5)])
+        );
+        assert_eq!(
+            parse("bytes=-3", 10),
+            Ranges::Satisfiable(vec![range(7, 3)])
+        );
+        assert_eq!(
+            parse("bytes=8-20, -100", 10),
+            Ranges::Satisfiable(vec![range(8, 2), range(0, 10)])
+        );
+        assert_eq!(
+            parse("bytes=0-0,2-3,20-", 10),
+            Ranges::Satisfiable(vec![range(0, 1), range(2, 2)])
+        );
+        assert_eq!(parse("bytes=10-", 10), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 10), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=4-2", 10), Ranges::Ignored);
+        assert_eq!(parse("bytes=a-b", 10), Ranges::Ignored);
+        assert_eq!(parse("items=0-1", 10), Ranges::Ignored);
+    }
+
+    #[test]
+    fn test_content_range() {
+        assert_eq!(range(2, 3).content_range(10), "bytes 2-4/10");
+    }
+}
@@ -0,0 +1,139 @@
+use super::range::ByteRange;
+use crate::{
+    http::{response::ResponseBody, stream::WriteHalf},
+    io::zero_copy,
+};
+use async_trait::async_trait;
+use std::{collections::VecDeque, io::SeekFrom};
+use tokio::{
+    fs::File,
+    io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug)]
+enum Segment {
+    Bytes(Box<[u8]>),
+    Range(ByteRange),
+}
+
+/// Ranges of a file, optionally interleaved with bytes (e.g. parts of `multipart/byteranges`).
+#[derive(Debug)]
+pub struct FileResponse {
+    file: File,
+    segments: VecDeque<Segment>,
+}
+
+impl FileResponse {
+    pub fn new(file: File, range: ByteRange) -> Self {
+        Self {
+            file,
+            segments: VecDeque::from([Segment::Range(range)]),
+        }
+    }
+
+    /// A `multipart/byteranges` body with the given boundary.
+    pub fn multipart(
+        file: File,
+        ranges: &[ByteRange],
+        size: u64,
+        content_type: &str,
+        boundary: &str,
+    ) -> Self {
+        let mut segments = VecDeque::with_capacity(ranges.len() * 2 + 1);
+        for range in ranges {
+            let header = format!(
+                "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                boundary,
+                content_type,
+                range.content_range(size)
+            );
+            segments.push_back(Segment::Bytes(header.into_bytes().into_boxed_slice()));
+            segments.push_back(Segment::Range(*range));
+        }
+        let trailer = format!("\r\n--{}--\r\n", boundary);
+        segments.push_back(Segment::Bytes(trailer.into_bytes().into_boxed_slice()));
+        Self { file, segments }
+    }
+
+    /// The length of the body in bytes.
+    pub fn content_length(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Bytes(bytes) => bytes.len() as u64,
+                Segment::Range(range) => range.length,
+            })
+            .sum()
+    }
+
+    async fn read_range(&mut self, range: ByteRange) -> io::Result<Vec<u8>> {
+        self.file.seek(SeekFrom::Start(range.start)).await?;
+        let mut buf = vec![0; range.length as usize];
+        self.file.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+
+    async fn copy_range(&mut self, range: ByteRange, writer: &mut WriteHalf) -> io::Result<()> {
+        if zero_copy::sendfile(&self.file, writer, range.start, range.length as usize)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(range.start)).await?;
+        let copied = io::copy(&mut (&mut self.file).take(range.length), writer).await?;
+        if copied < range.length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ResponseBody for FileResponse {
+    async fn read_all(mut self: Box<Self>, len: usize) -> io::Result<String> {
+        let mut buf = Vec::with_capacity(len);
+        while let Some(chunk) = self.read_chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.segments.pop_front() {
+            Some(Segment::Bytes(bytes)) => Ok(Some(bytes.into_vec())),
+            Some(Segment::Range(range)) => {
+                let length = range.length.min(CHUNK_SIZE);
+                if length < range.length {
+                    self.segments.push_front(Segment::Range(ByteRange {
+                        start: range.start + length,
+                        length: range.length - length,
+                    }));
+                }
+                self.read_range(ByteRange {
+                    start: range.start,
+                    length,
+                })
+                .await
+                .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn copy_to<'a>(
+        &mut self,
+        writer: &'a mut WriteHalf,
+        _length: Option<usize>,
+    ) -> io::Result<()> {
+        while let Some(segment) = self.segments.pop_front() {
+            match segment {
+                Segment::Bytes(bytes) => writer.write_all(&bytes).await?,
+                Segment::Range(range) => self.copy_range(range, writer).await?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
-    http::{stream::ReadHalf, Request, Response},
+    http::{request::RequestBody, Request, Response},
     Ctx, Result,
 };
 use async_trait::async_trait;
 use std::{collections::HashMap, io::Read};
 
+pub mod fs;
 pub mod tcp;
 
 pub type Origin = Box<dyn OriginServer + Send + Sync + 'static>;
@@ -16,13 +17,8 @@ pub trait OriginServer {
         std::any::type_name::<Self>()
     }
 
-    async fn connect(
-        &self,
-        context: &Ctx,
-        request: Request,
-        left_rx: ReadHalf,
-        left_remains: Vec<u8>,
-    ) -> Result<Response>;
+    async fn connect(&self, context: &Ctx, request: Request, body: RequestBody)
+        -> Result<Response>;
 }
 
 pub type OriginBuilder = Box<dyn OriginServerBuilder + Send + Sync + 'static>;
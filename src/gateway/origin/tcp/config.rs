@@ -1,16 +1,57 @@
+use crate::{io::proxy::Version, time::Time};
+
 #[derive(Debug)]
 pub struct Connection {
     pub addr: String,
     pub host: Option<String>,
+    pub proxy_protocol: Option<Version>,
+    pub idle_timeout: Option<Time>,
+    pub max_upgrades: Option<usize>,
+    #[cfg(feature = "http2")]
+    pub http2: bool,
 }
 
 impl Connection {
     pub fn new(addr: String) -> Self {
-        Self { addr, host: None }
+        Self {
+            addr,
+            host: None,
+            proxy_protocol: None,
+            idle_timeout: None,
+            max_upgrades: None,
+            #[cfg(feature = "http2")]
+            http2: false,
+        }
     }
 
     pub fn with_host(mut self, host: String) -> Self {
         self.host = Some(host);
         self
     }
+
+    /// Send a PROXY protocol header with the client address before each request.
+    pub fn with_proxy_protocol(mut self, version: Version) -> Self {
+        self.proxy_protocol = Some(version);
+        self
+    }
+
+    /// Close upgraded connections (e.g. WebSockets) when no data flows in either direction.
+    pub fn with_idle_timeout(mut self, idle_timeout: Time) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Limit the number of simultaneously upgraded connections to the origin.
+    /// Upgrade requests over the limit are rejected with `503 Service Unavailable`.
+    pub fn with_max_upgrades(mut self, max_upgrades: usize) -> Self {
+        self.max_upgrades = Some(max_upgrades);
+        self
+    }
+
+    /// Talk to the origin over HTTP/2 with prior knowledge (h2c), e.g. for gRPC services.
+    #[cfg(feature = "http2")]
+    pub fn with_http2(mut self) -> Self {
+        self.http2 = true;
+        self
+    }
 }
@@ -1,5 +1,8 @@
-use crate::{ConfigToContext, Result};
+use std::{sync::Arc, time::Duration};
+
+use crate::{io::proxy::Version, time::TimeUnit, ConfigToContext, Result};
 use async_trait::async_trait;
+use tokio::sync::Semaphore;
 
 use super::config;
 
@@ -7,11 +10,30 @@ use super::config;
 pub struct Connection {
     pub addr: Box<str>,
     pub host: Option<Box<str>>,
+    pub proxy_protocol: Option<Version>,
+    pub idle_timeout: Option<Duration>,
+    pub upgrades: Option<Arc<Semaphore>>,
+    #[cfg(feature = "http2")]
+    pub http2: bool,
 }
 
 impl Connection {
-    pub fn new(addr: Box<str>, host: Option<Box<str>>) -> Self {
-        Self { addr, host }
+    pub fn new(
+        addr: Box<str>,
+        host: Option<Box<str>>,
+        proxy_protocol: Option<Version>,
+        idle_timeout: Option<Duration>,
+        upgrades: Option<Arc<Semaphore>>,
+    ) -> Self {
+        Self {
+            addr,
+            host,
+            proxy_protocol,
+            idle_timeout,
+            upgrades,
+            #[cfg(feature = "http2")]
+            http2: false,
+        }
     }
 }
 
@@ -20,9 +42,20 @@ impl ConfigToContext for config::Connection {
     type Context = Connection;
 
     async fn into_context(self) -> Result<Self::Context> {
-        Ok(Self::Context::new(
+        let connection = Self::Context::new(
             self.addr.into_context().await?,
             self.host.into_context().await?,
-        ))
+            self.proxy_protocol,
+            self.idle_timeout
+                .map(|time| Duration::from_secs(time.convert(TimeUnit::Seconds).amount as u64)),
+            self.max_upgrades
+                .map(|max_upgrades| Arc::new(Semaphore::new(max_upgrades))),
+        );
+        #[cfg(feature = "http2")]
+        let connection = Connection {
+            http2: self.http2,
+            ..connection
+        };
+        Ok(connection)
     }
 }
@@ -0,0 +1,198 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use bytes::Bytes;
+use essentials::{debug, error};
+use h2::{RecvStream, SendStream};
+use http::header;
+use std::io::Cursor;
+use tokio::{
+    io::{self, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    spawn,
+};
+
+use crate::{
+    http::{
+        chunked::{self, Chunk},
+        http2::{remove_connection_headers, send_data},
+        response::ResponseBody,
+        stream::WriteHalf,
+        Body, HeaderMapExt, Request, RequestBody, Response,
+    },
+    Result,
+};
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Send the request to the origin over HTTP/2 with prior knowledge (h2c).
+/// The response body is re-encoded as `Transfer-Encoding: chunked`, so that trailers
+/// (e.g. `grpc-status`) can be passed on to the client.
+pub(super) async fn send(
+    right: TcpStream,
+    request: Request,
+    body: RequestBody,
+) -> Result<Response> {
+    let (client, connection) = h2::client::handshake(right)
+        .await
+        .with_context(|| "Failed to perform HTTP/2 handshake with origin".to_string())?;
+    spawn(async move {
+        if let Err(err) = connection.await {
+            error!(?err, "HTTP/2 connection to origin failed");
+        }
+    });
+    let mut client = client.ready().await?;
+    let chunked = request
+        .header(header::TRANSFER_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+    let length = request.get_content_length().filter(|_| !chunked);
+    let authority = request
+        .header(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("localhost")
+        .to_string();
+    let mut head = http::Request::builder()
+        .method(request.method.clone())
+        .uri(format!("http://{}{}", authority, request.path))
+        .body(())
+        .with_context(|| format!("Invalid request for HTTP/2 origin: {:?}", request))?;
+    *head.headers_mut() = request.headers().clone();
+    remove_connection_headers(head.headers_mut());
+    head.headers_mut().remove(header::HOST);
+    let end_of_stream = !chunked && length.unwrap_or_default() == 0;
+    let (response, send) = client
+        .send_request(head, end_of_stream)
+        .with_context(|| format!("Failed to send request to origin: {:?}", request))?;
+    debug!("Request sent to origin: {:?}", request);
+    if !end_of_stream {
+        spawn(async move {
+            let result = match body {
+                RequestBody::Client {
+                    left_rx,
+                    left_remains,
+                } => {
+                    let reader = BufReader::new(Cursor::new(left_remains).chain(left_rx));
+                    forward_request_body(reader, send, length).await
+                }
+                RequestBody::Stream(body) => forward_body(body, send).await,
+            };
+            if let Err(err) = result {
+                error!(?err, "failed forwarding request body to origin");
+            }
+        });
+    }
+    let (parts, body) = response
+        .await
+        .with_context(|| "Failed to read response from origin:")?
+        .into_parts();
+    let mut response = Response::new(parts.status);
+    *response.headers_mut() = parts.headers;
+    debug!("Response received from origin: {:?}", response);
+    if body.is_end_stream() {
+        response.insert_header(header::CONTENT_LENGTH, "0");
+        return Ok(response);
+    }
+    response.remove_header(header::CONTENT_LENGTH);
+    response.insert_header(header::TRANSFER_ENCODING, "chunked");
+    response.set_body(Http2Response { body });
+    Ok(response)
+}
+
+async fn forward_request_body<R>(
+    mut reader: R,
+    mut send: SendStream<Bytes>,
+    length: Option<usize>,
+) -> Result<()>
+where
+    R: io::AsyncBufRead + Unpin + Send,
+{
+    let Some(mut remaining) = length else {
+        loop {
+            match chunked::read_chunk(&mut reader).await? {
+                Chunk::Data(data) => send_data(&mut send, data.into()).await?,
+                Chunk::End(trailers) if trailers.is_empty() => {
+                    send.send_data(Bytes::new(), true)?;
+                    return Ok(());
+                }
+                Chunk::End(trailers) => {
+                    send.send_trailers(trailers)?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+    let mut buf = vec![0_u8; BUFFER_SIZE];
+    while remaining > 0 {
+        let read = reader.read(&mut buf[..remaining.min(BUFFER_SIZE)]).await?;
+        if read == 0 {
+            break;
+        }
+        remaining -= read;
+        send_data(&mut send, Bytes::copy_from_slice(&buf[..read])).await?;
+    }
+    send.send_data(Bytes::new(), true)?;
+    Ok(())
+}
+
+/// Forward a body attached by a middleware, trailers are not supported.
+async fn forward_body(mut body: Body, mut send: SendStream<Bytes>) -> Result<()> {
+    while let Some(chunk) = body.next_chunk().await? {
+        send_data(&mut send, chunk.into()).await?;
+    }
+    send.send_data(Bytes::new(), true)?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Http2Response {
+    body: RecvStream,
+}
+
+impl Http2Response {
+    async fn next(&mut self) -> io::Result<Option<Bytes>> {
+        match self.body.data().await {
+            Some(data) => {
+                let data = data.map_err(io::Error::other)?;
+                self.body
+                    .flow_control()
+                    .release_capacity(data.len())
+                    .map_err(io::Error::other)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl ResponseBody for Http2Response {
+    async fn read_all(mut self: Box<Self>, len: usize) -> io::Result<String> {
+        let mut buf = Vec::with_capacity(len);
+        while let Some(data) = self.next().await? {
+            buf.extend_from_slice(&data);
+        }
+        String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.next().await?.map(|data| data.to_vec()))
+    }
+
+    async fn copy_to<'a>(
+        &mut self,
+        writer: &'a mut WriteHalf,
+        _length: Option<usize>,
+    ) -> io::Result<()> {
+        while let Some(data) = self.next().await? {
+            chunked::write_chunk(writer, &data).await?;
+        }
+        let trailers = self
+            .body
+            .trailers()
+            .await
+            .map_err(io::Error::other)?
+            .unwrap_or_default();
+        chunked::write_last_chunk(writer, &trailers).await?;
+        writer.flush().await
+    }
+}
@@ -1,8 +1,11 @@
 mod builder;
 pub mod config;
 mod context;
+#[cfg(feature = "http2")]
+mod http2;
 mod origin;
 mod response;
+mod tunnel;
 
 use builder::TcpOriginBuilder;
 use origin::Origin;
@@ -1,11 +1,15 @@
-use super::response::OriginResponse;
+use super::{response::OriginResponse, tunnel::Tunnel};
 use crate::{
-    http::{stream::ReadHalf, HeaderMapExt, ReadResponse, Request, Response},
+    http::{chunked, HeaderMapExt, ReadResponse, Request, RequestBody, Response},
+    io::{
+        proxy::{ProxyHeader, WriteProxyHeader},
+        zero_copy,
+    },
     Ctx, OriginServer, Result, WriteRequest,
 };
 use anyhow::Context;
 use async_trait::async_trait;
-use essentials::{debug, error};
+use essentials::{debug, error, warn};
 use http::{header, StatusCode};
 #[cfg(feature = "tls")]
 use tokio::io::AsyncReadExt;
@@ -19,8 +23,7 @@ impl OriginServer for Origin {
         &self,
         context: &Ctx,
         mut request: Request,
-        mut left_rx: ReadHalf,
-        left_remains: Vec<u8>,
+        body: RequestBody,
     ) -> Result<Response> {
         let connection = match self.0.get(context.app_id) {
             Some(addr) => addr.global(),
@@ -28,14 +31,39 @@ impl OriginServer for Origin {
                 return Ok(Response::new(StatusCode::NOT_FOUND));
             }
         };
-        let right = TcpStream::connect(connection.addr.to_string())
+        let upgrade = request.is_upgrade();
+        let permit = match connection.upgrades.as_ref().filter(|_| upgrade) {
+            Some(upgrades) => match upgrades.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    warn!("Too many upgraded connections to origin");
+                    return Ok(Response::new(StatusCode::SERVICE_UNAVAILABLE));
+                }
+            },
+            None => None,
+        };
+        let mut right = TcpStream::connect(connection.addr.to_string())
             .await
             .with_context(|| "Failed to connect to origin".to_string())?;
-        let (mut right_rx, mut right_tx) = right.into_split();
+        if let Some(version) = connection.proxy_protocol {
+            let header = match (context.peer.remote_addr, context.peer.local_addr) {
+                (Some(source), Some(destination)) => ProxyHeader::new(source, destination),
+                _ => ProxyHeader::local(),
+            };
+            right
+                .write_proxy_header(&header, version)
+                .await
+                .with_context(|| "Failed to send PROXY protocol header to origin".to_string())?;
+        }
         debug!("Connected to origin");
         if let Some(host) = connection.host.as_deref() {
             request.insert_header(header::HOST, host);
         }
+        #[cfg(feature = "http2")]
+        if connection.http2 && !upgrade {
+            return super::http2::send(right, request, body).await;
+        }
+        let (mut right_rx, mut right_tx) = right.into_split();
         right_tx
             .write_request(&request)
             .await
@@ -45,26 +73,52 @@ impl OriginServer for Origin {
             .await
             .with_context(|| "Failed to flush request to origin".to_string())?;
         debug!("Request sent to origin: {:?}", request);
-        right_tx
-            .write_all(left_remains.as_slice())
-            .await
-            .with_context(|| format!("Failed to send remains to origin: {:?}", left_remains))?;
-        debug!("Remains sent to origin: {:?}", left_remains);
-        match request.get_content_length().map(|v| v - left_remains.len()) {
-            Some(size) => {
-                if size > 0 {
-                    #[cfg(not(feature = "tls"))]
-                    ::io::copy_tcp(&mut left_rx, &mut right_tx, Some(size)).await?;
-                    #[cfg(feature = "tls")]
-                    tokio::io::copy(&mut left_rx.take(size as u64), &mut right_tx).await?;
-                }
+        let tunnel = match body {
+            RequestBody::Stream(mut body) => {
+                body.write_to(&mut right_tx)
+                    .await
+                    .with_context(|| "Failed to send body to origin".to_string())?;
+                None
             }
-            None => {
-                spawn(async move {
-                    if let Err(err) = tokio::io::copy(&mut left_rx, &mut right_tx).await {
-                        error!(?err, "failed forwarding request body to origin");
-                    }
-                });
+            RequestBody::Client {
+                mut left_rx,
+                left_remains,
+            } => {
+                right_tx
+                    .write_all(left_remains.as_slice())
+                    .await
+                    .with_context(|| {
+                        format!("Failed to send remains to origin: {:?}", left_remains)
+                    })?;
+                debug!("Remains sent to origin: {:?}", left_remains);
+                if upgrade {
+                    Some((left_rx, right_tx))
+                } else {
+                    match request.get_content_length().map(|v| v - left_remains.len()) {
+                        Some(size) => {
+                            if size > 0
+                                && zero_copy::splice(&mut left_rx, &mut right_tx, Some(size))
+                                    .await?
+                                    .is_none()
+                            {
+                                #[cfg(not(feature = "tls"))]
+                                ::io::copy_tcp(&mut left_rx, &mut right_tx, Some(size)).await?;
+                                #[cfg(feature = "tls")]
+                                tokio::io::copy(&mut left_rx.take(size as u64), &mut right_tx)
+                                    .await?;
+                            }
+                        }
+                        None => {
+                            spawn(async move {
+                                if let Err(err) = tokio::io::copy(&mut left_rx, &mut right_tx).await
+                                {
+                                    error!(?err, "failed forwarding request body to origin");
+                                }
+                            });
+                        }
+                    };
+                    None
+                }
             }
         };
         debug!("Body sent to origin");
@@ -75,10 +129,27 @@ impl OriginServer for Origin {
             .await
             .with_context(|| "Failed to read response from origin:")?;
         debug!("Response received from origin: {:?}", response);
-        response.set_body(OriginResponse {
-            remains: right_remains,
-            reader: right_rx,
-        });
+        match tunnel {
+            Some((left_rx, right_tx)) if response.status == StatusCode::SWITCHING_PROTOCOLS => {
+                debug!("Upgrading connection");
+                response.set_body(Tunnel {
+                    remains: right_remains,
+                    left_rx,
+                    right_rx,
+                    right_tx,
+                    idle_timeout: connection.idle_timeout,
+                    _permit: permit,
+                });
+            }
+            _ => {
+                response.set_body(OriginResponse {
+                    remains: right_remains,
+                    reader: right_rx,
+                    remaining: response.get_content_length(),
+                    decoder: response.is_chunked().then(chunked::Decoder::new),
+                });
+            }
+        }
         Ok(response)
     }
 }
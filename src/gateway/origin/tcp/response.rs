@@ -1,14 +1,71 @@
-use crate::http::{response::ResponseBody, stream::WriteHalf};
+use crate::{
+    http::{chunked, response::ResponseBody, stream::WriteHalf},
+    io::zero_copy,
+};
 use async_trait::async_trait;
 use tokio::{
     io::{self, AsyncReadExt, AsyncWriteExt},
     net::tcp::OwnedReadHalf,
 };
 
+const CHUNK_SIZE: usize = 16 * 1024;
+
 #[derive(Debug)]
 pub struct OriginResponse {
     pub remains: Box<[u8]>,
     pub reader: OwnedReadHalf,
+    /// Bytes of the body left to be read in chunks, until the end of the stream when unknown.
+    pub remaining: Option<usize>,
+    /// Decoder of a `Transfer-Encoding: chunked` body, so that chunks contain only the payload.
+    pub decoder: Option<chunked::Decoder>,
+}
+
+impl OriginResponse {
+    async fn read_raw_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let limit = self.remaining.unwrap_or(usize::MAX);
+        if limit == 0 {
+            return Ok(None);
+        }
+        let chunk = if self.remains.is_empty() {
+            let mut chunk = vec![0; limit.min(CHUNK_SIZE)];
+            let read = self.reader.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+            chunk.truncate(read);
+            chunk
+        } else {
+            let mut chunk = std::mem::take(&mut self.remains).into_vec();
+            chunk.truncate(limit);
+            chunk
+        };
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= chunk.len();
+        }
+        Ok(Some(chunk))
+    }
+
+    async fn read_decoded_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut output = Vec::new();
+        while let Some(decoder) = self.decoder.as_mut().filter(|decoder| !decoder.is_done()) {
+            let input = if self.remains.is_empty() {
+                let mut input = vec![0; CHUNK_SIZE];
+                let read = self.reader.read(&mut input).await?;
+                if read == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                input.truncate(read);
+                input
+            } else {
+                std::mem::take(&mut self.remains).into_vec()
+            };
+            decoder.decode(&input, &mut output)?;
+            if !output.is_empty() {
+                return Ok(Some(output));
+            }
+        }
+        Ok(None)
+    }
 }
 
 #[async_trait]
@@ -28,6 +85,13 @@ impl ResponseBody for OriginResponse {
         Ok(buf)
     }
 
+    async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.decoder.is_some() {
+            return self.read_decoded_chunk().await;
+        }
+        self.read_raw_chunk().await
+    }
+
     async fn copy_to<'a>(
         &mut self,
         writer: &'a mut WriteHalf,
@@ -40,6 +104,13 @@ impl ResponseBody for OriginResponse {
             }
         }
         writer.write_all(&self.remains).await?;
+        let remaining = length.map(|length| length.saturating_sub(self.remains.len()));
+        if zero_copy::splice(&mut self.reader, writer, remaining)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
         #[cfg(feature = "tls")]
         tokio::io::copy(&mut self.reader, writer).await?;
         #[cfg(not(feature = "tls"))]
@@ -0,0 +1,101 @@
+use crate::http::{
+    response::ResponseBody,
+    stream::{ReadHalf, WriteHalf},
+};
+use async_trait::async_trait;
+use std::{fmt::Debug, time::Duration};
+use tokio::{
+    io::{self, AsyncReadExt, AsyncWriteExt},
+    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+    sync::OwnedSemaphorePermit,
+    time::timeout,
+};
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Body of a `101 Switching Protocols` response.
+/// Splices the client and the origin connections in both directions until either side closes.
+pub struct Tunnel {
+    pub remains: Box<[u8]>,
+    pub left_rx: ReadHalf,
+    pub right_rx: OwnedReadHalf,
+    pub right_tx: OwnedWriteHalf,
+    pub idle_timeout: Option<Duration>,
+    pub _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Debug for Tunnel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tunnel")
+            .field("remains", &self.remains)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+enum Event {
+    Upstream(io::Result<usize>),
+    Downstream(io::Result<usize>),
+}
+
+#[async_trait]
+impl ResponseBody for Tunnel {
+    async fn read_all(self: Box<Self>, _len: usize) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "upgraded connections cannot be buffered",
+        ))
+    }
+
+    async fn copy_to<'a>(
+        &mut self,
+        writer: &'a mut WriteHalf,
+        _length: Option<usize>,
+    ) -> io::Result<()> {
+        let Self {
+            remains,
+            left_rx,
+            right_rx,
+            right_tx,
+            idle_timeout,
+            ..
+        } = self;
+        writer.write_all(&remains[..]).await?;
+        writer.flush().await?;
+        let mut upstream = vec![0_u8; BUFFER_SIZE];
+        let mut downstream = vec![0_u8; BUFFER_SIZE];
+        loop {
+            let event = async {
+                tokio::select! {
+                    read = left_rx.read(&mut upstream) => Event::Upstream(read),
+                    read = right_rx.read(&mut downstream) => Event::Downstream(read),
+                }
+            };
+            let event = match idle_timeout {
+                Some(idle_timeout) => timeout(*idle_timeout, event)
+                    .await
+                    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connection idle"))?,
+                None => event.await,
+            };
+            match event {
+                Event::Upstream(read) => {
+                    let read = read?;
+                    if read == 0 {
+                        break;
+                    }
+                    right_tx.write_all(&upstream[..read]).await?;
+                }
+                Event::Downstream(read) => {
+                    let read = read?;
+                    if read == 0 {
+                        break;
+                    }
+                    writer.write_all(&downstream[..read]).await?;
+                    writer.flush().await?;
+                }
+            }
+        }
+        right_tx.shutdown().await?;
+        Ok(())
+    }
+}
@@ -18,6 +18,20 @@ impl ParamRouterBuilder {
         self.routes.push((method, path, endpoint_id));
         self
     }
+
+    /// Route a single gRPC method, e.g. `add_grpc_method("helloworld.Greeter", "SayHello", ..)`.
+    pub fn add_grpc_method(self, service: &str, method: &str, endpoint_id: String) -> Self {
+        self.add_route(
+            Method::POST,
+            format!("/{}/{}", service, method),
+            endpoint_id,
+        )
+    }
+
+    /// Route all methods of a gRPC service, e.g. `add_grpc_service("helloworld.Greeter", ..)`.
+    pub fn add_grpc_service(self, service: &str, endpoint_id: String) -> Self {
+        self.add_route(Method::POST, format!("/{}/:method", service), endpoint_id)
+    }
 }
 
 impl RouterBuilder for ParamRouterBuilder {
@@ -0,0 +1,403 @@
+use async_trait::async_trait;
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use http::HeaderMap;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{chunked, response::ResponseBody, stream::WriteHalf};
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// A body of a request or a response as a stream of byte chunks with an optional known length.
+///
+/// Bodies with a known length are written as is, otherwise with `Transfer-Encoding: chunked`.
+pub struct Body {
+    // The stream is only ever accessed mutably, the mutex makes the body `Sync`.
+    stream: Mutex<BoxStream<'static, io::Result<Vec<u8>>>>,
+    length: Option<usize>,
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Body")
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A transformation applied to the chunks of a body while it is streamed.
+pub trait Transform: Send + 'static {
+    /// Transform a chunk of the body, returning the output available so far.
+    fn transform(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>>;
+
+    /// Return the remaining output once the body ends.
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    /// The length of the transformed body of the given length, if it is known in advance.
+    fn length(&self, _length: Option<usize>) -> Option<usize> {
+        None
+    }
+}
+
+impl Body {
+    pub fn new<S>(stream: S, length: Option<usize>) -> Self
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        Self {
+            stream: Mutex::new(stream.boxed()),
+            length,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(stream::empty(), Some(0))
+    }
+
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        let bytes = bytes.into();
+        let length = bytes.len();
+        Self::new(stream::once(async move { Ok(bytes) }), Some(length))
+    }
+
+    /// Read the body from the reader, up to the given length or until the end of the stream.
+    pub fn from_reader<R>(reader: R, length: Option<usize>) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let stream = stream::try_unfold(
+            (reader, length.unwrap_or(usize::MAX)),
+            |(mut reader, remaining)| async move {
+                if remaining == 0 {
+                    return Ok(None);
+                }
+                let mut chunk = vec![0; remaining.min(CHUNK_SIZE)];
+                let read = reader.read(&mut chunk).await?;
+                if read == 0 {
+                    return match length {
+                        Some(_) => Err(io::ErrorKind::UnexpectedEof.into()),
+                        None => Ok(None),
+                    };
+                }
+                chunk.truncate(read);
+                Ok(Some((chunk, (reader, remaining - read))))
+            },
+        );
+        Self::new(stream, length)
+    }
+
+    /// Read the body from the reader of a `Transfer-Encoding: chunked` body.
+    pub fn from_chunked<R>(reader: R) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let stream = stream::try_unfold(
+            (reader, chunked::Decoder::new()),
+            |(mut reader, mut decoder)| async move {
+                if decoder.is_done() {
+                    return Ok(None);
+                }
+                let mut input = vec![0; CHUNK_SIZE];
+                let read = reader.read(&mut input).await?;
+                if read == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                let mut output = Vec::with_capacity(read);
+                decoder.decode(&input[..read], &mut output)?;
+                Ok(Some((output, (reader, decoder))))
+            },
+        );
+        Self::new(stream, None)
+    }
+
+    /// Stream the chunks of a response body, see [`ResponseBody::read_chunk`].
+    pub fn from_response_body(
+        body: Box<dyn ResponseBody + Send + Sync + 'static>,
+        length: Option<usize>,
+    ) -> Self {
+        let stream = stream::try_unfold(body, |mut body| async move {
+            Ok(body.read_chunk().await?.map(|chunk| (chunk, body)))
+        });
+        Self::new(stream, length)
+    }
+
+    /// The length of the body, if it is known in advance.
+    pub fn length(&self) -> Option<usize> {
+        self.length
+    }
+
+    /// Read the next non-empty chunk of the body, `None` once the body is complete.
+    pub async fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let stream = self.stream.get_mut().unwrap();
+        while let Some(chunk) = stream.next().await.transpose()? {
+            if !chunk.is_empty() {
+                return Ok(Some(chunk));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Buffer the whole body, failing when it is longer than the limit.
+    pub async fn collect(mut self, limit: usize) -> io::Result<Vec<u8>> {
+        if self.length.is_some_and(|length| length > limit) {
+            return Err(too_large());
+        }
+        let mut buf = Vec::with_capacity(self.length.unwrap_or_default());
+        while let Some(chunk) = self.next_chunk().await? {
+            if buf.len() + chunk.len() > limit {
+                return Err(too_large());
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
+    }
+
+    /// Write the body as is when its length is known, otherwise with chunked framing.
+    pub async fn write_to<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let chunked = self.length.is_none();
+        while let Some(chunk) = self.next_chunk().await? {
+            if chunked {
+                chunked::write_chunk(writer, &chunk).await?;
+            } else {
+                writer.write_all(&chunk).await?;
+            }
+        }
+        if chunked {
+            chunked::write_last_chunk(writer, &HeaderMap::new()).await?;
+        }
+        writer.flush().await
+    }
+
+    /// Apply the transform to the chunks of the body while it is streamed.
+    pub fn transform<T: Transform>(self, transform: T) -> Self {
+        let length = transform.length(self.length);
+        let stream = self.stream.into_inner().unwrap();
+        let stream = stream::unfold(Some((stream, transform)), |state| async move {
+            let (mut stream, mut transform) = state?;
+            loop {
+                let result = match stream.next().await {
+                    Some(Ok(chunk)) => transform.transform(chunk),
+                    Some(Err(err)) => Err(err),
+                    None => {
+                        return match transform.finish() {
+                            Ok(output) if output.is_empty() => None,
+                            result => Some((result, None)),
+                        };
+                    }
+                };
+                match result {
+                    Ok(output) if output.is_empty() => {}
+                    Ok(output) => return Some((Ok(output), Some((stream, transform)))),
+                    Err(err) => return Some((Err(err), None)),
+                }
+            }
+        });
+        Self::new(stream, length)
+    }
+
+    /// Call the function with each chunk of the body, e.g. to hash it or count its size.
+    pub fn inspect<F>(self, f: F) -> Self
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        self.transform(Inspect(f))
+    }
+
+    /// Replace all occurrences of the pattern in the body, including across chunks.
+    pub fn replace(self, from: &[u8], to: &[u8]) -> Self {
+        if from.is_empty() {
+            return self;
+        }
+        self.transform(Replace {
+            from: from.to_vec(),
+            to: to.to_vec(),
+            pending: Vec::new(),
+        })
+    }
+}
+
+impl Stream for Body {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().stream.get_mut().unwrap().poll_next_unpin(cx)
+    }
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "body exceeds the size limit")
+}
+
+struct Inspect<F>(F);
+
+impl<F> Transform for Inspect<F>
+where
+    F: FnMut(&[u8]) + Send + 'static,
+{
+    fn transform(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>> {
+        (self.0)(&chunk);
+        Ok(chunk)
+    }
+
+    fn length(&self, length: Option<usize>) -> Option<usize> {
+        length
+    }
+}
+
+struct Replace {
+    from: Vec<u8>,
+    to: Vec<u8>,
+    /// The end of the previous chunks which may be the beginning of a match.
+    pending: Vec<u8>,
+}
+
+impl Transform for Replace {
+    fn transform(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut buf = std::mem::take(&mut self.pending);
+        buf.extend_from_slice(&chunk);
+        let mut output = Vec::with_capacity(buf.len());
+        let mut start = 0;
+        while let Some(position) = buf[start..]
+            .windows(self.from.len())
+            .position(|window| window == self.from)
+        {
+            output.extend_from_slice(&buf[start..start + position]);
+            output.extend_from_slice(&self.to);
+            start += position + self.from.len();
+        }
+        let keep = (buf.len() - start).min(self.from.len() - 1);
+        output.extend_from_slice(&buf[start..buf.len() - keep]);
+        self.pending = buf[buf.len() - keep..].to_vec();
+        Ok(output)
+    }
+
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        Ok(std::mem::take(&mut self.pending))
+    }
+
+    fn length(&self, length: Option<usize>) -> Option<usize> {
+        length.filter(|_| self.from.len() == self.to.len())
+    }
+}
+
+#[async_trait]
+impl ResponseBody for Body {
+    async fn read_all(self: Box<Self>, _len: usize) -> io::Result<String> {
+        let body = self.collect(usize::MAX).await?;
+        String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.next_chunk().await
+    }
+
+    async fn copy_to<'a>(
+        &mut self,
+        writer: &'a mut WriteHalf,
+        _length: Option<usize>,
+    ) -> io::Result<()> {
+        self.write_to(writer).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    fn chunks(chunks: &[&str]) -> Body {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| Ok(chunk.as_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        Body::new(stream::iter(chunks), None)
+    }
+
+    #[tokio::test]
+    async fn test_replace() {
+        let body =
+            chunks(&["Hello, wo", "rld! Hello, w", "", "orld", "!"]).replace(b"world", b"there");
+        assert_eq!(body.length(), None);
+        assert_eq!(
+            body.collect(1024).await.unwrap(),
+            b"Hello, there! Hello, there!"
+        );
+        let body = Body::from_bytes("aaaa").replace(b"aa", b"b");
+        assert_eq!(body.collect(1024).await.unwrap(), b"bb");
+    }
+
+    struct Uppercase;
+
+    impl Transform for Uppercase {
+        fn transform(&mut self, chunk: Vec<u8>) -> io::Result<Vec<u8>> {
+            Ok(chunk.to_ascii_uppercase())
+        }
+
+        fn finish(&mut self) -> io::Result<Vec<u8>> {
+            Ok(b"!".to_vec())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transform() {
+        let mut body = chunks(&["hello, ", "", "world"]).transform(Uppercase);
+        assert_eq!(body.next_chunk().await.unwrap(), Some(b"HELLO, ".to_vec()));
+        assert_eq!(body.next_chunk().await.unwrap(), Some(b"WORLD".to_vec()));
+        assert_eq!(body.next_chunk().await.unwrap(), Some(b"!".to_vec()));
+        assert_eq!(body.next_chunk().await.unwrap(), None);
+        let body = Body::from_bytes("hello").transform(Uppercase);
+        assert_eq!(body.length(), None);
+        assert!(body.collect(5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inspect() {
+        let size = Arc::new(AtomicUsize::new(0));
+        let counter = size.clone();
+        let body = Body::from_bytes("Hello, world!").inspect(move |chunk| {
+            counter.fetch_add(chunk.len(), Ordering::SeqCst);
+        });
+        assert_eq!(body.length(), Some(13));
+        assert_eq!(body.collect(1024).await.unwrap(), b"Hello, world!");
+        assert_eq!(size.load(Ordering::SeqCst), 13);
+    }
+
+    #[tokio::test]
+    async fn test_from_chunked() {
+        let data: &[u8] = b"7\r\nHello, \r\n6\r\nworld!\r\n0\r\n\r\n";
+        let body = Body::from_chunked(data);
+        assert_eq!(body.collect(1024).await.unwrap(), b"Hello, world!");
+        let data: &[u8] = b"7\r\nHello, \r\n";
+        assert!(Body::from_chunked(data).collect(1024).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_from_reader() {
+        let data: &[u8] = b"Hello, world!";
+        let body = Body::from_reader(data, Some(5));
+        assert_eq!(body.collect(1024).await.unwrap(), b"Hello");
+        assert!(Body::from_reader(data, Some(20))
+            .collect(1024)
+            .await
+            .is_err());
+        assert!(Body::from_reader(data, None).collect(5).await.is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use http::HeaderMap;
+use tokio::io::{self, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{ReadHeaders, WriteHeaders};
+
+/// A single part of a `Transfer-Encoding: chunked` body.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Chunk {
+    Data(Vec<u8>),
+    /// The last chunk together with the trailer section.
+    End(HeaderMap),
+}
+
+/// Read a single chunk of a `Transfer-Encoding: chunked` body.
+pub async fn read_chunk<R>(reader: &mut R) -> io::Result<Chunk>
+where
+    R: AsyncBufRead + ?Sized + Unpin + Send,
+{
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let size = line
+        .split(';')
+        .next()
+        .map(str::trim)
+        .and_then(|size| usize::from_str_radix(size, 16).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+    if size == 0 {
+        return Ok(Chunk::End(reader.read_headers().await?));
+    }
+    let mut data = vec![0_u8; size + 2];
+    reader.read_exact(&mut data).await?;
+    if !data.ends_with(b"\r\n") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunk is not terminated by CRLF",
+        ));
+    }
+    data.truncate(size);
+    Ok(Chunk::Data(data))
+}
+
+/// Write a single chunk of a `Transfer-Encoding: chunked` body.
+/// Empty data is skipped, since a zero-sized chunk marks the end of the body.
+pub async fn write_chunk<W>(writer: &mut W, data: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + ?Sized + Unpin + Send,
+{
+    if data.is_empty() {
+        return Ok(());
+    }
+    writer
+        .write_all(format!("{:x}\r\n", data.len()).as_bytes())
+        .await?;
+    writer.write_all(data).await?;
+    writer.write_all(b"\r\n").await
+}
+
+/// Write the last chunk of a `Transfer-Encoding: chunked` body followed by the trailer section.
+pub async fn write_last_chunk<W>(writer: &mut W, trailers: &HeaderMap) -> io::Result<()>
+where
+    W: AsyncWrite + ?Sized + Unpin + Send,
+{
+    writer.write_all(b"0\r\n").await?;
+    writer.write_headers(trailers).await?;
+    writer.write_all(b"\r\n").await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// Incremental decoder of a `Transfer-Encoding: chunked` body fed with arbitrary slices of it.
+/// Trailers are skipped.
+#[derive(Debug)]
+pub struct Decoder {
+    state: State,
+    line: Vec<u8>,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self {
+            state: State::Size,
+            line: Vec::new(),
+        }
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the last chunk and the trailer section have been decoded.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Decode the input, appending the data of the body to the output.
+    /// Input following the end of the body is ignored.
+    pub fn decode(&mut self, mut input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        while !input.is_empty() {
+            match self.state {
+                State::Size | State::Trailers | State::DataEnd => {
+                    let (line, complete) = match input.iter().position(|b| *b == b'\n') {
+                        Some(end) => (&input[..end], true),
+                        None => (input, false),
+                    };
+                    self.line.extend_from_slice(line);
+                    input = &input[(line.len() + complete as usize)..];
+                    if complete {
+                        self.end_line()?;
+                    }
+                }
+                State::Data(remaining) => {
+                    let length = remaining.min(input.len());
+                    output.extend_from_slice(&input[..length]);
+                    input = &input[length..];
+                    self.state = match remaining - length {
+                        0 => State::DataEnd,
+                        remaining => State::Data(remaining),
+                    };
+                }
+                State::Done => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> io::Result<()> {
+        let line = std::mem::take(&mut self.line);
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        self.state = match self.state {
+            State::Size => {
+                let size = std::str::from_utf8(line)
+                    .ok()
+                    .and_then(|line| line.split(';').next())
+                    .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size")
+                    })?;
+                match size {
+                    0 => State::Trailers,
+                    size => State::Data(size),
+                }
+            }
+            State::DataEnd if line.is_empty() => State::Size,
+            State::DataEnd => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "chunk is not terminated by CRLF",
+                ));
+            }
+            State::Trailers if line.is_empty() => State::Done,
+            state => state,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::io::BufReader;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let mut buf = Vec::new();
+        write_chunk(&mut buf, b"Hello, ").await.unwrap();
+        write_chunk(&mut buf, b"").await.unwrap();
+        write_chunk(&mut buf, b"world!").await.unwrap();
+        write_last_chunk(&mut buf, &HeaderMap::new()).await.unwrap();
+        assert_eq!(buf, b"7\r\nHello, \r\n6\r\nworld!\r\n0\r\n\r\n");
+        let mut reader = BufReader::new(buf.as_slice());
+        assert_eq!(
+            read_chunk(&mut reader).await.unwrap(),
+            Chunk::Data(b"Hello, ".to_vec())
+        );
+        assert_eq!(
+            read_chunk(&mut reader).await.unwrap(),
+            Chunk::Data(b"world!".to_vec())
+        );
+        assert_eq!(
+            read_chunk(&mut reader).await.unwrap(),
+            Chunk::End(HeaderMap::new())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+        let mut buf = Vec::new();
+        write_last_chunk(&mut buf, &trailers).await.unwrap();
+        assert_eq!(buf, b"0\r\ngrpc-status: 0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_decoder() {
+        let data: &[u8] = b"7;ext=1\r\nHello, \r\n6\r\nworld!\r\n0\r\ngrpc-status: 0\r\n\r\nrest";
+        for size in 1..data.len() {
+            let mut decoder = Decoder::new();
+            let mut output = Vec::new();
+            for slice in data.chunks(size) {
+                decoder.decode(slice, &mut output).unwrap();
+            }
+            assert!(decoder.is_done());
+            assert_eq!(output, b"Hello, world!");
+        }
+        let mut decoder = Decoder::new();
+        assert!(decoder.decode(b"x\r\n", &mut Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trailers() {
+        let data: &[u8] = b"4;ext=1\r\nbody\r\n0\r\ngrpc-status: 0\r\n\r\n";
+        let mut reader = BufReader::new(data);
+        assert_eq!(
+            read_chunk(&mut reader).await.unwrap(),
+            Chunk::Data(b"body".to_vec())
+        );
+        match read_chunk(&mut reader).await.unwrap() {
+            Chunk::End(trailers) => assert_eq!(trailers.get("grpc-status").unwrap(), "0"),
+            chunk => panic!("unexpected chunk: {:?}", chunk),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use http::{header, StatusCode};
+
+use super::{headers, HeaderMapExt, Response};
+
+pub const CONTENT_TYPE: &str = "application/grpc";
+
+/// gRPC status codes as defined by <https://grpc.github.io/grpc/core/md_doc_statuscodes.html>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Code {
+    Ok = 0,
+    Cancelled = 1,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    AlreadyExists = 6,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    FailedPrecondition = 9,
+    Aborted = 10,
+    OutOfRange = 11,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    DataLoss = 15,
+    Unauthenticated = 16,
+}
+
+impl From<StatusCode> for Code {
+    fn from(status: StatusCode) -> Self {
+        match status {
+            StatusCode::OK => Code::Ok,
+            StatusCode::BAD_REQUEST => Code::Internal,
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::NOT_FOUND => Code::Unimplemented,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => Code::DeadlineExceeded,
+            StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+            status if status.is_server_error() => Code::Internal,
+            _ => Code::Unknown,
+        }
+    }
+}
+
+/// Create a "Trailers-Only" gRPC response carrying the given status.
+pub fn error(code: Code, message: &str) -> Response {
+    let mut response = Response::new(StatusCode::OK);
+    response.insert_header(header::CONTENT_TYPE, CONTENT_TYPE);
+    response.insert_header(&headers::GRPC_STATUS, (code as u8).to_string());
+    if !message.is_empty() {
+        response.insert_header(&headers::GRPC_MESSAGE, message);
+    }
+    response
+}
+
+/// Translate a response which is not a valid gRPC response (e.g. generated by a middleware)
+/// into a gRPC error, so that clients receive a proper `grpc-status`.
+pub fn translate(response: Response) -> Response {
+    if response.status == StatusCode::OK || response.header(&headers::GRPC_STATUS).is_some() {
+        return response;
+    }
+    let mut translated = error(
+        Code::from(response.status),
+        response.status.canonical_reason().unwrap_or_default(),
+    );
+    for name in [
+        &headers::RATE_LIMIT_LIMIT,
+        &headers::RATE_LIMIT_REMAINING,
+        &headers::RATE_LIMIT_RESET,
+    ] {
+        if let Some(value) = response.header(name) {
+            translated.insert_header(name, value.clone());
+        }
+    }
+    translated
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        let response = translate(Response::new(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.header(&headers::GRPC_STATUS).unwrap(), "8");
+        assert_eq!(
+            response.header(&headers::GRPC_MESSAGE).unwrap(),
+            "Too Many Requests"
+        );
+        let response = translate(Response::new(StatusCode::BAD_GATEWAY));
+        assert_eq!(response.header(&headers::GRPC_STATUS).unwrap(), "14");
+        let response = translate(Response::new(StatusCode::UNAUTHORIZED));
+        assert_eq!(response.header(&headers::GRPC_STATUS).unwrap(), "16");
+    }
+
+    #[test]
+    fn test_keep_grpc_response() {
+        let response = translate(error(Code::NotFound, "missing"));
+        assert_eq!(response.header(&headers::GRPC_STATUS).unwrap(), "5");
+        assert_eq!(response.header(&headers::GRPC_MESSAGE).unwrap(), "missing");
+    }
+}
@@ -12,10 +12,18 @@ use crate::io::error::{error, Headers};
 
 pub static API_TOKEN: HeaderName = HeaderName::from_static("x-api-token");
 pub static USERNAME: HeaderName = HeaderName::from_static("x-username");
+pub static CLIENT_SUBJECT: HeaderName = HeaderName::from_static("x-client-subject");
+pub static CLIENT_SAN: HeaderName = HeaderName::from_static("x-client-san");
+pub static CLIENT_FINGERPRINT: HeaderName = HeaderName::from_static("x-client-fingerprint");
 pub static REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+pub static FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
 pub static RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
 pub static RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
 pub static RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+pub static X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+pub static SURROGATE_KEY: HeaderName = HeaderName::from_static("surrogate-key");
+pub static GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+pub static GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
 
 #[async_trait]
 pub trait WriteHeaders {
@@ -77,6 +85,45 @@ pub trait HeaderMapExt {
 
     fn headers_mut(&mut self) -> &mut HeaderMap;
 
+    /// Whether the message asks for a protocol upgrade (e.g. WebSocket).
+    fn is_upgrade(&self) -> bool {
+        self.headers().contains_key(http::header::UPGRADE)
+            && self
+                .headers()
+                .get(http::header::CONNECTION)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| {
+                    value
+                        .split(',')
+                        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+                })
+    }
+
+    /// Whether the message is a gRPC call (`Content-Type: application/grpc[+proto|+json|...]`).
+    fn is_grpc(&self) -> bool {
+        self.headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .strip_prefix(crate::http::grpc::CONTENT_TYPE)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['+', ';']))
+            })
+    }
+
+    /// Whether the body is sent with `Transfer-Encoding: chunked`.
+    fn is_chunked(&self) -> bool {
+        self.headers()
+            .get(http::header::TRANSFER_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .rsplit(',')
+                    .next()
+                    .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+            })
+    }
+
     fn get_content_length(&self) -> Option<usize> {
         self.headers()
             .get(http::header::CONTENT_LENGTH)?
@@ -86,6 +133,24 @@ pub trait HeaderMapExt {
             .ok()
     }
 
+    /// Set the framing headers for a body of the given length, chunked when it is unknown.
+    fn set_body_length(&mut self, length: Option<usize>) {
+        let headers = self.headers_mut();
+        match length {
+            Some(length) => {
+                headers.remove(http::header::TRANSFER_ENCODING);
+                headers.insert(http::header::CONTENT_LENGTH, HeaderValue::from(length));
+            }
+            None => {
+                headers.remove(http::header::CONTENT_LENGTH);
+                headers.insert(
+                    http::header::TRANSFER_ENCODING,
+                    HeaderValue::from_static("chunked"),
+                );
+            }
+        }
+    }
+
     fn parse_header(&mut self, header: String) -> io::Result<()> {
         debug!(?header, "Parsing header");
         let i = header.find(':').ok_or_else(|| {
@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::future::poll_fn;
+use h2::SendStream;
+use http::{header, HeaderMap, HeaderName};
+
+/// Connection-specific headers which must not be sent over HTTP/2.
+static CONNECTION_HEADERS: [HeaderName; 5] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    HeaderName::from_static("proxy-connection"),
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Remove the headers which are specific to a single HTTP/1.1 connection.
+pub(crate) fn remove_connection_headers(headers: &mut HeaderMap) {
+    for name in CONNECTION_HEADERS.iter() {
+        headers.remove(name);
+    }
+}
+
+/// Send data respecting the HTTP/2 flow control window of the stream.
+pub(crate) async fn send_data(send: &mut SendStream<Bytes>, mut data: Bytes) -> Result<()> {
+    while !data.is_empty() {
+        send.reserve_capacity(data.len());
+        let capacity = poll_fn(|cx| send.poll_capacity(cx))
+            .await
+            .ok_or_else(|| anyhow!("HTTP/2 stream closed"))??;
+        send.send_data(data.split_to(capacity.min(data.len())), false)?;
+    }
+    Ok(())
+}
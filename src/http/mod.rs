@@ -1,9 +1,15 @@
+pub mod body;
+pub mod chunked;
+pub mod grpc;
 pub mod headers;
+#[cfg(feature = "http2")]
+pub(crate) mod http2;
 pub mod request;
 pub mod response;
 pub mod server;
 pub mod stream;
 
+pub use body::{Body, Transform};
 pub use headers::{HeaderMapExt, ReadHeaders, WriteHeaders};
-pub use request::{ReadRequest, Request, WriteRequest};
+pub use request::{ReadRequest, Request, RequestBody, WriteRequest};
 pub use response::{ReadResponse, Response, WriteResponse};
@@ -1,9 +1,10 @@
 use crate::io::error::{error, RequestStatusLine};
 
-use super::{headers::HeaderMapExt, ReadHeaders, WriteHeaders};
+use super::{body::Body, headers::HeaderMapExt, stream::ReadHalf, ReadHeaders, WriteHeaders};
 use async_trait::async_trait;
 use http::{HeaderMap, Method};
-use tokio::io::{self, AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use std::{fmt, io::Cursor};
+use tokio::io::{self, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 #[derive(Debug, Clone)]
 pub struct Request {
@@ -24,6 +25,77 @@ impl Request {
     }
 }
 
+/// Body of a request passed along the middlewares to the origin.
+pub enum RequestBody {
+    /// The body is still on the client connection, starting with the bytes read
+    /// together with the request head.
+    Client {
+        left_rx: ReadHalf,
+        left_remains: Vec<u8>,
+    },
+    /// The body was taken, buffered or replaced by a middleware.
+    Stream(Body),
+}
+
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestBody::Client { left_remains, .. } => f
+                .debug_struct("Client")
+                .field("left_remains", left_remains)
+                .finish_non_exhaustive(),
+            RequestBody::Stream(body) => f.debug_tuple("Stream").field(body).finish(),
+        }
+    }
+}
+
+impl RequestBody {
+    pub fn new(left_rx: ReadHalf, left_remains: Vec<u8>) -> Self {
+        Self::Client {
+            left_rx,
+            left_remains,
+        }
+    }
+
+    /// Take the body out to be read as a stream, the request is forwarded without a body
+    /// unless it is replaced.
+    /// Upgrade requests keep the client connection, which is tunneled to the origin.
+    pub fn take(&mut self, request: &Request) -> Body {
+        if request.is_upgrade() {
+            return Body::empty();
+        }
+        match std::mem::replace(self, Self::Stream(Body::empty())) {
+            Self::Client {
+                left_rx,
+                left_remains,
+            } => {
+                let reader = Cursor::new(left_remains).chain(left_rx);
+                if request.is_chunked() {
+                    Body::from_chunked(reader)
+                } else {
+                    Body::from_reader(reader, Some(request.get_content_length().unwrap_or(0)))
+                }
+            }
+            Self::Stream(body) => body,
+        }
+    }
+
+    /// Replace the body forwarded to the origin.
+    pub fn replace(&mut self, body: Body) {
+        *self = Self::Stream(body);
+    }
+
+    /// Buffer the whole body, failing when it is longer than the limit.
+    /// The buffered body is still forwarded to the origin unless it is replaced.
+    pub async fn collect(&mut self, request: &Request, limit: usize) -> io::Result<Vec<u8>> {
+        let body = self.take(request).collect(limit).await?;
+        if !request.is_upgrade() {
+            self.replace(Body::from_bytes(body.clone()));
+        }
+        Ok(body)
+    }
+}
+
 impl HeaderMapExt for Request {
     fn headers(&self) -> &HeaderMap {
         &self.headers
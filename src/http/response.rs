@@ -1,4 +1,4 @@
-use super::{headers::HeaderMapExt, stream::WriteHalf, WriteHeaders};
+use super::{body::Body, headers::HeaderMapExt, stream::WriteHalf, WriteHeaders};
 use crate::io::error::{error, ResponseStatusLine};
 use async_trait::async_trait;
 use essentials::debug;
@@ -21,6 +21,15 @@ pub struct Response {
 pub trait ResponseBody: Debug {
     async fn read_all(self: Box<Self>, len: usize) -> io::Result<String>;
 
+    /// Read the next chunk of the body, `None` once the body is complete.
+    /// Bodies which cannot be read in chunks (e.g. upgraded connections) return an error.
+    async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "body cannot be read in chunks",
+        ))
+    }
+
     async fn copy_to<'a>(
         &mut self,
         writer: &'a mut WriteHalf,
@@ -54,6 +63,25 @@ impl Response {
     pub fn body(self) -> Option<Box<dyn ResponseBody + Send + Sync + 'static>> {
         self.body
     }
+
+    /// Take the body out of the response, e.g. to wrap it in another body.
+    pub fn take_body(&mut self) -> Option<Box<dyn ResponseBody + Send + Sync + 'static>> {
+        self.body.take()
+    }
+
+    /// Take the body out of the response as a stream of chunks.
+    /// The length is unknown for `Transfer-Encoding: chunked` responses.
+    pub fn take_stream(&mut self) -> Option<Body> {
+        let body = self.body.take()?;
+        let length = self.get_content_length().filter(|_| !self.is_chunked());
+        Some(Body::from_response_body(body, length))
+    }
+
+    /// Replace the body with the stream and update the framing headers to match its length.
+    pub fn set_stream(&mut self, body: Body) {
+        self.set_body_length(body.length());
+        self.set_body(body);
+    }
 }
 
 impl HeaderMapExt for Response {
@@ -1,31 +1,159 @@
+use futures::task::AtomicWaker;
+use std::any::Any;
+use std::cell::UnsafeCell;
 use std::io;
 use std::pin::Pin;
-use std::sync::{Arc, Mutex};
-use std::task::{Context, Poll};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tokio::net::TcpStream;
-use tokio_rustls::server::TlsStream;
 
-type Stream = Arc<Mutex<TlsStream<TcpStream>>>;
+/// A bidirectional stream which can be split into [`ReadHalf`] and [`WriteHalf`].
+pub trait AsyncStream: AsyncRead + AsyncWrite + Send + Unpin {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<S> AsyncStream for S
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
 
 pub struct ReadHalf {
-    inner: Stream,
+    inner: Arc<Inner>,
 }
 
 pub struct WriteHalf {
-    inner: Stream,
+    inner: Arc<Inner>,
 }
 
-pub fn split(stream: TlsStream<TcpStream>) -> (ReadHalf, WriteHalf) {
-    let stream = Arc::new(Mutex::new(stream));
+/// Split the stream into independently usable read and write halves.
+///
+/// The stream is only borrowed for the duration of a single `poll_*` call. A half finding the
+/// stream borrowed by the other half returns [`Poll::Pending`] and is woken once it is released,
+/// so the halves never block the executor and can be driven from different tasks.
+pub fn split<S>(stream: S) -> (ReadHalf, WriteHalf)
+where
+    S: AsyncStream + 'static,
+{
+    let inner = Arc::new(Inner {
+        is_write_vectored: stream.is_write_vectored(),
+        #[cfg(target_os = "linux")]
+        is_raw: stream.as_any().is::<tokio::net::TcpStream>(),
+        stream: UnsafeCell::new(Box::new(stream)),
+        locked: AtomicBool::new(false),
+        contended: AtomicBool::new(false),
+        wakers: Arc::new(Wakers::default()),
+    });
     (
         ReadHalf {
-            inner: stream.clone(),
+            inner: inner.clone(),
         },
-        WriteHalf { inner: stream },
+        WriteHalf { inner },
     )
 }
 
+#[derive(Clone, Copy)]
+enum Side {
+    Read,
+    Write,
+}
+
+struct Inner {
+    stream: UnsafeCell<Box<dyn AsyncStream>>,
+    is_write_vectored: bool,
+    /// Whether the stream is a plain TCP socket usable for zero-copy transfers.
+    #[cfg(target_os = "linux")]
+    is_raw: bool,
+    locked: AtomicBool,
+    contended: AtomicBool,
+    wakers: Arc<Wakers>,
+}
+
+// The stream is only accessed while `locked` is held.
+unsafe impl Send for Inner {}
+unsafe impl Sync for Inner {}
+
+impl Inner {
+    fn poll<R>(
+        &self,
+        side: Side,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(Pin<&mut dyn AsyncStream>, &mut Context<'_>) -> Poll<R>,
+    ) -> Poll<R> {
+        self.wakers.register(side, cx.waker());
+        if !self.try_lock() {
+            self.contended.store(true, Ordering::SeqCst);
+            // The other half may have released the stream before it could see the flag.
+            if !self.try_lock() {
+                return Poll::Pending;
+            }
+        }
+        let _guard = Guard(self);
+        // The stream is polled with a waker notifying both halves, because a TLS stream may wait
+        // on the opposite direction of the socket (e.g. flushing a key update while reading).
+        let waker = Waker::from(self.wakers.clone());
+        let mut cx = Context::from_waker(&waker);
+        // SAFETY: the lock grants exclusive access to the stream until the guard is dropped.
+        let stream = unsafe { &mut *self.stream.get() };
+        f(Pin::new(stream.as_mut()), &mut cx)
+    }
+
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    fn unlock(&self) {
+        self.locked.store(false, Ordering::SeqCst);
+        if self.contended.swap(false, Ordering::SeqCst) {
+            self.wakers.wake_all();
+        }
+    }
+}
+
+struct Guard<'a>(&'a Inner);
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        self.0.unlock();
+    }
+}
+
+#[derive(Default)]
+struct Wakers {
+    read: AtomicWaker,
+    write: AtomicWaker,
+}
+
+impl Wakers {
+    fn register(&self, side: Side, waker: &Waker) {
+        match side {
+            Side::Read => self.read.register(waker),
+            Side::Write => self.write.register(waker),
+        }
+    }
+
+    fn wake_all(&self) {
+        self.read.wake();
+        self.write.wake();
+    }
+}
+
+impl Wake for Wakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_all();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_all();
+    }
+}
+
 impl AsyncRead for ReadHalf {
     #[inline]
     fn poll_read(
@@ -33,13 +161,8 @@ impl AsyncRead for ReadHalf {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        match self.get_mut().inner.lock() {
-            Ok(stream) => Pin::new(stream).as_mut().poll_read(cx, buf),
-            Err(_) => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to lock stream",
-            ))),
-        }
+        self.inner
+            .poll(Side::Read, cx, |stream, cx| stream.poll_read(cx, buf))
     }
 }
 
@@ -50,13 +173,8 @@ impl AsyncWrite for WriteHalf {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        match self.get_mut().inner.lock() {
-            Ok(stream) => Pin::new(stream).as_mut().poll_write(cx, buf),
-            Err(_) => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to lock stream",
-            ))),
-        }
+        self.inner
+            .poll(Side::Write, cx, |stream, cx| stream.poll_write(cx, buf))
     }
 
     #[inline]
@@ -65,42 +183,111 @@ impl AsyncWrite for WriteHalf {
         cx: &mut Context<'_>,
         bufs: &[io::IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
-        match self.get_mut().inner.lock() {
-            Ok(stream) => Pin::new(stream).as_mut().poll_write_vectored(cx, bufs),
-            Err(_) => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to lock stream",
-            ))),
-        }
+        self.inner.poll(Side::Write, cx, |stream, cx| {
+            stream.poll_write_vectored(cx, bufs)
+        })
     }
 
     #[inline]
     fn is_write_vectored(&self) -> bool {
-        self.inner
-            .lock()
-            .map(|stream| stream.is_write_vectored())
-            .unwrap_or_default()
+        self.inner.is_write_vectored
     }
 
     #[inline]
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self.get_mut().inner.lock() {
-            Ok(stream) => Pin::new(stream).as_mut().poll_flush(cx),
-            Err(_) => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to lock stream",
-            ))),
-        }
+        self.inner
+            .poll(Side::Write, cx, |stream, cx| stream.poll_flush(cx))
     }
 
     #[inline]
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match self.get_mut().inner.lock() {
-            Ok(stream) => Pin::new(stream).as_mut().poll_shutdown(cx),
-            Err(_) => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to lock stream",
-            ))),
+        self.inner
+            .poll(Side::Write, cx, |stream, cx| stream.poll_shutdown(cx))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod raw {
+    use std::{
+        io,
+        os::fd::RawFd,
+        task::{Context, Poll},
+    };
+    use tokio::{io::Interest, net::TcpStream};
+
+    use super::{AsyncStream, Inner, ReadHalf, Side, WriteHalf};
+    use crate::io::zero_copy::{poll_tcp, RawRead, RawWrite};
+
+    impl Inner {
+        fn poll_raw(
+            &self,
+            side: Side,
+            interest: Interest,
+            cx: &mut Context<'_>,
+            operation: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>> {
+            self.poll(side, cx, |stream, cx| {
+                match AsyncStream::as_any(&*stream).downcast_ref::<TcpStream>() {
+                    Some(tcp) => poll_tcp(tcp, interest, cx, operation),
+                    None => Poll::Ready(Err(io::ErrorKind::Unsupported.into())),
+                }
+            })
+        }
+    }
+
+    impl RawRead for ReadHalf {
+        fn is_raw(&self) -> bool {
+            self.inner.is_raw
+        }
+
+        fn poll_read_raw(
+            &mut self,
+            cx: &mut Context<'_>,
+            read: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>> {
+            self.inner
+                .poll_raw(Side::Read, Interest::READABLE, cx, read)
+        }
+    }
+
+    impl RawWrite for WriteHalf {
+        fn is_raw(&self) -> bool {
+            self.inner.is_raw
+        }
+
+        fn poll_write_raw(
+            &mut self,
+            cx: &mut Context<'_>,
+            write: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>> {
+            self.inner
+                .poll_raw(Side::Write, Interest::WRITABLE, cx, write)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_full_duplex() {
+        let (local, remote) = io::duplex(1024);
+        let (mut rx, mut tx) = split(local);
+        let (mut remote_rx, mut remote_tx) = io::split(remote);
+        tokio::spawn(async move { io::copy(&mut remote_rx, &mut remote_tx).await });
+        let data = (0..1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let expected = data.clone();
+        let writer = tokio::spawn(async move {
+            tx.write_all(&data).await.unwrap();
+            tx.flush().await.unwrap();
+        });
+        let mut received = vec![0; expected.len()];
+        rx.read_exact(&mut received).await.unwrap();
+        writer.await.unwrap();
+        assert_eq!(received, expected);
+    }
+}
@@ -12,6 +12,7 @@ pub enum CustomError {
     RequestStatusLine(RequestStatusLine),
     ResponseStatusLine(ResponseStatusLine),
     Headers(Headers),
+    ProxyProtocol(ProxyProtocol),
     PeerConnection,
     MutexPoison,
 }
@@ -70,3 +71,20 @@ impl From<Headers> for CustomError {
         CustomError::Headers(value)
     }
 }
+
+#[derive(Debug)]
+
+pub enum ProxyProtocol {
+    InvalidSignature,
+    InvalidVersion,
+    InvalidCommand,
+    InvalidProtocol,
+    InvalidAddress,
+    HeaderTooLong,
+}
+
+impl From<ProxyProtocol> for CustomError {
+    fn from(value: ProxyProtocol) -> Self {
+        CustomError::ProxyProtocol(value)
+    }
+}
@@ -1,4 +1,6 @@
 pub mod error;
+pub mod proxy;
 mod streams;
+pub mod zero_copy;
 
 pub use streams::WriteReader;
@@ -0,0 +1,286 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use async_trait::async_trait;
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::error::{error, ProxyProtocol};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_LENGTH: usize = 107;
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+
+/// Version of the PROXY protocol header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+/// Addresses carried by a PROXY protocol header.
+/// Both addresses are `None` for `LOCAL`/`UNKNOWN` connections (e.g. load balancer health checks).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProxyHeader {
+    pub source: Option<SocketAddr>,
+    pub destination: Option<SocketAddr>,
+}
+
+impl ProxyHeader {
+    pub fn new(source: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            source: Some(source),
+            destination: Some(destination),
+        }
+    }
+
+    pub fn local() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+pub trait ReadProxyHeader {
+    async fn read_proxy_header(&mut self) -> io::Result<ProxyHeader>;
+}
+
+#[async_trait]
+impl<R> ReadProxyHeader for R
+where
+    R: AsyncRead + ?Sized + Unpin + Send,
+{
+    async fn read_proxy_header(&mut self) -> io::Result<ProxyHeader> {
+        // The shortest valid header (`PROXY UNKNOWN\r\n`) is longer than the v2 signature,
+        // so it is safe to read the signature without consuming any of the request.
+        let mut signature = [0_u8; 12];
+        self.read_exact(&mut signature).await?;
+        if signature == V2_SIGNATURE {
+            read_v2(self).await
+        } else if signature.starts_with(V1_PREFIX) {
+            read_v1(self, signature.to_vec()).await
+        } else {
+            Err(error(ProxyProtocol::InvalidSignature))
+        }
+    }
+}
+
+async fn read_v1<R>(reader: &mut R, mut line: Vec<u8>) -> io::Result<ProxyHeader>
+where
+    R: AsyncRead + ?Sized + Unpin + Send,
+{
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(error(ProxyProtocol::HeaderTooLong));
+        }
+        line.push(reader.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[V1_PREFIX.len()..line.len() - 2])
+        .map_err(|_| error(ProxyProtocol::InvalidAddress))?;
+    let mut parts = line.split(' ');
+    match parts.next() {
+        Some("UNKNOWN") => return Ok(ProxyHeader::local()),
+        Some("TCP4") | Some("TCP6") => {}
+        _ => return Err(error(ProxyProtocol::InvalidProtocol)),
+    }
+    let mut next = || parts.next().ok_or(error(ProxyProtocol::InvalidAddress));
+    let source_ip = next()?;
+    let destination_ip = next()?;
+    let source_port = next()?;
+    let destination_port = next()?;
+    let parse = |ip: &str, port: &str| -> io::Result<SocketAddr> {
+        Ok(SocketAddr::new(
+            ip.parse::<IpAddr>()
+                .map_err(|_| error(ProxyProtocol::InvalidAddress))?,
+            port.parse::<u16>()
+                .map_err(|_| error(ProxyProtocol::InvalidAddress))?,
+        ))
+    };
+    Ok(ProxyHeader::new(
+        parse(source_ip, source_port)?,
+        parse(destination_ip, destination_port)?,
+    ))
+}
+
+async fn read_v2<R>(reader: &mut R) -> io::Result<ProxyHeader>
+where
+    R: AsyncRead + ?Sized + Unpin + Send,
+{
+    let version_command = reader.read_u8().await?;
+    if version_command >> 4 != 2 {
+        return Err(error(ProxyProtocol::InvalidVersion));
+    }
+    let family = reader.read_u8().await?;
+    let length = reader.read_u16().await? as usize;
+    let mut data = vec![0_u8; length];
+    reader.read_exact(&mut data).await?;
+    match version_command & 0x0F {
+        0x00 => return Ok(ProxyHeader::local()),
+        0x01 => {}
+        _ => return Err(error(ProxyProtocol::InvalidCommand)),
+    }
+    let port = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+    match family >> 4 {
+        0x1 if data.len() >= 12 => {
+            let source = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+            let destination = Ipv4Addr::new(data[4], data[5], data[6], data[7]);
+            Ok(ProxyHeader::new(
+                SocketAddr::new(source.into(), port(8)),
+                SocketAddr::new(destination.into(), port(10)),
+            ))
+        }
+        0x2 if data.len() >= 36 => {
+            let mut source = [0_u8; 16];
+            let mut destination = [0_u8; 16];
+            source.copy_from_slice(&data[0..16]);
+            destination.copy_from_slice(&data[16..32]);
+            Ok(ProxyHeader::new(
+                SocketAddr::new(Ipv6Addr::from(source).into(), port(32)),
+                SocketAddr::new(Ipv6Addr::from(destination).into(), port(34)),
+            ))
+        }
+        0x0 | 0x3 => Ok(ProxyHeader::local()),
+        _ => Err(error(ProxyProtocol::InvalidAddress)),
+    }
+}
+
+#[async_trait]
+pub trait WriteProxyHeader {
+    async fn write_proxy_header(
+        &mut self,
+        header: &ProxyHeader,
+        version: Version,
+    ) -> io::Result<()>;
+}
+
+#[async_trait]
+impl<W> WriteProxyHeader for W
+where
+    W: AsyncWrite + ?Sized + Unpin + Send,
+{
+    async fn write_proxy_header(
+        &mut self,
+        header: &ProxyHeader,
+        version: Version,
+    ) -> io::Result<()> {
+        let addresses = match (header.source, header.destination) {
+            (Some(source), Some(destination)) if source.is_ipv4() == destination.is_ipv4() => {
+                Some((source, destination))
+            }
+            _ => None,
+        };
+        match version {
+            Version::V1 => {
+                let line = match addresses {
+                    Some((source, destination)) => format!(
+                        "PROXY {} {} {} {} {}\r\n",
+                        if source.is_ipv4() { "TCP4" } else { "TCP6" },
+                        source.ip(),
+                        destination.ip(),
+                        source.port(),
+                        destination.port()
+                    ),
+                    None => "PROXY UNKNOWN\r\n".to_string(),
+                };
+                self.write_all(line.as_bytes()).await
+            }
+            Version::V2 => {
+                let mut buf = V2_SIGNATURE.to_vec();
+                match addresses {
+                    Some((source, destination)) => {
+                        buf.push(0x21);
+                        let mut data = Vec::with_capacity(36);
+                        match (source.ip(), destination.ip()) {
+                            (IpAddr::V4(source), IpAddr::V4(destination)) => {
+                                buf.push(0x11);
+                                data.extend_from_slice(&source.octets());
+                                data.extend_from_slice(&destination.octets());
+                            }
+                            (source, destination) => {
+                                buf.push(0x21);
+                                data.extend_from_slice(&to_ipv6(source).octets());
+                                data.extend_from_slice(&to_ipv6(destination).octets());
+                            }
+                        }
+                        data.extend_from_slice(&source.port().to_be_bytes());
+                        data.extend_from_slice(&destination.port().to_be_bytes());
+                        buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                        buf.extend_from_slice(&data);
+                    }
+                    None => {
+                        buf.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+                    }
+                }
+                self.write_all(&buf).await
+            }
+        }
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_v1() {
+        let mut data: &[u8] =
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET / HTTP/1.1\r\n";
+        let header = data.read_proxy_header().await.unwrap();
+        assert_eq!(
+            header,
+            ProxyHeader::new(
+                "192.168.0.1:56324".parse().unwrap(),
+                "192.168.0.11:443".parse().unwrap()
+            )
+        );
+        assert_eq!(data, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_v1_unknown() {
+        let mut data: &[u8] = b"PROXY UNKNOWN\r\nGET / HTTP/1.1\r\n";
+        let header = data.read_proxy_header().await.unwrap();
+        assert_eq!(header, ProxyHeader::local());
+        assert_eq!(data, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_invalid() {
+        let mut data: &[u8] = b"GET / HTTP/1.1\r\nHost: app\r\n\r\n";
+        assert!(data.read_proxy_header().await.is_err());
+        let mut data: &[u8] = b"PROXY TCP4 192.168.0.1\r\n";
+        assert!(data.read_proxy_header().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        for version in [Version::V1, Version::V2] {
+            for header in [
+                ProxyHeader::new(
+                    "10.0.0.1:1234".parse().unwrap(),
+                    "10.0.0.2:80".parse().unwrap(),
+                ),
+                ProxyHeader::new(
+                    "[2001:db8::1]:1234".parse().unwrap(),
+                    "[2001:db8::2]:443".parse().unwrap(),
+                ),
+                ProxyHeader::local(),
+            ] {
+                let mut buf = Vec::new();
+                buf.write_proxy_header(&header, version).await.unwrap();
+                buf.extend_from_slice(b"GET /");
+                let mut data = buf.as_slice();
+                assert_eq!(data.read_proxy_header().await.unwrap(), header);
+                assert_eq!(data, b"GET /");
+            }
+        }
+    }
+}
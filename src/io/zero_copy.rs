@@ -0,0 +1,294 @@
+//! Zero-copy transfers between sockets and from files to sockets.
+//!
+//! On Linux, data is moved by the kernel using `splice(2)` through a pipe and `sendfile(2)`.
+//! The functions return `Ok(None)` without transferring anything when a stream is not a plain
+//! TCP socket (e.g. a TLS stream) or on other platforms, so that callers can fall back to
+//! a userspace copy.
+
+#[cfg(all(target_os = "linux", feature = "tls"))]
+pub(crate) use linux::poll_tcp;
+#[cfg(target_os = "linux")]
+pub use linux::{sendfile, splice, RawRead, RawWrite};
+
+#[cfg(not(target_os = "linux"))]
+pub async fn splice<R: ?Sized, W: ?Sized>(
+    _reader: &mut R,
+    _writer: &mut W,
+    _length: Option<usize>,
+) -> std::io::Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn sendfile<F: ?Sized, W: ?Sized>(
+    _file: &F,
+    _writer: &mut W,
+    _offset: u64,
+    _length: usize,
+) -> std::io::Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        future::poll_fn,
+        io,
+        os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        ptr,
+        task::{ready, Context, Poll},
+    };
+    use tokio::{
+        io::Interest,
+        net::{
+            tcp::{OwnedReadHalf, OwnedWriteHalf},
+            TcpStream,
+        },
+    };
+
+    const PIPE_SIZE: usize = 64 * 1024;
+    const SENDFILE_SIZE: usize = 1024 * 1024;
+
+    /// A stream which may be read directly from its socket.
+    pub trait RawRead: Send {
+        /// Whether the stream is a plain TCP socket.
+        fn is_raw(&self) -> bool;
+
+        /// Run `read` with the socket once it is readable.
+        fn poll_read_raw(
+            &mut self,
+            cx: &mut Context<'_>,
+            read: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>>;
+    }
+
+    /// A stream which may be written directly to its socket.
+    pub trait RawWrite: Send {
+        /// Whether the stream is a plain TCP socket.
+        fn is_raw(&self) -> bool;
+
+        /// Run `write` with the socket once it is writable.
+        fn poll_write_raw(
+            &mut self,
+            cx: &mut Context<'_>,
+            write: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>>;
+    }
+
+    /// Run the operation on the socket, waiting for readiness until it does not block.
+    pub(crate) fn poll_tcp(
+        tcp: &TcpStream,
+        interest: Interest,
+        cx: &mut Context<'_>,
+        operation: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if interest.is_readable() {
+                ready!(tcp.poll_read_ready(cx))?;
+            } else {
+                ready!(tcp.poll_write_ready(cx))?;
+            }
+            match tcp.try_io(interest, || operation(tcp.as_raw_fd())) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    impl RawRead for OwnedReadHalf {
+        fn is_raw(&self) -> bool {
+            true
+        }
+
+        fn poll_read_raw(
+            &mut self,
+            cx: &mut Context<'_>,
+            read: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>> {
+            poll_tcp(self.as_ref(), Interest::READABLE, cx, read)
+        }
+    }
+
+    impl RawWrite for OwnedWriteHalf {
+        fn is_raw(&self) -> bool {
+            true
+        }
+
+        fn poll_write_raw(
+            &mut self,
+            cx: &mut Context<'_>,
+            write: &mut dyn FnMut(RawFd) -> io::Result<usize>,
+        ) -> Poll<io::Result<usize>> {
+            poll_tcp(self.as_ref(), Interest::WRITABLE, cx, write)
+        }
+    }
+
+    /// Move up to `length` bytes (or until EOF) from the reader to the writer through a pipe.
+    pub async fn splice<R, W>(
+        reader: &mut R,
+        writer: &mut W,
+        length: Option<usize>,
+    ) -> io::Result<Option<u64>>
+    where
+        R: RawRead + ?Sized,
+        W: RawWrite + ?Sized,
+    {
+        if !reader.is_raw() || !writer.is_raw() {
+            return Ok(None);
+        }
+        let (pipe_rx, pipe_tx) = pipe()?;
+        let mut total = 0;
+        loop {
+            let chunk = length.map_or(PIPE_SIZE, |length| (length - total).min(PIPE_SIZE));
+            if chunk == 0 {
+                break;
+            }
+            let read = poll_fn(|cx| {
+                reader.poll_read_raw(cx, &mut |fd| splice_fd(fd, pipe_tx.as_raw_fd(), chunk))
+            })
+            .await?;
+            if read == 0 {
+                break;
+            }
+            let mut pending = read;
+            while pending > 0 {
+                let written = poll_fn(|cx| {
+                    writer.poll_write_raw(cx, &mut |fd| splice_fd(pipe_rx.as_raw_fd(), fd, pending))
+                })
+                .await?;
+                if written == 0 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                pending -= written;
+            }
+            total += read;
+        }
+        Ok(Some(total as u64))
+    }
+
+    /// Send `length` bytes of the file starting at `offset` to the writer.
+    pub async fn sendfile<F, W>(
+        file: &F,
+        writer: &mut W,
+        offset: u64,
+        length: usize,
+    ) -> io::Result<Option<u64>>
+    where
+        F: AsRawFd + ?Sized,
+        W: RawWrite + ?Sized,
+    {
+        if !writer.is_raw() {
+            return Ok(None);
+        }
+        let mut offset = offset as libc::off_t;
+        let mut remaining = length;
+        while remaining > 0 {
+            let sent = poll_fn(|cx| {
+                writer.poll_write_raw(cx, &mut |fd| {
+                    let sent = unsafe {
+                        libc::sendfile(
+                            fd,
+                            file.as_raw_fd(),
+                            &mut offset,
+                            remaining.min(SENDFILE_SIZE),
+                        )
+                    };
+                    match sent {
+                        -1 => Err(io::Error::last_os_error()),
+                        sent => Ok(sent as usize),
+                    }
+                })
+            })
+            .await?;
+            if sent == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            remaining -= sent;
+        }
+        Ok(Some(length as u64))
+    }
+
+    fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let pipe = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        // Best effort, the default pipe capacity is used when resizing is not permitted.
+        unsafe { libc::fcntl(fds[1], libc::F_SETPIPE_SZ, PIPE_SIZE as libc::c_int) };
+        Ok(pipe)
+    }
+
+    fn splice_fd(from: RawFd, to: RawFd, length: usize) -> io::Result<usize> {
+        let moved = unsafe {
+            libc::splice(
+                from,
+                ptr::null_mut(),
+                to,
+                ptr::null_mut(),
+                length,
+                libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK,
+            )
+        };
+        match moved {
+            -1 => Err(io::Error::last_os_error()),
+            moved => Ok(moved as usize),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use pretty_assertions::assert_eq;
+        use std::{env, fs};
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        use super::*;
+
+        async fn connect() -> (TcpStream, TcpStream) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (server, _) = listener.accept().await.unwrap();
+            (client, server)
+        }
+
+        #[tokio::test]
+        async fn test_splice() {
+            let (mut source, left) = connect().await;
+            let (right, mut destination) = connect().await;
+            let data = (0..512 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let expected = data.clone();
+            tokio::spawn(async move { source.write_all(&data).await });
+            let (mut left_rx, _left_tx) = left.into_split();
+            let (_right_rx, mut right_tx) = right.into_split();
+            let length = expected.len();
+            let copy =
+                tokio::spawn(
+                    async move { splice(&mut left_rx, &mut right_tx, Some(length)).await },
+                );
+            let mut received = vec![0; expected.len()];
+            destination.read_exact(&mut received).await.unwrap();
+            assert_eq!(copy.await.unwrap().unwrap(), Some(length as u64));
+            assert_eq!(received, expected);
+        }
+
+        #[tokio::test]
+        async fn test_sendfile() {
+            let path = env::temp_dir().join(format!("gateway-sendfile-{}", std::process::id()));
+            fs::write(&path, b"0123456789").unwrap();
+            let file = fs::File::open(&path).unwrap();
+            let (right, mut destination) = connect().await;
+            let (_right_rx, mut right_tx) = right.into_split();
+            assert_eq!(sendfile(&file, &mut right_tx, 2, 5).await.unwrap(), Some(5));
+            drop(right_tx);
+            let mut received = String::new();
+            destination.read_to_string(&mut received).await.unwrap();
+            assert_eq!(received, "23456");
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -7,7 +7,7 @@
 //! use async_trait::async_trait;
 //! use essentials::info;
 //! use gateway::{
-//!     http::{response::ResponseBody, HeaderMapExt, Request, Response},
+//!     http::{request::RequestBody, response::ResponseBody, HeaderMapExt, Request, Response},
 //!     tcp, time, Ctx, Middleware, MiddlewareBuilder, Next, Origin, OriginServer,
 //!     OriginServerBuilder, ParamRouterBuilder, Result, Service,
 //! };
@@ -16,7 +16,7 @@
 //! use tokio::{
 //!     fs::File,
 //!     io::{self, AsyncReadExt},
-//!     net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+//!     net::tcp::OwnedWriteHalf,
 //! };
 //!
 //! struct Gateway;
@@ -85,8 +85,7 @@
 //!         &self,
 //!         _ctx: &Ctx,
 //!         request: Request,
-//!         _left_rx: OwnedReadHalf,
-//!         _left_remains: Vec<u8>,
+//!         _body: RequestBody,
 //!     ) -> Result<Response> {
 //!         println!("[origin] Request received: {:?}", request);
 //!         let path = Path::new("static").join(&request.path.as_str()[1..]);
@@ -209,6 +208,7 @@
 //!                                     unit: time::TimeUnit::Minutes,
 //!                                 },
 //!                             }),
+//!                             algorithm: rate_limit::config::Algorithm::FixedWindow,
 //!                         }),
 //!                         HashMap::new(),
 //!                     ),
@@ -233,6 +233,10 @@
 pub mod auth;
 #[cfg(feature = "cache")]
 pub mod cache;
+#[cfg(feature = "compression")]
+pub mod compression;
+#[cfg(feature = "concurrency-limit")]
+pub mod concurrency_limit;
 #[cfg(feature = "cors")]
 pub mod cors;
 pub(crate) mod gateway;
@@ -243,11 +247,18 @@ pub mod rate_limit;
 pub(crate) mod server;
 pub(crate) mod utils;
 
+#[cfg(feature = "acme")]
+pub use gateway::entrypoint::tls::acme;
+#[cfg(feature = "tls")]
+pub use gateway::entrypoint::tls::{
+    load_certified_key, CertStore, ClientAuth, ClientCa, ClientCertificate, Hsts, Redirect,
+    RedirectConfig, RedirectPolicy,
+};
 pub use gateway::{
-    ctx::{AppConfig, AppCtx, ConfigToContext, Ctx, Id, MiddlewareConfig, MiddlewareCtx},
+    ctx::{AppConfig, AppCtx, ConfigToContext, Ctx, Id, MiddlewareConfig, MiddlewareCtx, Peer},
     entrypoint::EntryPoint,
     middleware::{Middleware, MiddlewareBuilder, Service},
-    origin::{tcp, Origin, OriginBuilder, OriginResponse, OriginServer, OriginServerBuilder},
+    origin::{fs, tcp, Origin, OriginBuilder, OriginResponse, OriginServer, OriginServerBuilder},
     router::{
         AnyRouter, AnyRouterBuilder, ParamRouter, ParamRouterBuilder, RegexRouter,
         RegexRouterBuilder, Router, RouterBuilder, RouterBuilderService, RouterService,
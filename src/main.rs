@@ -1,17 +1,13 @@
-use anyhow::Context;
 use async_trait::async_trait;
 use essentials::info;
 use gateway::{
-    http::{response::ResponseBody, HeaderMapExt, Request, Response},
-    tcp, Ctx, Middleware, MiddlewareBuilder, Next, Origin, OriginServer, OriginServerBuilder,
-    ParamRouterBuilder, ReadHalf, Result, Service, WriteHalf,
-};
-use http::{header, Method, StatusCode};
-use std::{collections::HashMap, env, path::Path};
-use tokio::{
-    fs::File,
-    io::{self, AsyncReadExt},
+    fs,
+    http::{HeaderMapExt, Request, Response},
+    tcp, AnyRouterBuilder, Ctx, Middleware, MiddlewareBuilder, Next, ParamRouterBuilder, Result,
+    Service,
 };
+use http::Method;
+use std::{collections::HashMap, env};
 
 struct Gateway;
 
@@ -45,84 +41,6 @@ impl MiddlewareBuilder for GatewayBuilder {
     }
 }
 
-#[derive(Default)]
-struct FileServer;
-
-#[derive(Default)]
-pub struct FileServerBuilder;
-
-impl FileServerBuilder {
-    pub fn new() -> Self {
-        Self
-    }
-}
-
-#[async_trait]
-impl OriginServerBuilder for FileServerBuilder {
-    async fn build(
-        self: Box<Self>,
-        _: &[String],
-        _: &HashMap<String, Vec<String>>,
-    ) -> Result<Origin> {
-        Ok(Box::new(FileServer))
-    }
-}
-
-#[derive(Debug)]
-pub struct FileResponse {
-    file: File,
-}
-
-#[async_trait]
-impl OriginServer for FileServer {
-    async fn connect(
-        &self,
-        _ctx: &Ctx,
-        request: Request,
-        _left_rx: ReadHalf,
-        _left_remains: Vec<u8>,
-    ) -> Result<Response> {
-        println!("[origin] Request received: {:?}", request);
-        let path = Path::new("static").join(&request.path.as_str()[1..]);
-        if !path.exists() {
-            return Ok(Response::new(StatusCode::NOT_FOUND));
-        }
-        let file = File::open(path)
-            .await
-            .with_context(|| format!("Failed to open file: {:?}", request.path))?;
-        let length = file
-            .metadata()
-            .await
-            .with_context(|| "Failed to read file metadata")?
-            .len();
-        let mut response = Response::new(StatusCode::OK);
-        response.insert_header(header::CONTENT_LENGTH, length.to_string());
-        response.set_body(FileResponse { file });
-        println!("[origin] Returning file response");
-        Ok(response)
-    }
-}
-
-#[async_trait]
-impl ResponseBody for FileResponse {
-    async fn read_all(mut self: Box<Self>, len: usize) -> io::Result<String> {
-        println!("reading all");
-        let mut buf = vec![0; len];
-        self.file.read_exact(&mut buf).await?;
-        Ok(String::from_utf8(buf).unwrap())
-    }
-
-    async fn copy_to<'a>(
-        &mut self,
-        writer: &'a mut WriteHalf,
-        _length: Option<usize>,
-    ) -> io::Result<()> {
-        println!("copying response to client");
-        tokio::io::copy(&mut self.file, writer).await?;
-        Ok(())
-    }
-}
-
 #[tokio::main]
 async fn main() {
     println!("Starting gateway");
@@ -132,21 +50,19 @@ async fn main() {
     essentials::install();
     info!("Starting gateway");
     tokio::spawn(
-        gateway::builder(FileServerBuilder::new(), |_| Some((String::new(), None)))
-            .register_peer(
-                String::new(),
-                ParamRouterBuilder::new().add_route(
-                    Method::GET,
-                    "/:file".to_string(),
-                    "file".to_string(),
-                ),
-            )
-            .with_app_port(81)
-            .with_health_check_port(9001)
-            .build()
-            .await
-            .unwrap()
-            .run(),
+        gateway::builder(
+            fs::Builder::new()
+                .add_root("", fs::config::Root::new("static"))
+                .build(),
+            |_| Some((String::new(), None)),
+        )
+        .register_peer(String::new(), AnyRouterBuilder)
+        .with_app_port(81)
+        .with_health_check_port(9001)
+        .build()
+        .await
+        .unwrap()
+        .run(),
     );
     let mut server_builder = gateway::builder(
         tcp::Builder::new()
@@ -203,6 +119,7 @@ async fn main() {
                                     unit: time::TimeUnit::Minutes,
                                 },
                             }),
+                            algorithm: rate_limit::config::Algorithm::FixedWindow,
                         }),
                         HashMap::new(),
                     ),
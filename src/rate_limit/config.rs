@@ -1,21 +1,107 @@
 use std::collections::HashMap;
 
+use http::StatusCode;
+
 use crate::utils::time::Frequency;
 
 #[derive(Debug)]
 pub struct Rules {
+    /// Quota of the requests with an API token without its own quota.
     pub root: Option<Quota>,
+    /// Quotas by the API token sent in the `X-Api-Token` header.
     pub tokens: HashMap<String, Quota>,
+    /// Quota of the requests without an API token, the root quota is used when not set.
+    /// Once set, requests with an API token without a quota of the app or endpoint
+    /// are rejected with 401, so that made-up tokens do not get around the anonymous quota.
+    pub anonymous: Option<Quota>,
+    /// Key the total quota is counted by.
+    pub total_key: Key,
+    /// Key the user quota is counted by.
+    pub user_key: Key,
+    pub missing_key: MissingKey,
 }
 
 impl Rules {
     pub fn new(root: Option<Quota>, tokens: HashMap<String, Quota>) -> Self {
-        Self { root, tokens }
+        Self {
+            root,
+            tokens,
+            anonymous: None,
+            total_key: Key::ApiToken,
+            user_key: Key::Ip,
+            missing_key: MissingKey::default(),
+        }
+    }
+
+    pub fn with_anonymous(mut self, quota: Quota) -> Self {
+        self.anonymous = Some(quota);
+        self
+    }
+
+    pub fn with_total_key(mut self, key: Key) -> Self {
+        self.total_key = key;
+        self
+    }
+
+    pub fn with_user_key(mut self, key: Key) -> Self {
+        self.user_key = key;
+        self
     }
+
+    pub fn with_missing_key(mut self, policy: MissingKey) -> Self {
+        self.missing_key = policy;
+        self
+    }
+}
+
+/// Part of the request the requests are counted by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// Client IP address from the `X-Real-IP` header.
+    Ip,
+    /// API token from the `X-Api-Token` header.
+    ApiToken,
+    /// Any header, e.g. `X-Username` set by the auth middlewares from a JWT claim.
+    Header(String),
+    /// Parameter of the route pattern, e.g. `id` of `/users/:id`.
+    Param { route: String, name: String },
+    /// Combination of the keys, missing if any of them is.
+    All(Vec<Key>),
+}
+
+/// What to do with requests missing the key of a quota.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissingKey {
+    /// Count all requests missing the key together.
+    #[default]
+    Shared,
+    /// Do not count the requests against the quota.
+    Skip,
+    /// Respond with the status code.
+    Reject(StatusCode),
 }
 
 #[derive(Debug)]
 pub struct Quota {
     pub total: Frequency,
     pub user: Option<Frequency>,
+    pub algorithm: Algorithm,
+}
+
+/// How the requests are counted against the frequency of a quota.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Counter reset at the end of each interval, allows bursts of twice the amount at the boundaries.
+    #[default]
+    FixedWindow,
+    /// Log of the requests within the last interval, exact but stores every request.
+    SlidingWindowLog,
+    /// Counters of the current and the previous interval, the previous one weighted by its overlap
+    /// with the last interval.
+    SlidingWindowCounter,
+    /// Bucket of `burst` tokens refilled at the rate of the frequency, each request takes one token.
+    TokenBucket { burst: usize },
+    /// Generic cell rate algorithm, requests are spaced by the rate of the frequency allowing
+    /// `burst` requests at once.
+    Gcra { burst: usize },
 }
@@ -2,25 +2,32 @@ use async_trait::async_trait;
 
 use crate::{ConfigToContext, Result};
 
-use super::{config, Quota};
+use super::{config, Key, MissingKey, Quota};
 
 #[derive(Debug)]
 pub struct Rules {
     pub root: Option<Quota>,
     pub tokens: Box<[(Box<str>, Quota)]>,
+    pub anonymous: Option<Quota>,
+    pub total_key: Key,
+    pub user_key: Key,
+    pub missing_key: MissingKey,
 }
 
 impl Rules {
-    fn new(root: Option<Quota>, tokens: Box<[(Box<str>, Quota)]>) -> Self {
-        Self { root, tokens }
+    pub fn find_quota(&self, token: Option<&str>) -> Option<&Quota> {
+        match token {
+            Some(token) => self.token_quota(token),
+            None => self.anonymous.as_ref(),
+        }
+        .or(self.root.as_ref())
     }
 
-    pub fn find_quota(&self, token: &str) -> Option<&Quota> {
+    pub fn token_quota(&self, token: &str) -> Option<&Quota> {
         self.tokens
             .iter()
             .find(|(t, _)| t.as_ref() == token)
             .map(|(_, quota)| quota)
-            .or(self.root.as_ref())
     }
 }
 
@@ -29,13 +36,18 @@ impl ConfigToContext for config::Rules {
     type Context = Rules;
 
     async fn into_context(self) -> Result<Self::Context> {
-        Ok(Rules::new(
-            self.root,
-            self.tokens
+        Ok(Rules {
+            root: self.root,
+            tokens: self
+                .tokens
                 .into_iter()
                 .map(|(token, quota)| (token.into(), quota))
                 .collect::<Vec<_>>()
                 .into_boxed_slice(),
-        ))
+            anonymous: self.anonymous,
+            total_key: self.total_key,
+            user_key: self.user_key,
+            missing_key: self.missing_key,
+        })
     }
 }
@@ -1,48 +1,83 @@
-use crate::utils::time::{Frequency, TimeUnit};
+use crate::{rate_limit::config::Algorithm, utils::time::Frequency};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, Once},
+    time::Duration,
 };
 
-use super::{Datastore, RateLimit, Response};
+use super::{to_seconds, window, Datastore, RateLimit, Response};
 
-#[derive(Debug, Default)]
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Datastore keeping the rate limits in memory.
+/// Rate limits which would start over with the next request are removed by a background task
+/// started with the first request.
+#[derive(Debug)]
 pub struct InMemoryDatastore {
-    data: Arc<Mutex<HashMap<String, RateLimit>>>,
+    data: Arc<Mutex<HashMap<String, Entry>>>,
+    sweep_interval: Duration,
+    sweeper: Once,
+}
+
+#[derive(Debug)]
+struct Entry {
+    state: State,
+    /// Timestamp in milliseconds after which the state is the same as a new one.
+    expires: u64,
 }
 
-unsafe impl Send for InMemoryDatastore {}
+/// State of a key for each of the algorithms, timestamps are in milliseconds.
+#[derive(Debug)]
+enum State {
+    FixedWindow {
+        remaining: usize,
+        reset: u64,
+    },
+    SlidingWindowLog(VecDeque<u64>),
+    SlidingWindowCounter {
+        window: u64,
+        current: usize,
+        previous: usize,
+    },
+    TokenBucket {
+        tokens: f64,
+        updated: u64,
+    },
+    Gcra {
+        tat: f64,
+    },
+}
 
 #[async_trait]
 impl Datastore for InMemoryDatastore {
-    async fn get_rate_limit(&self, key: &str, quota: &Frequency) -> Result<Response> {
-        let now = chrono::Utc::now().timestamp() as usize;
+    async fn get_rate_limit(
+        &self,
+        key: &str,
+        quota: &Frequency,
+        algorithm: Algorithm,
+    ) -> Result<Response> {
+        self.start_sweeper();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
         let mut data = self
             .data
             .lock()
             .map_err(|_| anyhow!("Failed to acquire lock for key: {}", key))?;
-        let rate_limit = if let Some(rate_limit) = data.get_mut(key) {
-            if rate_limit.reset < now {
-                rate_limit.remaining = quota.amount - 1;
-                rate_limit.reset = now + quota.interval.convert(TimeUnit::Seconds).amount;
-            } else if rate_limit.remaining > 0 {
-                rate_limit.remaining -= 1;
-            } else {
-                return Ok(Response::Limited(rate_limit.reset));
-            }
-            rate_limit.clone()
-        } else {
-            let rate_limit = RateLimit {
-                limit: quota.amount,
-                remaining: quota.amount - 1,
-                reset: now + quota.interval.convert(TimeUnit::Seconds).amount,
-            };
-            data.insert(key.to_string(), rate_limit.clone());
-            rate_limit
-        };
-        Ok(Response::Ok(rate_limit))
+        let state = data.remove(key).map(|entry| entry.state);
+        let (response, entry) = limit(state, quota, algorithm, now);
+        data.insert(key.to_string(), entry);
+        Ok(response)
+    }
+}
+
+impl Default for InMemoryDatastore {
+    fn default() -> Self {
+        Self {
+            data: Arc::default(),
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+            sweeper: Once::new(),
+        }
     }
 }
 
@@ -50,4 +85,343 @@ impl InMemoryDatastore {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// How often rate limits past their reset are removed.
+    pub fn with_sweep_interval(mut self, interval: Duration) -> Self {
+        self.sweep_interval = interval;
+        self
+    }
+
+    fn start_sweeper(&self) {
+        self.sweeper.call_once(|| {
+            let data = Arc::downgrade(&self.data);
+            let mut interval = tokio::time::interval(self.sweep_interval);
+            tokio::spawn(async move {
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let data = match data.upgrade() {
+                        Some(data) => data,
+                        None => break,
+                    };
+                    sweep(&data, chrono::Utc::now().timestamp_millis() as u64);
+                }
+            });
+        });
+    }
+}
+
+/// Remove the rate limits past their reset, they start over with the next request anyway.
+fn sweep(data: &Mutex<HashMap<String, Entry>>, now: u64) {
+    if let Ok(mut data) = data.lock() {
+        data.retain(|_, entry| entry.expires >= now);
+    }
+}
+
+/// Count the request against the quota, a state of another algorithm is started over.
+fn limit(
+    state: Option<State>,
+    quota: &Frequency,
+    algorithm: Algorithm,
+    now: u64,
+) -> (Response, Entry) {
+    let window = window(quota);
+    let (response, state, expires) = match algorithm {
+        Algorithm::FixedWindow => fixed_window(state, quota.amount, window, now),
+        Algorithm::SlidingWindowLog => sliding_window_log(state, quota.amount, window, now),
+        Algorithm::SlidingWindowCounter => sliding_window_counter(state, quota.amount, window, now),
+        Algorithm::TokenBucket { burst } => {
+            token_bucket(state, quota.amount, window, burst.max(1), now)
+        }
+        Algorithm::Gcra { burst } => gcra(state, quota.amount, window, burst.max(1), now),
+    };
+    (response, Entry { state, expires })
+}
+
+fn fixed_window(
+    state: Option<State>,
+    limit: usize,
+    window: u64,
+    now: u64,
+) -> (Response, State, u64) {
+    let (remaining, reset) = match state {
+        Some(State::FixedWindow { remaining, reset }) if now <= reset => (remaining, reset),
+        _ => (limit, now + window),
+    };
+    if remaining == 0 {
+        return (
+            Response::Limited(to_seconds(reset)),
+            State::FixedWindow { remaining, reset },
+            reset,
+        );
+    }
+    let remaining = remaining - 1;
+    let rate_limit = RateLimit {
+        limit,
+        remaining,
+        reset: to_seconds(reset),
+    };
+    (
+        Response::Ok(rate_limit),
+        State::FixedWindow { remaining, reset },
+        reset,
+    )
+}
+
+fn sliding_window_log(
+    state: Option<State>,
+    limit: usize,
+    window: u64,
+    now: u64,
+) -> (Response, State, u64) {
+    let mut log = match state {
+        Some(State::SlidingWindowLog(log)) => log,
+        _ => VecDeque::new(),
+    };
+    while log.front().is_some_and(|request| request + window <= now) {
+        log.pop_front();
+    }
+    let limited = log.len() >= limit;
+    if !limited {
+        log.push_back(now);
+    }
+    let reset = log.front().map_or(now + window, |request| request + window);
+    let expires = log.back().map_or(now, |request| request + window);
+    let response = if limited {
+        Response::Limited(to_seconds(reset))
+    } else {
+        Response::Ok(RateLimit {
+            limit,
+            remaining: limit - log.len(),
+            reset: to_seconds(reset),
+        })
+    };
+    (response, State::SlidingWindowLog(log), expires)
+}
+
+fn sliding_window_counter(
+    state: Option<State>,
+    limit: usize,
+    window: u64,
+    now: u64,
+) -> (Response, State, u64) {
+    let index = now / window;
+    let (current, previous) = match state {
+        Some(State::SlidingWindowCounter {
+            window: stored,
+            current,
+            previous,
+        }) if stored == index => (current, previous),
+        Some(State::SlidingWindowCounter {
+            window: stored,
+            current,
+            ..
+        }) if stored + 1 == index => (0, current),
+        _ => (0, 0),
+    };
+    let end = (index + 1) * window;
+    // Requests of the previous interval overlapping the last one, scaled by the interval.
+    let weighted = previous as u64 * (end - now);
+    let limited = weighted + (current as u64 + 1) * window > limit as u64 * window;
+    let current = if limited { current } else { current + 1 };
+    let state = State::SlidingWindowCounter {
+        window: index,
+        current,
+        previous,
+    };
+    if limited {
+        let reset = if current < limit && previous > 0 {
+            end - (limit - 1 - current) as u64 * window / previous as u64
+        } else {
+            end
+        };
+        return (Response::Limited(to_seconds(reset)), state, end + window);
+    }
+    let rate_limit = RateLimit {
+        limit,
+        remaining: ((limit as u64 * window - weighted - current as u64 * window) / window) as usize,
+        reset: to_seconds(end),
+    };
+    (Response::Ok(rate_limit), state, end + window)
+}
+
+fn token_bucket(
+    state: Option<State>,
+    amount: usize,
+    window: u64,
+    burst: usize,
+    now: u64,
+) -> (Response, State, u64) {
+    let capacity = burst as f64;
+    let (amount, window) = (amount as f64, window as f64);
+    // Milliseconds it takes to refill the tokens.
+    let refill = |tokens: f64| (tokens * window / amount).ceil() as u64;
+    let tokens = match state {
+        Some(State::TokenBucket { tokens, updated }) => {
+            (tokens + now.saturating_sub(updated) as f64 * amount / window).min(capacity)
+        }
+        _ => capacity,
+    };
+    let limited = tokens < 1.0;
+    let tokens = if limited { tokens } else { tokens - 1.0 };
+    let full = now + refill(capacity - tokens);
+    let state = State::TokenBucket {
+        tokens,
+        updated: now,
+    };
+    if limited {
+        let reset = now + refill(1.0 - tokens);
+        return (Response::Limited(to_seconds(reset)), state, full);
+    }
+    let rate_limit = RateLimit {
+        limit: burst,
+        remaining: tokens.floor() as usize,
+        reset: to_seconds(full),
+    };
+    (Response::Ok(rate_limit), state, full)
+}
+
+fn gcra(
+    state: Option<State>,
+    amount: usize,
+    window: u64,
+    burst: usize,
+    now: u64,
+) -> (Response, State, u64) {
+    let now = now as f64;
+    // Time between two requests at the rate of the frequency.
+    let interval = window as f64 / amount as f64;
+    let tolerance = interval * burst as f64;
+    let tat = match state {
+        Some(State::Gcra { tat }) => tat.max(now),
+        _ => now,
+    };
+    let next = tat + interval;
+    let allow_at = next - tolerance;
+    if now < allow_at {
+        return (
+            Response::Limited(to_seconds(allow_at.ceil() as u64)),
+            State::Gcra { tat },
+            tat.ceil() as u64,
+        );
+    }
+    let rate_limit = RateLimit {
+        limit: burst,
+        remaining: ((now - allow_at) / interval).floor() as usize,
+        reset: to_seconds(next.ceil() as u64),
+    };
+    (
+        Response::Ok(rate_limit),
+        State::Gcra { tat: next },
+        next.ceil() as u64,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::time::{Time, TimeUnit};
+
+    fn quota(amount: usize) -> Frequency {
+        Frequency {
+            amount,
+            interval: Time {
+                amount: 1,
+                unit: TimeUnit::Seconds,
+            },
+        }
+    }
+
+    /// Send requests at the given milliseconds, returns the remaining requests or `None` when limited.
+    fn run(algorithm: Algorithm, amount: usize, requests: &[u64]) -> Vec<Option<usize>> {
+        let quota = quota(amount);
+        let mut state = None;
+        requests
+            .iter()
+            .map(|now| {
+                let (response, entry) = limit(state.take(), &quota, algorithm, *now);
+                state = Some(entry.state);
+                match response {
+                    Response::Ok(rate_limit) => Some(rate_limit.remaining),
+                    Response::Limited(_) => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_window() {
+        assert_eq!(
+            run(Algorithm::FixedWindow, 2, &[0, 100, 200, 1001]),
+            vec![Some(1), Some(0), None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn test_sliding_window_log() {
+        assert_eq!(
+            run(
+                Algorithm::SlidingWindowLog,
+                2,
+                &[0, 900, 950, 1000, 1100, 1900]
+            ),
+            vec![Some(1), Some(0), None, Some(0), None, Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_sliding_window_counter() {
+        assert_eq!(
+            run(
+                Algorithm::SlidingWindowCounter,
+                2,
+                &[500, 900, 1000, 1400, 1500, 2600]
+            ),
+            vec![Some(1), Some(0), None, None, Some(0), Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        assert_eq!(
+            run(
+                Algorithm::TokenBucket { burst: 3 },
+                2,
+                &[0, 0, 0, 0, 500, 500, 2000]
+            ),
+            vec![Some(2), Some(1), Some(0), None, Some(0), None, Some(2)]
+        );
+    }
+
+    #[test]
+    fn test_gcra() {
+        assert_eq!(
+            run(Algorithm::Gcra { burst: 2 }, 2, &[0, 0, 0, 500, 500, 2000]),
+            vec![Some(1), Some(0), None, Some(0), None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn test_limited_reset() {
+        let quota = quota(1);
+        let (_, entry) = limit(None, &quota, Algorithm::Gcra { burst: 1 }, 0);
+        let (response, _) = limit(Some(entry.state), &quota, Algorithm::Gcra { burst: 1 }, 200);
+        assert!(matches!(response, Response::Limited(1)));
+    }
+
+    #[tokio::test]
+    async fn test_sweep() {
+        let datastore = InMemoryDatastore::new();
+        datastore
+            .get_rate_limit("key", &quota(1), Algorithm::FixedWindow)
+            .await
+            .unwrap();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        sweep(&datastore.data, now);
+        assert_eq!(datastore.data.lock().unwrap().len(), 1);
+        sweep(&datastore.data, now + 2000);
+        assert_eq!(datastore.data.lock().unwrap().len(), 0);
+    }
 }
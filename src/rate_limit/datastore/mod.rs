@@ -1,4 +1,5 @@
-use crate::utils::time::Frequency;
+use super::config::Algorithm;
+use crate::utils::time::{Frequency, TimeUnit};
 use anyhow::Result;
 use async_trait::async_trait;
 
@@ -15,7 +16,12 @@ pub enum Response {
 
 #[async_trait]
 pub trait Datastore {
-    async fn get_rate_limit(&self, key: &str, quota: &Frequency) -> Result<Response>;
+    async fn get_rate_limit(
+        &self,
+        key: &str,
+        quota: &Frequency,
+        algorithm: Algorithm,
+    ) -> Result<Response>;
 }
 
 #[derive(Clone, Debug)]
@@ -24,3 +30,13 @@ pub struct RateLimit {
     pub remaining: usize,
     pub reset: usize,
 }
+
+/// Length of the interval of the frequency in milliseconds.
+fn window(quota: &Frequency) -> u64 {
+    quota.interval.convert(TimeUnit::Seconds).amount as u64 * 1000
+}
+
+/// Convert a timestamp in milliseconds to seconds, rounding up.
+fn to_seconds(timestamp: u64) -> usize {
+    timestamp.div_ceil(1000) as usize
+}
@@ -2,23 +2,116 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use bb8_redis::{bb8::Pool, redis, RedisConnectionManager};
 
-use crate::time::{Frequency, TimeUnit};
+use crate::{
+    rate_limit::config::Algorithm,
+    time::{Frequency, TimeUnit},
+};
 
-use super::{Datastore, RateLimit, Response};
+use super::{to_seconds, window, Datastore, RateLimit, Response};
+
+/// Scripts take the key of the rate limit, the current timestamp and the interval in milliseconds,
+/// the amount of the frequency and the limit of the requests, and return whether the request
+/// is allowed, the remaining requests and the timestamp of the reset in milliseconds.
+const SLIDING_WINDOW_LOG: &str = r"
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+local count = redis.call('ZCARD', KEYS[1])
+if count >= limit then
+    local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+    return {0, 0, tonumber(oldest[2]) + window}
+end
+redis.call('ZADD', KEYS[1], now, ARGV[1] .. ':' .. count)
+redis.call('PEXPIRE', KEYS[1], window)
+local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+return {1, limit - count - 1, tonumber(oldest[2]) + window}
+";
+
+const SLIDING_WINDOW_COUNTER: &str = r"
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+local index = math.floor(now / window)
+local state = redis.call('HMGET', KEYS[1], 'window', 'current', 'previous')
+local stored = tonumber(state[1])
+local current = tonumber(state[2]) or 0
+local previous = tonumber(state[3]) or 0
+if stored ~= index then
+    if stored == index - 1 then
+        previous = current
+    else
+        previous = 0
+    end
+    current = 0
+end
+local reset = (index + 1) * window
+local weighted = previous * (reset - now)
+if weighted + (current + 1) * window > limit * window then
+    if current < limit and previous > 0 then
+        reset = reset - math.floor((limit - 1 - current) * window / previous)
+    end
+    return {0, 0, reset}
+end
+current = current + 1
+redis.call('HSET', KEYS[1], 'window', index, 'current', current, 'previous', previous)
+redis.call('PEXPIRE', KEYS[1], 2 * window)
+return {1, math.floor((limit * window - weighted - current * window) / window), reset}
+";
+
+const TOKEN_BUCKET: &str = r"
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local amount = tonumber(ARGV[3])
+local burst = tonumber(ARGV[4])
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+local tokens = tonumber(state[1]) or burst
+local updated = tonumber(state[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - updated) * amount / window)
+if tokens < 1 then
+    return {0, 0, now + math.ceil((1 - tokens) * window / amount)}
+end
+tokens = tokens - 1
+local full = math.ceil((burst - tokens) * window / amount)
+redis.call('HSET', KEYS[1], 'tokens', string.format('%.6f', tokens), 'updated', ARGV[1])
+redis.call('PEXPIRE', KEYS[1], math.max(full, 1))
+return {1, math.floor(tokens), now + full}
+";
+
+const GCRA: &str = r"
+local now = tonumber(ARGV[1])
+local interval = tonumber(ARGV[2]) / tonumber(ARGV[3])
+local tolerance = interval * tonumber(ARGV[4])
+local tat = math.max(tonumber(redis.call('GET', KEYS[1])) or now, now)
+local next_tat = tat + interval
+local allow_at = next_tat - tolerance
+if now < allow_at then
+    return {0, 0, math.ceil(allow_at)}
+end
+redis.call('SET', KEYS[1], string.format('%.3f', next_tat), 'PX', math.ceil(next_tat - now))
+return {1, math.floor((now - allow_at) / interval), math.ceil(next_tat)}
+";
 
 pub struct RedisDatastore {
     pool: Pool<RedisConnectionManager>,
+    sliding_window_log: redis::Script,
+    sliding_window_counter: redis::Script,
+    token_bucket: redis::Script,
+    gcra: redis::Script,
 }
 
 impl RedisDatastore {
     pub fn new(pool: Pool<RedisConnectionManager>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            sliding_window_log: redis::Script::new(SLIDING_WINDOW_LOG),
+            sliding_window_counter: redis::Script::new(SLIDING_WINDOW_COUNTER),
+            token_bucket: redis::Script::new(TOKEN_BUCKET),
+            gcra: redis::Script::new(GCRA),
+        }
     }
-}
 
-#[async_trait]
-impl Datastore for RedisDatastore {
-    async fn get_rate_limit(&self, key: &str, quota: &Frequency) -> Result<Response> {
+    async fn fixed_window(&self, key: &str, quota: &Frequency) -> Result<Response> {
         let now = chrono::Utc::now().timestamp() as usize;
         let mut conn = self.pool.get().await.with_context(|| {
             format!("Failed to get connection from Redis pool for key: {}", key)
@@ -51,4 +144,83 @@ impl Datastore for RedisDatastore {
             })
             .with_context(|| format!("Failed to get rate limit for key: {}", key))
     }
+
+    async fn run_script(
+        &self,
+        script: &redis::Script,
+        key: &str,
+        quota: &Frequency,
+        limit: usize,
+    ) -> Result<Response> {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let mut conn = self.pool.get().await.with_context(|| {
+            format!("Failed to get connection from Redis pool for key: {}", key)
+        })?;
+        script
+            .key(key)
+            .arg(now)
+            .arg(window(quota))
+            .arg(quota.amount)
+            .arg(limit)
+            .invoke_async(&mut *conn)
+            .await
+            .map(|(allowed, remaining, reset): (bool, usize, u64)| {
+                if allowed {
+                    Response::Ok(RateLimit {
+                        limit,
+                        remaining,
+                        reset: to_seconds(reset),
+                    })
+                } else {
+                    Response::Limited(to_seconds(reset))
+                }
+            })
+            .with_context(|| format!("Failed to get rate limit for key: {}", key))
+    }
+}
+
+#[async_trait]
+impl Datastore for RedisDatastore {
+    async fn get_rate_limit(
+        &self,
+        key: &str,
+        quota: &Frequency,
+        algorithm: Algorithm,
+    ) -> Result<Response> {
+        // Each algorithm stores a different type under its own key.
+        match algorithm {
+            Algorithm::FixedWindow => self.fixed_window(key, quota).await,
+            Algorithm::SlidingWindowLog => {
+                self.run_script(
+                    &self.sliding_window_log,
+                    &format!("{key}:sliding-window-log"),
+                    quota,
+                    quota.amount,
+                )
+                .await
+            }
+            Algorithm::SlidingWindowCounter => {
+                self.run_script(
+                    &self.sliding_window_counter,
+                    &format!("{key}:sliding-window-counter"),
+                    quota,
+                    quota.amount,
+                )
+                .await
+            }
+            Algorithm::TokenBucket { burst } => {
+                self.run_script(
+                    &self.token_bucket,
+                    &format!("{key}:token-bucket"),
+                    quota,
+                    burst.max(1),
+                )
+                .await
+            }
+            Algorithm::Gcra { burst } => {
+                self.run_script(&self.gcra, &format!("{key}:gcra"), quota, burst.max(1))
+                    .await
+            }
+        }
+    }
 }
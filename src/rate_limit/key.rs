@@ -0,0 +1,67 @@
+use super::config::Key;
+use crate::{
+    http::{headers, HeaderMapExt, Request},
+    utils::route_params,
+};
+
+/// Value of the key in the request, `None` when the request is missing it.
+pub fn extract(key: &Key, request: &Request) -> Option<String> {
+    match key {
+        Key::Ip => header(request, &headers::REAL_IP),
+        Key::ApiToken => header(request, &headers::API_TOKEN),
+        Key::Header(name) => header(request, name.as_str()),
+        Key::Param { route, name } => {
+            let path = request.path.split(['?', '#']).next().unwrap_or_default();
+            route_params(route, path)
+                .find(|(param, value)| param == name && !value.is_empty())
+                .map(|(_, value)| value.to_string())
+        }
+        Key::All(keys) => keys
+            .iter()
+            .map(|key| extract(key, request))
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join("--")),
+    }
+}
+
+fn header<K: TryInto<http::HeaderName>>(request: &Request, name: K) -> Option<String> {
+    request
+        .header(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let mut request = Request::new("/users/1?page=2".to_string(), Method::GET);
+        request.insert_header(&headers::REAL_IP, "1.2.3.4");
+        request.insert_header(&headers::USERNAME, "user");
+        let param = Key::Param {
+            route: "/users/:id".to_string(),
+            name: "id".to_string(),
+        };
+        assert_eq!(extract(&Key::Ip, &request), Some("1.2.3.4".to_string()));
+        assert_eq!(extract(&Key::ApiToken, &request), None);
+        assert_eq!(
+            extract(&Key::Header("X-Username".to_string()), &request),
+            Some("user".to_string())
+        );
+        assert_eq!(extract(&param, &request), Some("1".to_string()));
+        assert_eq!(
+            extract(&Key::All(vec![Key::Ip, param.clone()]), &request),
+            Some("1.2.3.4--1".to_string())
+        );
+        assert_eq!(
+            extract(&Key::All(vec![Key::ApiToken, param]), &request),
+            None
+        );
+    }
+}
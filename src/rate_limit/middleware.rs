@@ -1,4 +1,4 @@
-use super::{datastore, Datastore};
+use super::{context, datastore, key, Datastore, Key, MissingKey};
 use crate::{
     gateway::{middleware::Middleware as TMiddleware, next::Next, Result},
     http::{headers, HeaderMapExt, Request, Response},
@@ -21,6 +21,29 @@ impl Middleware {
         Self { ctx, datastore }
     }
 
+    /// Key the requests are counted by in the scope, `None` when the request is not counted.
+    /// Requests missing the key are handled by the policy of the rules.
+    fn key(
+        ctx: &Ctx,
+        rules: &context::Rules,
+        scope: &str,
+        key: &Key,
+        request: &Request,
+    ) -> std::result::Result<Option<String>, StatusCode> {
+        let value = match key::extract(key, request) {
+            Some(value) => value,
+            None => match rules.missing_key {
+                MissingKey::Shared => String::new(),
+                MissingKey::Skip => return Ok(None),
+                MissingKey::Reject(status) => return Err(status),
+            },
+        };
+        Ok(Some(format!(
+            "{}--{}--{}--{}",
+            ctx.app_id, ctx.endpoint_id, scope, value
+        )))
+    }
+
     fn too_many_requests(reset: usize) -> Response {
         let mut response = Response::new(StatusCode::TOO_MANY_REQUESTS);
         response.insert_header(
@@ -40,42 +63,59 @@ impl TMiddleware for Middleware {
                 return next.run(request).await;
             }
         };
-        let token = match request
-            .header("X-Api-Token")
+        let token = request
+            .header(&headers::API_TOKEN)
             .and_then(|header| header.to_str().ok())
-        {
-            Some(token) => token.to_string(),
-            None => {
+            .map(str::to_string);
+        if let Some(token) = token.as_deref() {
+            // Unknown tokens would get around the anonymous quota with a bucket of their own.
+            let rules = [config.get(ctx.endpoint_id), Some(config.global())];
+            if rules
+                .iter()
+                .flatten()
+                .any(|rules| rules.anonymous.is_some())
+                && rules
+                    .iter()
+                    .flatten()
+                    .all(|rules| rules.token_quota(token).is_none())
+            {
                 return Ok(Response::new(StatusCode::UNAUTHORIZED));
             }
-        };
-        let quota = config
+        }
+        let rules = config
             .get(ctx.endpoint_id)
-            .and_then(|rules| rules.find_quota(&token))
-            .or_else(|| config.global().find_quota(&token));
-        let quota = match quota {
+            .filter(|rules| rules.find_quota(token.as_deref()).is_some())
+            .unwrap_or_else(|| config.global());
+        let quota = match rules.find_quota(token.as_deref()) {
             Some(quota) => quota,
             None => {
                 warn!("No quota found for endpoint: {}", ctx.endpoint_id);
                 return next.run(request).await;
             }
         };
-        let ip = match request
-            .header(&headers::REAL_IP)
-            .and_then(|header| header.to_str().ok())
-        {
-            Some(ip) => ip.to_string(),
-            None => {
-                return Ok(Response::new(StatusCode::BAD_REQUEST));
+        let total_key = match Self::key(ctx, rules, "total", &rules.total_key, &request) {
+            Ok(key) => key,
+            Err(status) => {
+                return Ok(Response::new(status));
             }
         };
-        let total_key = format!("{}--{}--{}", ctx.app_id, ctx.endpoint_id, token);
-        let user_key = format!("{}--{}--{}", ctx.app_id, ctx.endpoint_id, ip);
+        let user_key = match quota.user.as_ref() {
+            Some(_) => match Self::key(ctx, rules, "user", &rules.user_key, &request) {
+                Ok(key) => key,
+                Err(status) => {
+                    return Ok(Response::new(status));
+                }
+            },
+            None => None,
+        };
 
         let rate_limit = {
             use datastore::Response;
-            match quota.user.as_ref() {
-                Some(frequency) => match self.datastore.get_rate_limit(&user_key, frequency).await?
+            match quota.user.as_ref().zip(user_key) {
+                Some((frequency, user_key)) => match self
+                    .datastore
+                    .get_rate_limit(&user_key, frequency, quota.algorithm)
+                    .await?
                 {
                     Response::Ok(rate_limit) => Some(rate_limit),
                     Response::Limited(reset) => {
@@ -87,13 +127,15 @@ impl TMiddleware for Middleware {
         };
         {
             use datastore::Response;
-            if let Response::Limited(reset) = self
-                .datastore
-                .get_rate_limit(&total_key, &quota.total)
-                .await?
-            {
-                return Ok(Self::too_many_requests(reset));
-            };
+            if let Some(total_key) = total_key {
+                if let Response::Limited(reset) = self
+                    .datastore
+                    .get_rate_limit(&total_key, &quota.total, quota.algorithm)
+                    .await?
+                {
+                    return Ok(Self::too_many_requests(reset));
+                };
+            }
         }
         let mut response = next.run(request).await?;
         if let Some(rate_limit) = rate_limit {
@@ -2,6 +2,7 @@ mod builder;
 pub mod config;
 mod context;
 pub mod datastore;
+pub(crate) mod key;
 mod middleware;
 
 use std::collections::HashMap;
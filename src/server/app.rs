@@ -5,6 +5,13 @@ use tokio::sync::{mpsc, oneshot};
 #[cfg(feature = "tls")]
 use tokio_rustls::{rustls, TlsAcceptor};
 
+#[cfg(feature = "cache")]
+use crate::cache;
+#[cfg(feature = "acme")]
+use crate::gateway::entrypoint::tls::acme::{self, AcmeConfig, AcmeManager};
+#[cfg(feature = "tls")]
+use crate::{CertStore, ClientAuth, RedirectConfig};
+
 use crate::gateway::entrypoint::{self, EntryPoint};
 use crate::gateway::middleware::MiddlewareBuilderService;
 use crate::gateway::router::{RouterBuilder, RouterBuilderService};
@@ -31,6 +38,17 @@ pub struct ServerBuilder {
     app_tls_port: u16,
     #[cfg(feature = "tls")]
     tls_config: TlsAcceptor,
+    proxy_protocol: bool,
+    #[cfg(feature = "tls")]
+    tls_proxy_protocol: bool,
+    #[cfg(feature = "tls")]
+    client_auth: ClientAuth,
+    #[cfg(feature = "tls")]
+    redirect: RedirectConfig,
+    #[cfg(feature = "acme")]
+    acme: Option<AcmeManager>,
+    #[cfg(feature = "cache")]
+    cache_admin: Option<(u16, cache::Admin)>,
     health_check_port: u16,
 }
 
@@ -51,6 +69,17 @@ impl ServerBuilder {
                     .with_no_client_auth()
                     .with_cert_resolver(std::sync::Arc::new(entrypoint::tls::EmptyResolver::new())),
             )),
+            proxy_protocol: false,
+            #[cfg(feature = "tls")]
+            tls_proxy_protocol: false,
+            #[cfg(feature = "tls")]
+            client_auth: ClientAuth::new(),
+            #[cfg(feature = "tls")]
+            redirect: RedirectConfig::new(),
+            #[cfg(feature = "acme")]
+            acme: None,
+            #[cfg(feature = "cache")]
+            cache_admin: None,
             health_check_port: 9000,
         }
     }
@@ -97,6 +126,71 @@ impl ServerBuilder {
         self
     }
 
+    /// Serve TLS on the given port with certificates selected from the store by SNI.
+    /// The store may be updated (e.g. by [`CertStore::watch`]) while the server is running.
+    #[cfg(feature = "tls")]
+    pub fn with_cert_store(self, port: u16, store: std::sync::Arc<CertStore>) -> Self {
+        self.with_tls(
+            port,
+            TlsAcceptor::from(std::sync::Arc::new(
+                rustls::ServerConfig::builder()
+                    .with_no_client_auth()
+                    .with_cert_resolver(store),
+            )),
+        )
+    }
+
+    /// Serve TLS on the given port with certificates obtained and renewed via ACME.
+    /// HTTP-01 challenges are answered on the application port, TLS-ALPN-01 challenges on the TLS port.
+    /// Other certificates may be served alongside by issuing into a shared store with [`AcmeConfig::with_store`].
+    #[cfg(feature = "acme")]
+    pub fn with_acme(mut self, port: u16, config: AcmeConfig) -> Self {
+        let (manager, acceptor) = acme::setup(config);
+        self.acme = Some(manager);
+        self.with_tls(port, acceptor)
+    }
+
+    /// Expect a PROXY protocol (v1 or v2) header on every connection to the application port.
+    /// The client address announced by the proxy is used instead of the socket peer address.
+    /// The default is false
+    pub fn with_proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// Expect a PROXY protocol (v1 or v2) header on every connection to the TLS port.
+    /// The header is read before the TLS handshake.
+    /// The default is false
+    #[cfg(feature = "tls")]
+    pub fn with_tls_proxy_protocol(mut self, enabled: bool) -> Self {
+        self.tls_proxy_protocol = enabled;
+        self
+    }
+
+    /// Verify client certificates on the TLS port against the given certificate authorities.
+    /// The verified certificate is available to middlewares as [`crate::Peer::client_certificate`].
+    /// The default is no client certificate verification
+    #[cfg(feature = "tls")]
+    pub fn with_client_auth(mut self, client_auth: ClientAuth) -> Self {
+        self.client_auth = client_auth;
+        self
+    }
+
+    /// Configure how requests to the application port are handled when TLS is enabled.
+    /// The default is to redirect all requests to HTTPS with `301 Moved Permanently`
+    #[cfg(feature = "tls")]
+    pub fn with_redirect(mut self, redirect: RedirectConfig) -> Self {
+        self.redirect = redirect;
+        self
+    }
+
+    /// Serve the cache admin API on the port.
+    #[cfg(feature = "cache")]
+    pub fn with_cache_admin(mut self, port: u16, admin: cache::Admin) -> Self {
+        self.cache_admin = Some((port, admin));
+        self
+    }
+
     /// Set the port for the health check service.
     /// The default port is 9000
     pub fn with_health_check_port(mut self, port: u16) -> Self {
@@ -136,21 +230,39 @@ impl ServerBuilder {
             middlewares,
         );
         #[cfg(feature = "tls")]
+        let entrypoint = entrypoint.with_hsts(|app| self.redirect.hsts(app));
+        #[cfg(feature = "tls")]
+        let redirect = entrypoint::tls::RedirectHandler::new(self.proxy_protocol, self.redirect);
+        #[cfg(feature = "acme")]
+        let redirect = match self.acme.as_ref() {
+            Some(acme) => redirect.with_acme_challenges(acme.challenges()),
+            None => redirect,
+        };
+        #[cfg(feature = "tls")]
         let handler = entrypoint::tls::build(
             entrypoint,
             self.host,
             self.app_port,
             self.app_tls_port,
-            self.tls_config,
+            entrypoint::tls::Acceptor::new(self.tls_config, self.client_auth)?,
+            self.tls_proxy_protocol,
+            redirect,
         );
         #[cfg(not(feature = "tls"))]
-        let handler = entrypoint::tcp::build(entrypoint, self.host, self.app_port);
+        let handler =
+            entrypoint::tcp::build(entrypoint, self.host, self.app_port, self.proxy_protocol);
         let server = Server {
             app: handler,
             health_check: HttpServer::new(
                 SocketAddr::new(self.host, self.health_check_port),
                 HealthCheck,
             ),
+            #[cfg(feature = "acme")]
+            acme: self.acme,
+            #[cfg(feature = "cache")]
+            cache_admin: self
+                .cache_admin
+                .map(|(port, admin)| HttpServer::new(SocketAddr::new(self.host, port), admin)),
         };
         Ok(server)
     }
@@ -162,12 +274,28 @@ pub struct Server {
     #[cfg(not(feature = "tls"))]
     pub app: entrypoint::tcp::TcpServer,
     pub health_check: HttpServer<HealthCheck>,
+    #[cfg(feature = "acme")]
+    pub acme: Option<AcmeManager>,
+    #[cfg(feature = "cache")]
+    pub cache_admin: Option<HttpServer<cache::Admin>>,
 }
 
 impl Server {
     /// Start the server.
     pub async fn run(self) {
         debug!("Starting server");
+        #[cfg(feature = "acme")]
+        if let Some(acme) = self.acme {
+            tokio::spawn(acme.run());
+        }
+        #[cfg(feature = "cache")]
+        if let Some(cache_admin) = self.cache_admin {
+            tokio::spawn(async move {
+                if let Err(err) = cache_admin.run().await {
+                    error!("cache_admin error: {:?}", err);
+                }
+            });
+        }
         let (tx_app, rx_app) = oneshot::channel();
         let (tx_health, rx_health) = oneshot::channel();
         let (tx, mut rx) = mpsc::channel(2);
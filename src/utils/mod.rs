@@ -29,3 +29,41 @@ impl<T> Also<T> for T {
         self
     }
 }
+
+/// Parameters of the route pattern (e.g. `/users/:id`) matched against the path segments.
+#[cfg(any(feature = "cache", feature = "rate-limit"))]
+pub(crate) fn route_params<'a>(
+    route: &'a str,
+    path: &'a str,
+) -> impl Iterator<Item = (&'a str, &'a str)> {
+    route
+        .split('/')
+        .zip(path.split('/'))
+        .filter_map(|(pattern, segment)| Some((pattern.strip_prefix(':')?, segment)))
+}
+
+/// Random id telling the gateways sharing a datastore apart, e.g. in lock tokens.
+#[cfg(any(feature = "cache", feature = "concurrency-limit"))]
+pub(crate) fn instance_id() -> String {
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+    };
+
+    format!("{:016x}", RandomState::new().build_hasher().finish())
+}
+
+#[cfg(all(test, any(feature = "cache", feature = "rate-limit")))]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_route_params() {
+        assert_eq!(
+            route_params("/users/:id/posts/:post", "/users/1/posts/2").collect::<Vec<_>>(),
+            vec![("id", "1"), ("post", "2")]
+        );
+    }
+}
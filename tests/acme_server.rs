@@ -0,0 +1,234 @@
+mod helper;
+
+#[cfg(feature = "acme")]
+mod tests {
+    use helper::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+    use testing_utils::macros as utils;
+    use tokio::time::sleep;
+
+    async fn assert_certificate_issued(ctx: &Context) {
+        let cert_path = ctx.storage.join("example.test.crt");
+        for _ in 0..60 {
+            if cert_path.is_file() {
+                break;
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+        assert!(cert_path.is_file(), "certificate was not issued");
+        assert!(ctx.storage.join("example.test.key").is_file());
+        assert!(ctx.storage.join("account.key").is_file());
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            handshake(ctx, "example.test").await,
+            Some(b"http/1.1".to_vec())
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_issue_certificate(ctx: Context) {
+        assert_certificate_issued(&ctx).await;
+    }
+
+    #[utils::test(setup = before_each_tls_alpn, teardown = after_each)]
+    async fn should_issue_certificate_with_tls_alpn_challenge(ctx: Context) {
+        assert_certificate_issued(&ctx).await;
+    }
+
+    #[cfg(unix)]
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_store_private_keys_readable_by_owner_only(ctx: Context) {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_certificate_issued(&ctx).await;
+        for file in ["example.test.key", "account.key"] {
+            let mode = std::fs::metadata(ctx.storage.join(file))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file);
+        }
+    }
+
+    mod helper {
+        use gateway::{
+            acme::{AcmeConfig, Challenge},
+            time::{Time, TimeUnit},
+        };
+        use std::{env, fs, net::IpAddr, path::PathBuf, sync::Arc};
+        use testing_utils::testcontainers::{
+            core::{ContainerPort, Host, Mount, WaitFor},
+            runners::AsyncRunner,
+            ContainerAsync, GenericImage, ImageExt,
+        };
+        use tokio::net::TcpStream;
+        use tokio_rustls::{
+            rustls::{
+                client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+                crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+                pki_types::{CertificateDer, ServerName, UnixTime},
+                ClientConfig, DigitallySignedStruct, SignatureScheme,
+            },
+            TlsConnector,
+        };
+
+        /// Pebble issues certificates from a random root, so only the handshake is verified.
+        #[derive(Debug)]
+        struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+        impl ServerCertVerifier for AcceptAnyCertificate {
+            fn verify_server_cert(
+                &self,
+                _end_entity: &CertificateDer<'_>,
+                _intermediates: &[CertificateDer<'_>],
+                _server_name: &ServerName<'_>,
+                _ocsp_response: &[u8],
+                _now: UnixTime,
+            ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+                Ok(ServerCertVerified::assertion())
+            }
+
+            fn verify_tls12_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+                verify_tls12_signature(
+                    message,
+                    cert,
+                    dss,
+                    &self.0.signature_verification_algorithms,
+                )
+            }
+
+            fn verify_tls13_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+                verify_tls13_signature(
+                    message,
+                    cert,
+                    dss,
+                    &self.0.signature_verification_algorithms,
+                )
+            }
+
+            fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+                self.0.signature_verification_algorithms.supported_schemes()
+            }
+        }
+
+        /// Perform a TLS handshake, returning the negotiated ALPN protocol.
+        pub async fn handshake(ctx: &Context, domain: &str) -> Option<Vec<u8>> {
+            let mut config = ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(Arc::new(
+                    tokio_rustls::rustls::crypto::aws_lc_rs::default_provider(),
+                ))))
+                .with_no_client_auth();
+            config.alpn_protocols = vec![b"http/1.1".to_vec()];
+            let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app))
+                .await
+                .unwrap();
+            let stream = TlsConnector::from(Arc::new(config))
+                .connect(ServerName::try_from(domain.to_string()).unwrap(), stream)
+                .await
+                .unwrap();
+            stream.get_ref().1.alpn_protocol().map(<[u8]>::to_vec)
+        }
+
+        pub struct Context {
+            _context: crate::helper::Context,
+            _pebble: ContainerAsync<GenericImage>,
+            pub app: u16,
+            pub storage: PathBuf,
+        }
+
+        /// Pebble configuration validating the challenges against the ports of the gateway.
+        fn pebble_config(http_port: u16, tls_port: u16) -> String {
+            format!(
+                r#"{{
+    "pebble": {{
+        "listenAddress": "0.0.0.0:14000",
+        "managementListenAddress": "0.0.0.0:15000",
+        "certificate": "/test/certs/localhost/cert.pem",
+        "privateKey": "/test/certs/localhost/key.pem",
+        "httpPort": {http_port},
+        "tlsPort": {tls_port},
+        "ocspResponderURL": "",
+        "externalAccountBindingRequired": false
+    }}
+}}"#
+            )
+        }
+
+        pub async fn before_each() -> Context {
+            setup(Challenge::Http01).await
+        }
+
+        pub async fn before_each_tls_alpn() -> Context {
+            setup(Challenge::TlsAlpn01).await
+        }
+
+        async fn setup(challenge: Challenge) -> Context {
+            // Pebble validates the challenges by connecting to the ports of the gateway,
+            // so they have to be known before it is started.
+            let ports = testing_utils::get_random_ports(2);
+            let (http_port, tls_port) = (ports[0], ports[1]);
+            let storage = env::temp_dir().join(format!("gateway-acme-{}", tls_port));
+            let _ = fs::remove_dir_all(&storage);
+            fs::create_dir_all(&storage).unwrap();
+            let config_path = storage.join("pebble-config.json");
+            fs::write(&config_path, pebble_config(http_port, tls_port)).unwrap();
+            let pebble = GenericImage::new("ghcr.io/letsencrypt/pebble", "2.6.0")
+                .with_exposed_port(ContainerPort::Tcp(14000))
+                .with_wait_for(WaitFor::message_on_stdout("ACME directory available"))
+                .with_mount(Mount::bind_mount(
+                    config_path.to_string_lossy(),
+                    "/pebble-config.json",
+                ))
+                .with_cmd(["-config", "/pebble-config.json"])
+                // The domain resolves to the host the gateway is running on.
+                .with_host("example.test", Host::HostGateway)
+                .with_env_var("PEBBLE_VA_NOSLEEP", "1")
+                .start()
+                .await
+                .expect("Pebble could not be started");
+            let pebble_port = pebble.get_host_port_ipv4(14000).await.unwrap();
+            let (context, _) = crate::helper::setup_with_ports(0, |server_builder, _| {
+                server_builder
+                    // Reachable from the Pebble container.
+                    .with_host(IpAddr::from([0, 0, 0, 0]))
+                    .with_app_port(http_port)
+                    .with_acme(
+                        tls_port,
+                        AcmeConfig::new(
+                            format!("https://localhost:{}/dir", pebble_port),
+                            storage.clone(),
+                        )
+                        .with_contact("admin@example.test".to_string())
+                        .add_certificate(vec!["example.test".to_string()])
+                        .with_challenge(challenge)
+                        .with_check_interval(Time {
+                            amount: 1,
+                            unit: TimeUnit::Hours,
+                        })
+                        .with_accept_invalid_certs(true),
+                    )
+            })
+            .await;
+            Context {
+                _context: context,
+                _pebble: pebble,
+                app: tls_port,
+                storage,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
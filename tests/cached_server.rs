@@ -148,21 +148,259 @@ mod tests {
         }
     }
 
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_not_cache_responses_larger_than_max_size(ctx: Context) {
+        for count in 1..=2 {
+            let response = surf::get(format!("http://127.0.0.1:{}/email", &ctx.context.app))
+                .header("Host", "app")
+                .header("X-Email", "john.doe@example.com")
+                .await;
+            debug!("{:?}", response);
+            let mut response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.header("X-Cache").unwrap(), "MISS");
+            assert_eq!(
+                response.body_string().await.unwrap(),
+                "john.doe@example.com"
+            );
+            assert_req_count!(ctx, count);
+        }
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_follow_request_cache_control(ctx: Context) {
+        {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", &ctx.context.app))
+                .header("Host", "app")
+                .header("Cache-Control", "only-if-cached")
+                .await;
+            debug!("{:?}", response);
+            let response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::GatewayTimeout);
+            assert_eq!(response.header("X-Cache").unwrap().get(0).unwrap(), "MISS");
+            assert_req_count!(ctx, 0);
+        }
+        {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", &ctx.context.app))
+                .header("Host", "app")
+                .await;
+            debug!("{:?}", response);
+            let mut response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.body_string().await.unwrap(), "Hello, world!");
+            assert_eq!(response.header("X-Cache").unwrap().get(0).unwrap(), "MISS");
+            assert_req_count!(ctx, 1);
+        }
+        {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", &ctx.context.app))
+                .header("Host", "app")
+                .header("Cache-Control", "only-if-cached")
+                .await;
+            debug!("{:?}", response);
+            let mut response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.body_string().await.unwrap(), "Hello, world!");
+            assert_eq!(response.header("X-Cache").unwrap().get(0).unwrap(), "HIT");
+            assert!(response.header("Age").is_some());
+            assert_req_count!(ctx, 1);
+        }
+        {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", &ctx.context.app))
+                .header("Host", "app")
+                .header("Cache-Control", "no-cache")
+                .await;
+            debug!("{:?}", response);
+            let mut response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.body_string().await.unwrap(), "Hello, world!");
+            assert_eq!(response.header("X-Cache").unwrap().get(0).unwrap(), "MISS");
+            assert_req_count!(ctx, 2);
+        }
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_cached_response_on_unrefreshable_not_modified(ctx: Context) {
+        use wiremock::{
+            matchers::{header_exists, method, path},
+            Mock, ResponseTemplate,
+        };
+
+        Mock::given(method("GET"))
+            .and(path("/private"))
+            .and(header_exists("If-None-Match"))
+            .respond_with(
+                ResponseTemplate::new(304)
+                    .append_header("ETag", "\"v1\"")
+                    .append_header("Cache-Control", "max-age=0"),
+            )
+            .with_priority(1)
+            .mount(&ctx.context.origin_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/private"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("cached")
+                    .append_header("ETag", "\"v1\"")
+                    .append_header("Cache-Control", "max-age=60"),
+            )
+            .with_priority(2)
+            .mount(&ctx.context.origin_server)
+            .await;
+        let get = |headers: &'static [(&'static str, &'static str)]| {
+            let mut request = surf::get(format!("http://127.0.0.1:{}/private", &ctx.context.app))
+                .header("Host", "app");
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            request
+        };
+        {
+            let mut response = get(&[]).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.header("X-Cache").unwrap(), "MISS");
+            assert_eq!(response.body_string().await.unwrap(), "cached");
+            assert_req_count!(ctx, 1);
+        }
+        {
+            let mut response = get(&[("Cache-Control", "no-cache")]).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.header("X-Cache").unwrap(), "REVALIDATED");
+            assert_eq!(response.body_string().await.unwrap(), "cached");
+            assert_req_count!(ctx, 2);
+        }
+        {
+            let response = get(&[("Cache-Control", "no-cache"), ("If-None-Match", "\"v1\"")])
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NotModified);
+            assert_req_count!(ctx, 3);
+        }
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_purge_cached_responses(ctx: Context) {
+        let (app, admin_port) = (ctx.context.app, ctx.admin);
+        let get = move || async move {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", app))
+                .header("Host", "app")
+                .await;
+            debug!("{:?}", response);
+            let response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            response
+                .header("X-Cache")
+                .unwrap()
+                .get(0)
+                .unwrap()
+                .to_string()
+        };
+        let admin = move |path: &str, token: &str| {
+            surf::delete(format!("http://127.0.0.1:{}{}", admin_port, path))
+                .header("Authorization", format!("Bearer {}", token))
+        };
+        assert_eq!(get().await, "MISS");
+        assert_eq!(get().await, "HIT");
+        assert_req_count!(ctx, 1);
+        {
+            let response = admin("/apps/app", "invalid").await.unwrap();
+            assert_eq!(response.status(), StatusCode::Unauthorized);
+            assert_eq!(get().await, "HIT");
+        }
+        {
+            let mut response = admin("/apps/app", TOKEN).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.body_string().await.unwrap(), r#"{"purged":1}"#);
+            assert_eq!(get().await, "MISS");
+            assert_req_count!(ctx, 2);
+        }
+        {
+            let mut response = admin("/tags/greeting", TOKEN).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(response.body_string().await.unwrap(), r#"{"purged":1}"#);
+            assert_eq!(get().await, "MISS");
+            assert_req_count!(ctx, 3);
+        }
+        assert_eq!(purge(&ctx).await, http::StatusCode::OK);
+        assert_eq!(purge(&ctx).await, http::StatusCode::NOT_FOUND);
+        assert_eq!(get().await, "MISS");
+        assert_req_count!(ctx, 4);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_invalidate_local_tiers_of_other_gateways(ctx: Context) {
+        use gateway::cache::datastore::{Cache, Datastore, Response};
+
+        let first = tiered_datastore(&ctx).await;
+        let second = tiered_datastore(&ctx).await;
+        let cache = Cache::new(http::StatusCode::OK, vec![], b"cached".to_vec())
+            .with_tags(vec!["tag".to_string()]);
+        first.save_cache("key", cache, None, 60, 0).await.unwrap();
+        match second.fetch_cache("key").await.unwrap() {
+            Response::Hit(cache, _) => assert_eq!(cache.body, b"cached"),
+            _ => panic!("expected the entry saved by the other gateway"),
+        }
+        assert_eq!(first.purge_tag("tag").await.unwrap(), 1);
+        let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(1);
+        while !matches!(second.fetch_cache("key").await.unwrap(), Response::Miss) {
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "expected the entry to be invalidated"
+            );
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+    }
+
     mod helper {
-        use bb8_redis::{bb8, RedisConnectionManager};
+        use bb8_redis::{bb8, redis, RedisConnectionManager};
         use essentials::debug;
-        use gateway::{cache, time};
+        use gateway::{cache, http::HeaderMapExt, time, ReadResponse, Request, WriteRequest};
+        use http::{header, Method, StatusCode};
         use testing_utils::testcontainers::{
             core::{ContainerPort, WaitFor},
             runners::AsyncRunner,
             ContainerAsync, GenericImage,
         };
+        use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+        pub const TOKEN: &str = "token";
 
         pub struct Context {
             pub context: crate::helper::Context,
+            pub admin: u16,
+            pub redis_port: u16,
             _redis_server: ContainerAsync<GenericImage>,
         }
 
+        /// Send a `PURGE` request for the hello endpoint.
+        pub async fn purge(ctx: &Context) -> StatusCode {
+            let mut request =
+                Request::new("/hello".to_string(), Method::from_bytes(b"PURGE").unwrap());
+            request.insert_header(header::HOST, "app");
+            let mut stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.context.app))
+                .await
+                .unwrap();
+            stream.write_request(&request).await.unwrap();
+            stream.flush().await.unwrap();
+            stream.read_response().await.unwrap().0.status
+        }
+
+        /// Two-tier datastore sharing the Redis server with the gateway.
+        pub async fn tiered_datastore(ctx: &Context) -> cache::datastore::TieredDatastore {
+            let url = format!("redis://127.0.0.1:{}", ctx.redis_port);
+            let redis_pool = bb8::Pool::builder()
+                .build(RedisConnectionManager::new(url.as_str()).unwrap())
+                .await
+                .unwrap();
+            cache::datastore::TieredDatastore::connect(
+                cache::datastore::InMemoryDatastore::default(),
+                cache::datastore::RedisDatastore::new(redis_pool),
+                redis::Client::open(url).unwrap(),
+            )
+            .await
+            .unwrap()
+        }
+
         pub async fn before_each() -> Context {
             let redis = GenericImage::new("redis", "7.2.4")
                 .with_exposed_port(ContainerPort::Tcp(6379))
@@ -175,27 +413,56 @@ mod tests {
                 RedisConnectionManager::new(format!("redis://127.0.0.1:{redis_port}")).unwrap();
             let redis_pool = bb8::Pool::builder().build(redis_manager).await.unwrap();
             debug!("{:?}", redis_pool);
-            let context = crate::helper::setup(|server_builder| {
-                server_builder.register_middleware(
-                    1,
-                    cache::Builder::new()
-                        .add_endpoint(
-                            "app",
-                            "hello",
-                            cache::config::Endpoint::new(
-                                time::Time {
-                                    amount: 1,
-                                    unit: time::TimeUnit::Seconds,
-                                },
-                                vec!["X-Username".to_string()],
-                            ),
-                        )
-                        .build(cache::datastore::RedisDatastore::new(redis_pool)),
-                )
+            let datastore = cache::datastore::RedisDatastore::new(redis_pool);
+            let (context, ports) = crate::helper::setup_with_ports(1, |server_builder, ports| {
+                server_builder
+                    .register_middleware(
+                        1,
+                        cache::Builder::new()
+                            .add_endpoint(
+                                "app",
+                                "hello",
+                                cache::config::Endpoint::new(
+                                    time::Time {
+                                        amount: 1,
+                                        unit: time::TimeUnit::Seconds,
+                                    },
+                                    vec!["X-Username".to_string()],
+                                )
+                                .with_purge_method(),
+                            )
+                            .add_endpoint(
+                                "app",
+                                "email",
+                                cache::config::Endpoint::new(
+                                    time::Time {
+                                        amount: 1,
+                                        unit: time::TimeUnit::Minutes,
+                                    },
+                                    vec![],
+                                )
+                                .with_max_size(5),
+                            )
+                            .add_endpoint(
+                                "app",
+                                "private",
+                                cache::config::Endpoint::new(
+                                    time::Time {
+                                        amount: 1,
+                                        unit: time::TimeUnit::Minutes,
+                                    },
+                                    vec![],
+                                ),
+                            )
+                            .build(datastore.clone()),
+                    )
+                    .with_cache_admin(ports[0], cache::Admin::new(datastore, TOKEN))
             })
             .await;
             Context {
                 context,
+                admin: ports[0],
+                redis_port,
                 _redis_server: redis,
             }
         }
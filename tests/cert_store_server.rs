@@ -0,0 +1,120 @@
+mod helper;
+
+#[cfg(feature = "tls")]
+mod tests {
+    use gateway::{http::HeaderMapExt, ReadResponse, Request, WriteRequest};
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+    use testing_utils::macros as utils;
+    use tokio::{io::AsyncWriteExt, net::TcpStream, time::sleep};
+    use tokio_rustls::rustls::pki_types::ServerName;
+
+    async fn run_request(ctx: &Context, domain: &str, root: &Certificate) -> StatusCode {
+        let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app))
+            .await
+            .unwrap();
+        let domain = ServerName::try_from(domain.to_string()).unwrap();
+        let mut stream = connector(root).connect(domain, stream).await.unwrap();
+        let mut request = Request::new("/hello".to_string(), Method::GET);
+        request.insert_header(header::HOST, "app");
+        request.insert_header(header::CONTENT_LENGTH, "0");
+        stream.write_request(&request).await.unwrap();
+        stream.flush().await.unwrap();
+        stream.read_response().await.unwrap().0.status
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_select_certificate_by_sni(ctx: Context) {
+        let example = write_certificate(&ctx.dir, "example", "example.test");
+        let wildcard = write_certificate(&ctx.dir, "wildcard", "*.wildcard.test");
+        ctx.store.load_dir(&ctx.dir).unwrap();
+        assert_eq!(
+            run_request(&ctx, "example.test", &example).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            run_request(&ctx, "api.wildcard.test", &wildcard).await,
+            StatusCode::OK
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_reload_certificates(ctx: Context) {
+        let fallback = write_certificate(&ctx.dir, "default", "fallback.test");
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            run_request(&ctx, "fallback.test", &fallback).await,
+            StatusCode::OK
+        );
+        let added = write_certificate(&ctx.dir, "added", "added.test");
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            run_request(&ctx, "added.test", &added).await,
+            StatusCode::OK
+        );
+    }
+
+    mod helper {
+        use gateway::CertStore;
+        use rcgen::generate_simple_self_signed;
+        use std::{env, fs, path::PathBuf, sync::Arc, time::Duration};
+        use tokio::task::JoinHandle;
+        use tokio_rustls::{
+            rustls::{pki_types::CertificateDer, ClientConfig, RootCertStore},
+            TlsConnector,
+        };
+
+        pub type Certificate = CertificateDer<'static>;
+
+        pub fn write_certificate(dir: &PathBuf, stem: &str, domain: &str) -> Certificate {
+            let rcgen::CertifiedKey { cert, key_pair } =
+                generate_simple_self_signed(vec![domain.to_string()]).unwrap();
+            fs::write(dir.join(format!("{}.key", stem)), key_pair.serialize_pem()).unwrap();
+            fs::write(dir.join(format!("{}.crt", stem)), cert.pem()).unwrap();
+            cert.der().clone()
+        }
+
+        pub fn connector(root: &Certificate) -> TlsConnector {
+            let mut root_cert_store = RootCertStore::empty();
+            root_cert_store.add(root.clone()).unwrap();
+            TlsConnector::from(Arc::new(
+                ClientConfig::builder()
+                    .with_root_certificates(root_cert_store)
+                    .with_no_client_auth(),
+            ))
+        }
+
+        pub struct Context {
+            _context: crate::helper::Context,
+            _watcher: JoinHandle<()>,
+            pub app: u16,
+            pub dir: PathBuf,
+            pub store: Arc<CertStore>,
+        }
+
+        pub async fn before_each() -> Context {
+            let dir = env::temp_dir().join(format!(
+                "gateway-cert-store-{}",
+                testing_utils::get_random_ports(1)[0]
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            let store = Arc::new(CertStore::from_dir(&dir).unwrap());
+            let watcher = store.watch(dir.clone(), Duration::from_millis(50));
+            let (context, ports) = crate::helper::setup_with_ports(1, |server_builder, ports| {
+                server_builder.with_cert_store(ports[0], store.clone())
+            })
+            .await;
+            Context {
+                _context: context,
+                _watcher: watcher,
+                app: ports[0],
+                dir,
+                store,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
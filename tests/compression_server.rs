@@ -0,0 +1,161 @@
+mod helper;
+
+#[cfg(feature = "compression")]
+mod tests {
+    use essentials::debug;
+    use gateway::{
+        http::{chunked, HeaderMapExt},
+        Request,
+    };
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use std::io::Read;
+    use testing_utils::macros as utils;
+
+    fn request(accept_encoding: Option<&str>) -> Request {
+        let mut request = Request::new("/hello".to_string(), Method::GET);
+        request.insert_header(header::HOST, "app");
+        request.insert_header(header::CONTENT_LENGTH, "0");
+        if let Some(accept_encoding) = accept_encoding {
+            request.insert_header(header::ACCEPT_ENCODING, accept_encoding);
+        }
+        request
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_compress_with_gzip(ctx: Context) {
+        let (response, body) = run_request(request(Some("gzip")), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.header(header::CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(response.header(header::VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(
+            response.header(header::CONTENT_LENGTH).unwrap(),
+            body.len().to_string().as_str()
+        );
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&body[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "Hello, world!");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_prefer_configured_encoding(ctx: Context) {
+        let (response, body) = run_request(request(Some("gzip, br, zstd")), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.header(header::CONTENT_ENCODING).unwrap(), "zstd");
+        assert_eq!(zstd::decode_all(&body[..]).unwrap(), b"Hello, world!");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_not_compress_without_accept_encoding(ctx: Context) {
+        let (response, body) = run_request(request(None), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.header(header::CONTENT_ENCODING).is_none());
+        assert_eq!(response.header(header::VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(body, b"Hello, world!");
+    }
+
+    #[utils::test(setup = before_each_chunked, teardown = after_each)]
+    async fn should_compress_chunked_response(ctx: Context) {
+        let (response, body) = run_request(request(Some("gzip")), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.header(header::CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(
+            response.header(header::TRANSFER_ENCODING).unwrap(),
+            "chunked"
+        );
+        let mut decoder = chunked::Decoder::new();
+        let mut compressed = Vec::new();
+        decoder.decode(&body, &mut compressed).unwrap();
+        assert!(decoder.is_done());
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "Hello, world!");
+    }
+
+    mod helper {
+        use gateway::{compression, tcp, ReadResponse, Request, Response, WriteRequest};
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::{TcpListener, TcpStream},
+        };
+
+        pub use crate::helper::Context;
+
+        pub async fn run_request(request: Request, ctx: &Context) -> (Response, Vec<u8>) {
+            let mut stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app))
+                .await
+                .unwrap();
+            stream.write_request(&request).await.unwrap();
+            stream.flush().await.unwrap();
+            let (response, remains) = stream.read_response().await.unwrap();
+            let mut body = remains.to_vec();
+            stream.read_to_end(&mut body).await.unwrap();
+            (response, body)
+        }
+
+        pub async fn before_each() -> Context {
+            crate::helper::setup(|server_builder| {
+                server_builder.register_middleware(
+                    1,
+                    compression::Builder::new()
+                        .add_app("app", compression::config::App::new().with_min_size(0))
+                        .build(),
+                )
+            })
+            .await
+        }
+
+        /// Origin answering every request with a chunked response.
+        async fn chunked_origin() -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    tokio::spawn(async move {
+                        let mut request = Vec::new();
+                        let mut buf = [0; 1024];
+                        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                            match stream.read(&mut buf).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(read) => request.extend_from_slice(&buf[..read]),
+                            }
+                        }
+                        let _ = stream
+                            .write_all(
+                                b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                                Transfer-Encoding: chunked\r\n\r\n\
+                                7\r\nHello, \r\n6\r\nworld!\r\n0\r\n\r\n",
+                            )
+                            .await;
+                    });
+                }
+            });
+            addr
+        }
+
+        pub async fn before_each_chunked() -> Context {
+            crate::helper::setup_with_origin(
+                tcp::config::Connection::new(chunked_origin().await),
+                |server_builder| {
+                    server_builder.register_middleware(
+                        1,
+                        compression::Builder::new()
+                            .add_app("app", compression::config::App::new().with_min_size(0))
+                            .build(),
+                    )
+                },
+            )
+            .await
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
@@ -0,0 +1,141 @@
+mod helper;
+
+#[cfg(feature = "concurrency-limit")]
+mod tests {
+    use futures::future::join_all;
+    use helper::*;
+    use pretty_assertions::assert_eq;
+    use testing_utils::{
+        macros as utils,
+        surf::{self, StatusCode},
+    };
+
+    async fn get(ctx: &Context, ip: &str) -> StatusCode {
+        surf::get(format!("http://127.0.0.1:{}/slow", &ctx.context.app))
+            .header("X-Real-IP", ip)
+            .header("Host", "app")
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_queue_concurrent_requests_of_client(ctx: Context) {
+        let statuses = join_all([get(&ctx, "1.2.3.4"), get(&ctx, "1.2.3.4")]).await;
+        assert_eq!(statuses, vec![StatusCode::Ok, StatusCode::Ok]);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_reject_requests_over_endpoint_limit(ctx: Context) {
+        let mut statuses = join_all([
+            get(&ctx, "1.2.3.4"),
+            get(&ctx, "1.2.3.5"),
+            get(&ctx, "1.2.3.6"),
+        ])
+        .await;
+        statuses.sort_by_key(|status| *status as u16);
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::Ok,
+                StatusCode::Ok,
+                StatusCode::ServiceUnavailable
+            ]
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_release_redis_permits_no_longer_renewed(ctx: Context) {
+        use gateway::concurrency_limit::datastore::{Datastore, RedisDatastore};
+        use std::time::Duration;
+
+        let datastore =
+            RedisDatastore::new(ctx.redis_pool.clone()).with_lease(Duration::from_millis(300));
+        let permit = datastore.acquire("key", 1).await.unwrap().unwrap();
+        assert!(datastore.acquire("key", 1).await.unwrap().is_none());
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            datastore.renew("key", &permit).await.unwrap();
+        }
+        assert!(datastore.acquire("key", 1).await.unwrap().is_none());
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        let permit = datastore.acquire("key", 1).await.unwrap().unwrap();
+        assert!(datastore.acquire("key", 1).await.unwrap().is_none());
+        datastore.release("key", &permit).await.unwrap();
+        assert!(datastore.acquire("key", 1).await.unwrap().is_some());
+    }
+
+    mod helper {
+        use bb8_redis::{bb8, RedisConnectionManager};
+        use essentials::debug;
+        use gateway::{concurrency_limit, rate_limit, time};
+        use testing_utils::testcontainers::{
+            core::{ContainerPort, WaitFor},
+            runners::AsyncRunner,
+            ContainerAsync, GenericImage,
+        };
+
+        pub struct Context {
+            pub context: crate::helper::Context,
+            pub redis_pool: bb8::Pool<RedisConnectionManager>,
+            _redis_server: ContainerAsync<GenericImage>,
+        }
+
+        pub async fn before_each() -> Context {
+            let redis = GenericImage::new("redis", "7.2.4")
+                .with_exposed_port(ContainerPort::Tcp(6379))
+                .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+                .start()
+                .await
+                .expect("Redis could not be started");
+            let redis_port = redis.get_host_port_ipv4(6379).await.unwrap();
+            let redis_manager =
+                RedisConnectionManager::new(format!("redis://127.0.0.1:{redis_port}")).unwrap();
+            let redis_pool = bb8::Pool::builder().build(redis_manager).await.unwrap();
+            debug!("{:?}", redis_pool);
+            let context = crate::helper::setup(|server_builder| {
+                server_builder.register_middleware(
+                    1,
+                    concurrency_limit::Builder::new()
+                        .add_app(
+                            "app",
+                            concurrency_limit::config::Limits::default(),
+                            concurrency_limit::EndpointBuilder::new().add_endpoint(
+                                "slow",
+                                concurrency_limit::config::Limits::new(vec![
+                                    concurrency_limit::config::Limit::new(
+                                        1,
+                                        concurrency_limit::config::Scope::Key(
+                                            rate_limit::config::Key::Ip,
+                                        ),
+                                    )
+                                    .with_queue(
+                                        1,
+                                        time::Time {
+                                            amount: 5,
+                                            unit: time::TimeUnit::Seconds,
+                                        },
+                                    ),
+                                    concurrency_limit::config::Limit::new(
+                                        2,
+                                        concurrency_limit::config::Scope::Endpoint,
+                                    ),
+                                ]),
+                            ),
+                        )
+                        .build(concurrency_limit::datastore::RedisDatastore::new(
+                            redis_pool.clone(),
+                        )),
+                )
+            })
+            .await;
+            Context {
+                context,
+                redis_pool,
+                _redis_server: redis,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
@@ -0,0 +1,180 @@
+mod helper;
+
+#[cfg(feature = "http2")]
+mod tests {
+    use helper::*;
+    use http::StatusCode;
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_forward_message_and_trailers(ctx: Context) {
+        let response = run_call(&ctx, "/helloworld.Greeter/SayHello", b"\0\0\0\0\x05hello").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body, b"\0\0\0\0\x05hello");
+        assert_eq!(response.trailers.get("grpc-status").unwrap(), "0");
+        assert_eq!(response.trailers.get("x-echo").unwrap(), "true");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_translate_gateway_errors(ctx: Context) {
+        let response = run_call(&ctx, "/helloworld.Greeter/SayGoodbye", b"").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.headers.get("grpc-status").unwrap(), "7");
+        assert_eq!(
+            response.headers.get("content-type").unwrap(),
+            "application/grpc"
+        );
+    }
+
+    mod helper {
+        use bytes::Bytes;
+        use gateway::{tcp, ParamRouterBuilder};
+        use http::{HeaderMap, StatusCode};
+        use rcgen::{generate_simple_self_signed, CertifiedKey};
+        use std::sync::Arc;
+        use tokio::net::{TcpListener, TcpStream};
+        use tokio_rustls::{
+            rustls::{
+                pki_types::{PrivateKeyDer, ServerName},
+                ClientConfig, RootCertStore, ServerConfig,
+            },
+            TlsAcceptor, TlsConnector,
+        };
+
+        pub struct GrpcResponse {
+            pub status: StatusCode,
+            pub headers: HeaderMap,
+            pub body: Vec<u8>,
+            pub trailers: HeaderMap,
+        }
+
+        pub async fn run_call(ctx: &Context, path: &str, message: &'static [u8]) -> GrpcResponse {
+            let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app))
+                .await
+                .unwrap();
+            let domain = ServerName::try_from(ctx.domain.as_str())
+                .unwrap()
+                .to_owned();
+            let stream = ctx.connector.connect(domain, stream).await.unwrap();
+            let (client, connection) = h2::client::handshake(stream).await.unwrap();
+            tokio::spawn(connection);
+            let mut client = client.ready().await.unwrap();
+            let request = http::Request::builder()
+                .method("POST")
+                .uri(format!("https://app{}", path))
+                .header("content-type", "application/grpc")
+                .header("te", "trailers")
+                .body(())
+                .unwrap();
+            let (response, mut send) = client.send_request(request, false).unwrap();
+            send.send_data(Bytes::from_static(message), true).unwrap();
+            let response = response.await.unwrap();
+            let status = response.status();
+            let headers = response.headers().clone();
+            let mut body = response.into_body();
+            let mut data = Vec::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.unwrap();
+                body.flow_control().release_capacity(chunk.len()).unwrap();
+                data.extend_from_slice(&chunk);
+            }
+            let trailers = body.trailers().await.unwrap().unwrap_or_default();
+            GrpcResponse {
+                status,
+                headers,
+                body: data,
+                trailers,
+            }
+        }
+
+        /// A minimal h2c origin echoing every message back with `grpc-status: 0`.
+        async fn start_origin() -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(async move {
+                        let mut connection = h2::server::handshake(stream).await.unwrap();
+                        while let Some(Ok((request, mut respond))) = connection.accept().await {
+                            tokio::spawn(async move {
+                                let mut body = request.into_body();
+                                let mut data = Vec::new();
+                                while let Some(chunk) = body.data().await {
+                                    let chunk = chunk.unwrap();
+                                    body.flow_control().release_capacity(chunk.len()).unwrap();
+                                    data.extend_from_slice(&chunk);
+                                }
+                                let response = http::Response::builder()
+                                    .header("content-type", "application/grpc")
+                                    .body(())
+                                    .unwrap();
+                                let mut send = respond.send_response(response, false).unwrap();
+                                send.send_data(data.into(), false).unwrap();
+                                let mut trailers = HeaderMap::new();
+                                trailers.insert("grpc-status", "0".parse().unwrap());
+                                trailers.insert("x-echo", "true".parse().unwrap());
+                                send.send_trailers(trailers).unwrap();
+                            });
+                        }
+                    });
+                }
+            });
+            addr
+        }
+
+        pub struct Context {
+            _context: crate::helper::Context,
+            pub app: u16,
+            pub domain: String,
+            connector: TlsConnector,
+        }
+
+        pub async fn before_each() -> Context {
+            let domain = "hello.world.example".to_string();
+            let CertifiedKey { cert, key_pair } =
+                generate_simple_self_signed(vec![domain.clone()]).unwrap();
+            let mut root_cert_store = RootCertStore::empty();
+            root_cert_store.add(cert.der().clone()).unwrap();
+            let mut config = ClientConfig::builder()
+                .with_root_certificates(root_cert_store)
+                .with_no_client_auth();
+            config.alpn_protocols = vec![b"h2".to_vec()];
+            let connector = TlsConnector::from(Arc::new(config));
+            let origin = tcp::config::Connection::new(start_origin().await).with_http2();
+            let (context, ports) =
+                crate::helper::setup_with_origin_and_ports(origin, 1, |server_builder, ports| {
+                    server_builder
+                        .register_peer(
+                            "app".to_string(),
+                            ParamRouterBuilder::new().add_grpc_method(
+                                "helloworld.Greeter",
+                                "SayHello",
+                                "hello".to_string(),
+                            ),
+                        )
+                        .with_tls(
+                            ports[0],
+                            TlsAcceptor::from(Arc::new(
+                                ServerConfig::builder()
+                                    .with_no_client_auth()
+                                    .with_single_cert(
+                                        vec![cert.der().clone()],
+                                        PrivateKeyDer::try_from(key_pair.serialize_der()).unwrap(),
+                                    )
+                                    .unwrap(),
+                            )),
+                        )
+                })
+                .await;
+            Context {
+                _context: context,
+                app: ports[0],
+                domain,
+                connector,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
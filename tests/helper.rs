@@ -23,7 +23,8 @@ pub async fn setup_with_ports(
 ) -> (Context, Vec<u16>) {
     setup_system();
     let (mock_server, mock_addr) = create_origin_server().await;
-    let (server, server_ports, custom_ports) = create_server(mock_addr, ports, modify).await;
+    let (server, server_ports, custom_ports) =
+        create_server(tcp::config::Connection::new(mock_addr), ports, modify).await;
     let server_thread = tokio::spawn(server.run());
     wait_for_server(server_ports.1).await;
     (
@@ -36,6 +37,75 @@ pub async fn setup_with_ports(
     )
 }
 
+#[allow(dead_code)]
+pub async fn setup_with_origin(
+    origin: tcp::config::Connection,
+    modify: impl FnOnce(gateway::ServerBuilder) -> gateway::ServerBuilder,
+) -> Context {
+    setup_with_origin_and_ports(origin, 0, |builder, _| modify(builder))
+        .await
+        .0
+}
+
+#[allow(dead_code)]
+pub async fn setup_with_origin_and_ports(
+    origin: tcp::config::Connection,
+    ports: u16,
+    modify: impl FnOnce(gateway::ServerBuilder, &[u16]) -> gateway::ServerBuilder,
+) -> (Context, Vec<u16>) {
+    setup_system();
+    let (mock_server, _) = create_origin_server().await;
+    let (server, server_ports, custom_ports) = create_server(origin, ports, modify).await;
+    let server_thread = tokio::spawn(server.run());
+    wait_for_server(server_ports.1).await;
+    (
+        Context {
+            app: server_ports.0,
+            _app_server: server_thread,
+            origin_server: mock_server,
+        },
+        custom_ports,
+    )
+}
+
+/// Run a server with a custom origin and routers.
+#[allow(dead_code)]
+pub async fn setup_with_builder(server_builder: gateway::ServerBuilder) -> Context {
+    setup_system();
+    let (mock_server, _) = create_origin_server().await;
+    let ports = testing_utils::get_random_ports(2);
+    let server = server_builder
+        .with_app_port(ports[0])
+        .with_health_check_port(ports[1])
+        .build()
+        .await
+        .unwrap();
+    let server_thread = tokio::spawn(server.run());
+    wait_for_server(ports[1]).await;
+    Context {
+        app: ports[0],
+        _app_server: server_thread,
+        origin_server: mock_server,
+    }
+}
+
+/// Serve the app port over plain HTTP also when the TLS feature is enabled,
+/// where it redirects to HTTPS by default.
+#[cfg(feature = "tls")]
+#[allow(dead_code)]
+pub fn serve_plain_http(server_builder: gateway::ServerBuilder) -> gateway::ServerBuilder {
+    server_builder.with_redirect(
+        gateway::RedirectConfig::new()
+            .with_default(gateway::RedirectPolicy::new(gateway::Redirect::Serve)),
+    )
+}
+
+#[cfg(not(feature = "tls"))]
+#[allow(dead_code)]
+pub fn serve_plain_http(server_builder: gateway::ServerBuilder) -> gateway::ServerBuilder {
+    server_builder
+}
+
 #[macro_export]
 macro_rules! assert_req_count {
     ($ctx:expr,$count:expr) => {
@@ -68,6 +138,14 @@ impl Respond for RespondWithEmailHeader {
     }
 }
 
+struct RespondWithBody;
+
+impl Respond for RespondWithBody {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_bytes(request.body.clone())
+    }
+}
+
 fn setup_system() {
     if env::var("CI").is_err() {
         env::set_var("APP_ENV", "d");
@@ -86,7 +164,8 @@ async fn create_origin_server() -> (MockServer, String) {
         .respond_with(
             ResponseTemplate::new(200)
                 .set_body_string("Hello, world!")
-                .append_header("X-Custom", "unique"),
+                .append_header("X-Custom", "unique")
+                .append_header("Surrogate-Key", "greeting"),
         )
         .mount(&server)
         .await;
@@ -105,20 +184,32 @@ async fn create_origin_server() -> (MockServer, String) {
         .respond_with(RespondWithEmailHeader)
         .mount(&server)
         .await;
+    Mock::given(method("GET"))
+        .and(path("/slow"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("Hello, world!")
+                .set_delay(std::time::Duration::from_millis(500)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/echo"))
+        .respond_with(RespondWithBody)
+        .mount(&server)
+        .await;
     (server, mock_addr.to_string())
 }
 
 async fn create_server(
-    origin_addr: String,
+    origin: tcp::config::Connection,
     ports: u16,
     modify: impl FnOnce(gateway::ServerBuilder, &[u16]) -> gateway::ServerBuilder,
 ) -> (gateway::Server, (u16, u16), Vec<u16>) {
     let ports = testing_utils::get_random_ports(2 + ports);
     let custom_ports = ports[2..].to_vec();
     let server_builder = gateway::builder(
-        tcp::Builder::new()
-            .add_peer("app", tcp::config::Connection::new(origin_addr))
-            .build(),
+        tcp::Builder::new().add_peer("app", origin).build(),
         |request| {
             request
                 .header(header::HOST)
@@ -132,9 +223,16 @@ async fn create_server(
         "app".to_string(),
         ParamRouterBuilder::new()
             .add_route(Method::GET, "/hello".to_string(), "hello".to_string())
+            .add_route(
+                Method::from_bytes(b"PURGE").unwrap(),
+                "/hello".to_string(),
+                "hello".to_string(),
+            )
             .add_route(Method::GET, "/email".to_string(), "email".to_string())
             .add_route(Method::GET, "/secret".to_string(), "secret".to_string())
-            .add_route(Method::GET, "/private".to_string(), "private".to_string()),
+            .add_route(Method::GET, "/private".to_string(), "private".to_string())
+            .add_route(Method::POST, "/echo".to_string(), "echo".to_string())
+            .add_route(Method::GET, "/slow".to_string(), "slow".to_string()),
     );
     (
         modify(server_builder, &custom_ports).build().await.unwrap(),
@@ -0,0 +1,117 @@
+mod helper;
+
+#[cfg(feature = "http2")]
+mod tests {
+    use helper::*;
+    use http::{Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_negotiate_http2(ctx: Context) {
+        let (status, body) = run_request(&ctx, Method::GET, "/hello").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Hello, world!");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_run_pipeline_for_every_stream(ctx: Context) {
+        let (status, _) = run_request(&ctx, Method::GET, "/unknown/endpoint").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = run_request(&ctx, Method::GET, "/hello").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Hello, world!");
+    }
+
+    mod helper {
+        use bytes::Bytes;
+        use http::{Method, StatusCode};
+        use rcgen::{generate_simple_self_signed, CertifiedKey};
+        use std::sync::Arc;
+        use tokio::net::TcpStream;
+        use tokio_rustls::{
+            rustls::{
+                pki_types::{PrivateKeyDer, ServerName},
+                ClientConfig, RootCertStore, ServerConfig,
+            },
+            TlsAcceptor, TlsConnector,
+        };
+
+        pub async fn run_request(
+            ctx: &Context,
+            method: Method,
+            path: &str,
+        ) -> (StatusCode, String) {
+            let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app))
+                .await
+                .unwrap();
+            let domain = ServerName::try_from(ctx.domain.as_str())
+                .unwrap()
+                .to_owned();
+            let stream = ctx.connector.connect(domain, stream).await.unwrap();
+            assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+            let (client, connection) = h2::client::handshake(stream).await.unwrap();
+            tokio::spawn(connection);
+            let mut client = client.ready().await.unwrap();
+            let request = http::Request::builder()
+                .method(method)
+                .uri(format!("https://app{}", path))
+                .body(())
+                .unwrap();
+            let (response, _) = client.send_request(request, true).unwrap();
+            let response = response.await.unwrap();
+            let status = response.status();
+            let mut body = response.into_body();
+            let mut data = Vec::new();
+            while let Some(chunk) = body.data().await {
+                let chunk: Bytes = chunk.unwrap();
+                body.flow_control().release_capacity(chunk.len()).unwrap();
+                data.extend_from_slice(&chunk);
+            }
+            (status, String::from_utf8(data).unwrap())
+        }
+
+        pub struct Context {
+            _context: crate::helper::Context,
+            pub app: u16,
+            pub domain: String,
+            connector: TlsConnector,
+        }
+
+        pub async fn before_each() -> Context {
+            let domain = "hello.world.example".to_string();
+            let CertifiedKey { cert, key_pair } =
+                generate_simple_self_signed(vec![domain.clone()]).unwrap();
+            let mut root_cert_store = RootCertStore::empty();
+            root_cert_store.add(cert.der().clone()).unwrap();
+            let mut config = ClientConfig::builder()
+                .with_root_certificates(root_cert_store)
+                .with_no_client_auth();
+            config.alpn_protocols = vec![b"h2".to_vec()];
+            let connector = TlsConnector::from(Arc::new(config));
+            let (context, ports) = crate::helper::setup_with_ports(1, |server_builder, ports| {
+                server_builder.with_tls(
+                    ports[0],
+                    TlsAcceptor::from(Arc::new(
+                        ServerConfig::builder()
+                            .with_no_client_auth()
+                            .with_single_cert(
+                                vec![cert.der().clone()],
+                                PrivateKeyDer::try_from(key_pair.serialize_der()).unwrap(),
+                            )
+                            .unwrap(),
+                    )),
+                )
+            })
+            .await;
+            Context {
+                _context: context,
+                app: ports[0],
+                domain,
+                connector,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
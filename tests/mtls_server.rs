@@ -0,0 +1,193 @@
+mod helper;
+
+#[cfg(all(feature = "auth", feature = "tls"))]
+mod tests {
+    use essentials::debug;
+    use gateway::{http::HeaderMapExt, Request};
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+
+    fn request() -> Request {
+        let mut request = Request::new("/hello".to_string(), Method::GET);
+        request.insert_header(header::HOST, "app");
+        request.insert_header(header::CONTENT_LENGTH, "0");
+        request
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_forward_client_identity(ctx: Context) {
+        let response = run_request(request(), &ctx, Some(&ctx.allowed))
+            .await
+            .unwrap();
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        let requests = ctx.context.origin_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let headers = &requests[0].headers;
+        assert_eq!(headers.get("x-client-subject").unwrap(), "CN=client");
+        assert_eq!(headers.get("x-client-san").unwrap(), "client.example.com");
+        assert_eq!(headers.get("x-client-fingerprint").unwrap().len(), 64);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_return_403_when_identity_is_not_allowed(ctx: Context) {
+        let response = run_request(request(), &ctx, Some(&ctx.denied))
+            .await
+            .unwrap();
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_reject_client_without_certificate(ctx: Context) {
+        let response = run_request(request(), &ctx, None).await;
+        debug!("{:?}", response);
+        assert!(response.is_err());
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_return_421_when_host_does_not_match_server_name(ctx: Context) {
+        let response = run_request_to(request(), &ctx, Some(&ctx.allowed), OTHER_DOMAIN)
+            .await
+            .unwrap();
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::MISDIRECTED_REQUEST);
+        let requests = ctx.context.origin_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 0);
+    }
+
+    mod helper {
+        use gateway::{
+            auth::mtls::{self, config::Identity},
+            ClientAuth, ClientCa, ReadResponse, Request, Response, WriteRequest,
+        };
+        use rcgen::{
+            BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose,
+            IsCa, KeyPair,
+        };
+        use std::{io, sync::Arc};
+        use tokio::{io::AsyncWriteExt, net::TcpStream};
+        use tokio_rustls::{
+            rustls::{
+                pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+                ClientConfig, RootCertStore, ServerConfig,
+            },
+            TlsAcceptor, TlsConnector,
+        };
+
+        const DOMAIN: &str = "app";
+        pub const OTHER_DOMAIN: &str = "hello.world.example";
+
+        pub struct ClientIdentity {
+            cert: CertificateDer<'static>,
+            key: Vec<u8>,
+        }
+
+        pub async fn run_request(
+            request: Request,
+            ctx: &Context,
+            identity: Option<&ClientIdentity>,
+        ) -> io::Result<Response> {
+            run_request_to(request, ctx, identity, DOMAIN).await
+        }
+
+        pub async fn run_request_to(
+            request: Request,
+            ctx: &Context,
+            identity: Option<&ClientIdentity>,
+            domain: &'static str,
+        ) -> io::Result<Response> {
+            let config = ClientConfig::builder().with_root_certificates(ctx.roots.clone());
+            let config = match identity {
+                Some(identity) => config
+                    .with_client_auth_cert(
+                        vec![identity.cert.clone()],
+                        PrivateKeyDer::try_from(identity.key.clone()).unwrap(),
+                    )
+                    .unwrap(),
+                None => config.with_no_client_auth(),
+            };
+            let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app)).await?;
+            let mut stream = TlsConnector::from(Arc::new(config))
+                .connect(ServerName::try_from(domain).unwrap(), stream)
+                .await?;
+            stream.write_request(&request).await?;
+            stream.flush().await?;
+            let (response, _) = stream.read_response().await?;
+            Ok(response)
+        }
+
+        pub struct Context {
+            pub context: crate::helper::Context,
+            pub app: u16,
+            pub allowed: ClientIdentity,
+            pub denied: ClientIdentity,
+            roots: RootCertStore,
+        }
+
+        fn client(name: &str, ca: &Certificate, ca_key: &KeyPair) -> ClientIdentity {
+            let mut params = CertificateParams::new(vec![format!("{}.example.com", name)]).unwrap();
+            params.distinguished_name.push(DnType::CommonName, name);
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+            let key = KeyPair::generate().unwrap();
+            let cert = params.signed_by(&key, ca, ca_key).unwrap();
+            ClientIdentity {
+                cert: cert.der().clone(),
+                key: key.serialize_der(),
+            }
+        }
+
+        pub async fn before_each() -> Context {
+            let mut ca_params = CertificateParams::new(vec![]).unwrap();
+            ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            ca_params.distinguished_name.push(DnType::CommonName, "ca");
+            let ca_key = KeyPair::generate().unwrap();
+            let ca = ca_params.self_signed(&ca_key).unwrap();
+            let mut client_roots = RootCertStore::empty();
+            client_roots.add(ca.der().clone()).unwrap();
+
+            let server_key = KeyPair::generate().unwrap();
+            let server = CertificateParams::new(vec![DOMAIN.to_string(), OTHER_DOMAIN.to_string()])
+                .unwrap()
+                .self_signed(&server_key)
+                .unwrap();
+            let mut roots = RootCertStore::empty();
+            roots.add(server.der().clone()).unwrap();
+
+            let (context, ports) = crate::helper::setup_with_ports(1, |server_builder, ports| {
+                server_builder
+                    .with_tls(
+                        ports[0],
+                        TlsAcceptor::from(Arc::new(
+                            ServerConfig::builder()
+                                .with_no_client_auth()
+                                .with_single_cert(
+                                    vec![server.der().clone()],
+                                    PrivateKeyDer::try_from(server_key.serialize_der()).unwrap(),
+                                )
+                                .unwrap(),
+                        )),
+                    )
+                    .with_client_auth(ClientAuth::new().with_default(ClientCa::new(client_roots)))
+                    .register_middleware(
+                        1,
+                        mtls::Builder::new()
+                            .add_app_identity("app", Identity::CommonName("client".to_string()))
+                            .build(),
+                    )
+            })
+            .await;
+            Context {
+                context,
+                app: ports[0],
+                allowed: client("client", &ca, &ca_key),
+                denied: client("other", &ca, &ca_key),
+                roots,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
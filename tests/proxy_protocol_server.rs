@@ -0,0 +1,70 @@
+mod helper;
+
+#[cfg(not(feature = "tls"))]
+mod tests {
+    use gateway::{
+        http::HeaderMapExt,
+        io::proxy::{ProxyHeader, Version, WriteProxyHeader},
+        ReadResponse, Request, WriteRequest,
+    };
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+    use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+    async fn run_request(ctx: &Context, version: Version) -> StatusCode {
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", ctx.app))
+            .await
+            .unwrap();
+        stream
+            .write_proxy_header(
+                &ProxyHeader::new(
+                    "203.0.113.7:51234".parse().unwrap(),
+                    "192.0.2.1:80".parse().unwrap(),
+                ),
+                version,
+            )
+            .await
+            .unwrap();
+        let mut request = Request::new("/hello".to_string(), Method::GET);
+        request.insert_header(header::HOST, "app");
+        request.insert_header(header::CONTENT_LENGTH, "0");
+        stream.write_request(&request).await.unwrap();
+        stream.flush().await.unwrap();
+        let (response, _) = stream.read_response().await.unwrap();
+        response.status
+    }
+
+    async fn assert_forwarded_for(ctx: &Context) {
+        let requests = ctx.origin_server.received_requests().await.unwrap();
+        let request = requests.last().unwrap();
+        assert_eq!(request.headers.get("X-Real-IP").unwrap(), "203.0.113.7");
+        assert_eq!(
+            request.headers.get("X-Forwarded-For").unwrap(),
+            "203.0.113.7"
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_forward_client_address_from_v1_header(ctx: Context) {
+        assert_eq!(run_request(&ctx, Version::V1).await, StatusCode::OK);
+        assert_forwarded_for(&ctx).await;
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_forward_client_address_from_v2_header(ctx: Context) {
+        assert_eq!(run_request(&ctx, Version::V2).await, StatusCode::OK);
+        assert_forwarded_for(&ctx).await;
+    }
+
+    mod helper {
+        pub use crate::helper::Context;
+
+        pub async fn before_each() -> Context {
+            crate::helper::setup(|server_builder| server_builder.with_proxy_protocol(true)).await
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
@@ -103,6 +103,80 @@ mod tests {
         }
     }
 
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_limit_anonymous_requests(ctx: Context) {
+        for (ip, status) in [
+            ("1.2.3.4", StatusCode::Ok),
+            ("1.2.3.4", StatusCode::TooManyRequests),
+            ("1.2.3.5", StatusCode::Ok),
+            ("1.2.3.6", StatusCode::Ok),
+            ("1.2.3.7", StatusCode::TooManyRequests),
+        ] {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", &ctx.context.app))
+                .header("X-Real-IP", ip)
+                .header("Host", "app")
+                .await;
+            info!("{:?}", response);
+            let response = response.unwrap();
+            assert_eq!(response.status(), status, "{}", ip);
+            if status == StatusCode::Ok {
+                assert_eq!(response.header("RateLimit-Limit").unwrap(), "1");
+                assert_eq!(response.header("RateLimit-Remaining").unwrap(), "0");
+            }
+        }
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_reject_unknown_token_when_anonymous_quota_is_set(ctx: Context) {
+        for i in 0..3 {
+            let response = surf::get(format!("http://127.0.0.1:{}/hello", &ctx.context.app))
+                .header("X-Real-IP", "1.2.3.4")
+                .header("X-Api-Token", format!("junk-{}", i))
+                .header("Host", "app")
+                .await;
+            info!("{:?}", response);
+            let response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::Unauthorized);
+        }
+        let requests = ctx.context.origin_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 0);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_fail_after_2_requests_with_each_algorithm(ctx: Context) {
+        for path in ["/email", "/secret", "/private", "/echo"] {
+            let url = format!("http://127.0.0.1:{}{}", &ctx.context.app, path);
+            for remaining in ["1", "0"] {
+                let request = match path {
+                    "/echo" => surf::post(&url),
+                    _ => surf::get(&url),
+                };
+                let response = request
+                    .header("X-Real-IP", "1.2.3.4")
+                    .header("X-Api-Token", "token")
+                    .header("Host", "app")
+                    .await;
+                info!("{:?}", response);
+                let response = response.unwrap();
+                assert_eq!(response.status(), StatusCode::Ok, "{}", path);
+                assert_eq!(response.header("RateLimit-Limit").unwrap(), "2");
+                assert_eq!(response.header("RateLimit-Remaining").unwrap(), remaining);
+            }
+            let request = match path {
+                "/echo" => surf::post(&url),
+                _ => surf::get(&url),
+            };
+            let response = request
+                .header("X-Real-IP", "1.2.3.4")
+                .header("X-Api-Token", "token")
+                .header("Host", "app")
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::TooManyRequests, "{}", path);
+            assert!(response.header(header::RETRY_AFTER.as_str()).is_some());
+        }
+    }
+
     mod helper {
         use std::collections::HashMap;
 
@@ -120,6 +194,33 @@ mod tests {
             _redis_server: ContainerAsync<GenericImage>,
         }
 
+        /// Allow the requests per minute in total and from each user counted by the algorithm.
+        fn quota(
+            total: usize,
+            user: usize,
+            algorithm: rate_limit::config::Algorithm,
+        ) -> rate_limit::config::Quota {
+            let per_minute = |amount| time::Frequency {
+                amount,
+                interval: time::Time {
+                    amount: 1,
+                    unit: time::TimeUnit::Minutes,
+                },
+            };
+            rate_limit::config::Quota {
+                total: per_minute(total),
+                user: Some(per_minute(user)),
+                algorithm,
+            }
+        }
+
+        fn rules(algorithm: rate_limit::config::Algorithm) -> rate_limit::config::Rules {
+            rate_limit::config::Rules::new(
+                Some(quota(5, 2, algorithm)),
+                HashMap::from([("token".to_string(), quota(5, 2, algorithm))]),
+            )
+        }
+
         pub async fn before_each() -> Context {
             let redis = GenericImage::new("redis", "7.2.4")
                 .with_exposed_port(ContainerPort::Tcp(6379))
@@ -138,26 +239,26 @@ mod tests {
                     rate_limit::Builder::new()
                         .add_app(
                             "app",
-                            rate_limit::config::Rules {
-                                root: Some(rate_limit::config::Quota {
-                                    total: time::Frequency {
-                                        amount: 5,
-                                        interval: time::Time {
-                                            amount: 1,
-                                            unit: time::TimeUnit::Minutes,
-                                        },
-                                    },
-                                    user: Some(time::Frequency {
-                                        amount: 2,
-                                        interval: time::Time {
-                                            amount: 1,
-                                            unit: time::TimeUnit::Minutes,
-                                        },
-                                    }),
-                                }),
-                                tokens: HashMap::new(),
-                            },
-                            rate_limit::EndpointBuilder::new(),
+                            rules(rate_limit::config::Algorithm::FixedWindow).with_anonymous(
+                                quota(3, 1, rate_limit::config::Algorithm::FixedWindow),
+                            ),
+                            rate_limit::EndpointBuilder::new()
+                                .add_endpoint(
+                                    "email",
+                                    rules(rate_limit::config::Algorithm::SlidingWindowLog),
+                                )
+                                .add_endpoint(
+                                    "secret",
+                                    rules(rate_limit::config::Algorithm::SlidingWindowCounter),
+                                )
+                                .add_endpoint(
+                                    "private",
+                                    rules(rate_limit::config::Algorithm::TokenBucket { burst: 2 }),
+                                )
+                                .add_endpoint(
+                                    "echo",
+                                    rules(rate_limit::config::Algorithm::Gcra { burst: 2 }),
+                                ),
                         )
                         .build(rate_limit::datastore::RedisDatastore::new(redis_pool)),
                 )
@@ -0,0 +1,164 @@
+mod helper;
+
+#[cfg(feature = "tls")]
+mod tests {
+    use essentials::debug;
+    use gateway::{http::HeaderMapExt, Request};
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+
+    fn request(path: &str, host: &str) -> Request {
+        let mut request = Request::new(path.to_string(), Method::GET);
+        request.insert_header(header::HOST, host);
+        request.insert_header(header::CONTENT_LENGTH, "0");
+        request
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_exempt_path(ctx: Context) {
+        let response = run_http_request(request("/hello", "app"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_redirect_app_with_308(ctx: Context) {
+        let response = run_http_request(request("/secret?a=b", "app"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.header(header::LOCATION).unwrap(),
+            format!("https://app:{}/secret?a=b", ctx.tls).as_str()
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_redirect_unknown_app_with_301(ctx: Context) {
+        let response = run_http_request(request("/hello", "other"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.header(header::LOCATION).unwrap(),
+            "https://other/hello"
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_send_hsts_over_tls(ctx: Context) {
+        let response = run_https_request(request("/hello", "app"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(
+            response.header(header::STRICT_TRANSPORT_SECURITY).unwrap(),
+            "max-age=86400; includeSubDomains"
+        );
+    }
+
+    mod helper {
+        use gateway::{
+            time::{Time, TimeUnit},
+            Hsts, ReadResponse, Redirect, RedirectConfig, RedirectPolicy, Request, Response,
+            WriteRequest,
+        };
+        use rcgen::{generate_simple_self_signed, CertifiedKey};
+        use std::sync::Arc;
+        use tokio::{
+            io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+            net::TcpStream,
+        };
+        use tokio_rustls::{
+            rustls::{
+                pki_types::{PrivateKeyDer, ServerName},
+                ClientConfig, RootCertStore, ServerConfig,
+            },
+            TlsAcceptor, TlsConnector,
+        };
+
+        async fn send<S>(mut stream: S, request: Request) -> Response
+        where
+            S: AsyncRead + AsyncWrite + Unpin + Send,
+        {
+            stream.write_request(&request).await.unwrap();
+            stream.flush().await.unwrap();
+            stream.read_response().await.unwrap().0
+        }
+
+        pub async fn run_http_request(request: Request, ctx: &Context) -> Response {
+            let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.context.app))
+                .await
+                .unwrap();
+            send(stream, request).await
+        }
+
+        pub async fn run_https_request(request: Request, ctx: &Context) -> Response {
+            let stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.tls))
+                .await
+                .unwrap();
+            let stream = ctx
+                .connector
+                .connect(ServerName::try_from("app").unwrap(), stream)
+                .await
+                .unwrap();
+            send(stream, request).await
+        }
+
+        pub struct Context {
+            pub context: crate::helper::Context,
+            pub tls: u16,
+            connector: TlsConnector,
+        }
+
+        pub async fn before_each() -> Context {
+            let CertifiedKey { cert, key_pair } =
+                generate_simple_self_signed(vec!["app".to_string()]).unwrap();
+            let mut roots = RootCertStore::empty();
+            roots.add(cert.der().clone()).unwrap();
+            let connector = TlsConnector::from(Arc::new(
+                ClientConfig::builder()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            ));
+            let (context, ports) = crate::helper::setup_with_ports(1, |server_builder, ports| {
+                server_builder
+                    .with_tls(
+                        ports[0],
+                        TlsAcceptor::from(Arc::new(
+                            ServerConfig::builder()
+                                .with_no_client_auth()
+                                .with_single_cert(
+                                    vec![cert.der().clone()],
+                                    PrivateKeyDer::try_from(key_pair.serialize_der()).unwrap(),
+                                )
+                                .unwrap(),
+                        )),
+                    )
+                    .with_redirect(
+                        RedirectConfig::new()
+                            .add_app(
+                                "app",
+                                RedirectPolicy::new(Redirect::PermanentRedirect)
+                                    .with_target_port(ports[0])
+                                    .with_hsts(
+                                        Hsts::new(Time {
+                                            amount: 1,
+                                            unit: TimeUnit::Days,
+                                        })
+                                        .include_subdomains(),
+                                    ),
+                            )
+                            .add_exempt_path("/hello"),
+                    )
+            })
+            .await;
+            Context {
+                context,
+                tls: ports[0],
+                connector,
+            }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
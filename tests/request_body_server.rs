@@ -0,0 +1,151 @@
+mod helper;
+
+mod tests {
+    use essentials::debug;
+    use gateway::{http::HeaderMapExt, Request};
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+
+    fn request() -> Request {
+        let mut request = Request::new("/echo".to_string(), Method::POST);
+        request.insert_header(header::HOST, "app");
+        request
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_forward_buffered_body(ctx: Context) {
+        let mut request = request();
+        request.insert_header(header::CONTENT_LENGTH, "13");
+        let (response, body) = run_request(request, b"Hello, world!", &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(body, b"Hello, world!");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_forward_replaced_body(ctx: Context) {
+        let mut request = request();
+        request.insert_header(header::CONTENT_LENGTH, "13");
+        request.insert_header("X-Uppercase", "true");
+        let (response, body) = run_request(request, b"Hello, world!", &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(body, b"HELLO, WORLD! (uppercase)");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_decode_chunked_body(ctx: Context) {
+        let mut request = request();
+        request.insert_header(header::TRANSFER_ENCODING, "chunked");
+        let (response, body) =
+            run_request(request, b"7\r\nHello, \r\n6\r\nworld!\r\n0\r\n\r\n", &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(body, b"Hello, world!");
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_reject_body_over_limit(ctx: Context) {
+        let mut request = request();
+        request.insert_header(header::CONTENT_LENGTH, "40");
+        let (response, _) = run_request(request, &[b'a'; 40], &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            ctx.origin_server
+                .received_requests()
+                .await
+                .unwrap_or_default()
+                .len(),
+            0
+        );
+    }
+
+    mod helper {
+        use async_trait::async_trait;
+        use gateway::{
+            http::{Body, HeaderMapExt},
+            Ctx, Middleware, MiddlewareBuilder, Next, ReadResponse, Request, Response, Result,
+            Service, WriteRequest,
+        };
+        use http::StatusCode;
+        use std::collections::HashMap;
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        };
+
+        pub use crate::helper::Context;
+
+        const LIMIT: usize = 32;
+
+        /// Buffers the request body and replaces it when asked to.
+        struct BodyMiddleware;
+
+        #[async_trait]
+        impl Middleware for BodyMiddleware {
+            async fn run(
+                &self,
+                _ctx: &Ctx,
+                request: Request,
+                mut next: Next<'_>,
+            ) -> Result<Response> {
+                let body = match next.body.collect(&request, LIMIT).await {
+                    Ok(body) => body,
+                    Err(_) => {
+                        return Ok(Response::new(StatusCode::PAYLOAD_TOO_LARGE));
+                    }
+                };
+                if request.header("X-Uppercase").is_some() {
+                    let mut body = body.to_ascii_uppercase();
+                    body.extend_from_slice(b" (uppercase)");
+                    next.body.replace(Body::from_bytes(body));
+                }
+                next.run(request).await
+            }
+        }
+
+        struct BodyMiddlewareBuilder;
+
+        #[async_trait]
+        impl MiddlewareBuilder for BodyMiddlewareBuilder {
+            async fn build(
+                self: Box<Self>,
+                _: &[String],
+                _: &HashMap<String, Vec<String>>,
+            ) -> Result<Service> {
+                Ok(Box::new(BodyMiddleware))
+            }
+        }
+
+        pub async fn run_request(
+            request: Request,
+            body: &[u8],
+            ctx: &Context,
+        ) -> (Response, Vec<u8>) {
+            let mut stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.app))
+                .await
+                .unwrap();
+            stream.write_request(&request).await.unwrap();
+            stream.write_all(body).await.unwrap();
+            stream.flush().await.unwrap();
+            let (response, remains) = stream.read_response().await.unwrap();
+            let mut body = remains.to_vec();
+            stream.read_to_end(&mut body).await.unwrap();
+            (response, body)
+        }
+
+        pub async fn before_each() -> Context {
+            crate::helper::setup(|server_builder| {
+                crate::helper::serve_plain_http(
+                    server_builder.register_middleware(1, BodyMiddlewareBuilder),
+                )
+            })
+            .await
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
@@ -0,0 +1,246 @@
+mod helper;
+
+mod tests {
+    use essentials::debug;
+    use gateway::{http::HeaderMapExt, Request};
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+
+    fn request(path: &str) -> Request {
+        let mut request = Request::new(path.to_string(), Method::GET);
+        request.insert_header(header::HOST, "app");
+        request.insert_header(header::CONTENT_LENGTH, "0");
+        request
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_file_with_content_type(ctx: Context) {
+        let (response, body) = run_request(request("/app.js"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(
+            response.header(header::CONTENT_TYPE).unwrap(),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(response.header(header::ACCEPT_RANGES).unwrap(), "bytes");
+        assert_eq!(body, APP_JS);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_not_serve_files_outside_of_root(ctx: Context) {
+        for path in ["/../secret.txt", "/%2e%2e/secret.txt", "/..%2Fsecret.txt"] {
+            let (response, body) = run_request(request(path), &ctx).await;
+            debug!("{:?}", response);
+            assert_eq!(response.status, StatusCode::NOT_FOUND);
+            assert_eq!(body, "");
+        }
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_index_of_directory(ctx: Context) {
+        let (response, body) = run_request(request("/"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(
+            response.header(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(body, INDEX_HTML);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_fall_back_to_spa_entrypoint(ctx: Context) {
+        let (response, body) = run_request(request("/users/1?tab=profile"), &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(body, INDEX_HTML);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_return_304_when_not_modified(ctx: Context) {
+        let (response, _) = run_request(request("/app.js"), &ctx).await;
+        let etag = response.header(header::ETAG).unwrap().clone();
+        let last_modified = response.header(header::LAST_MODIFIED).unwrap().clone();
+
+        let mut conditional = request("/app.js");
+        conditional.insert_header(header::IF_NONE_MATCH, etag);
+        let (response, body) = run_request(conditional, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+        assert_eq!(body, "");
+
+        let mut conditional = request("/app.js");
+        conditional.insert_header(header::IF_MODIFIED_SINCE, last_modified);
+        let (response, _) = run_request(conditional, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+
+        let mut conditional = request("/app.js");
+        conditional.insert_header(header::IF_NONE_MATCH, "\"other\"");
+        let (response, body) = run_request(conditional, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(body, APP_JS);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_single_range(ctx: Context) {
+        let mut ranged = request("/app.js");
+        ranged.insert_header(header::RANGE, "bytes=0-4");
+        let (response, body) = run_request(ranged, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.header(header::CONTENT_RANGE).unwrap(),
+            format!("bytes 0-4/{}", APP_JS.len()).as_str()
+        );
+        assert_eq!(body, &APP_JS[..5]);
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_multiple_ranges(ctx: Context) {
+        let mut ranged = request("/app.js");
+        ranged.insert_header(header::RANGE, "bytes=0-4, -3");
+        let (response, body) = run_request(ranged, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+        let content_type = response
+            .header(header::CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        assert_eq!(
+            response.header(header::CONTENT_LENGTH).unwrap(),
+            body.len().to_string().as_str()
+        );
+        let size = APP_JS.len();
+        assert_eq!(
+            body,
+            format!(
+                "\r\n--{b}\r\nContent-Type: text/javascript; charset=utf-8\r\nContent-Range: bytes 0-4/{s}\r\n\r\n{}\r\n--{b}\r\nContent-Type: text/javascript; charset=utf-8\r\nContent-Range: bytes {}-{}/{s}\r\n\r\n{}\r\n--{b}--\r\n",
+                &APP_JS[..5],
+                size - 3,
+                size - 1,
+                &APP_JS[size - 3..],
+                b = boundary,
+                s = size,
+            )
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_return_416_for_unsatisfiable_range(ctx: Context) {
+        let mut ranged = request("/app.js");
+        ranged.insert_header(header::RANGE, "bytes=1000-");
+        let (response, _) = run_request(ranged, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            response.header(header::CONTENT_RANGE).unwrap(),
+            format!("bytes */{}", APP_JS.len()).as_str()
+        );
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_serve_precompressed_sibling(ctx: Context) {
+        let mut compressed = request("/app.js");
+        compressed.insert_header(header::ACCEPT_ENCODING, "gzip, br");
+        let (response, body) = run_request(compressed, &ctx).await;
+        debug!("{:?}", response);
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.header(header::CONTENT_ENCODING).unwrap(), "br");
+        assert_eq!(
+            response.header(header::CONTENT_TYPE).unwrap(),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(response.header(header::VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(body, APP_JS_BR);
+    }
+
+    mod helper {
+        use gateway::{
+            fs, http::HeaderMapExt, AnyRouterBuilder, ReadResponse, Request, Response, WriteRequest,
+        };
+        use http::header;
+        use std::{
+            env,
+            path::PathBuf,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        };
+
+        pub const INDEX_HTML: &str = "<html>index</html>";
+        pub const APP_JS: &str = "console.log('hello world');";
+        pub const APP_JS_BR: &str = "compressed";
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        pub async fn run_request(request: Request, ctx: &Context) -> (Response, String) {
+            let mut stream = TcpStream::connect(&format!("127.0.0.1:{}", ctx.context.app))
+                .await
+                .unwrap();
+            stream.write_request(&request).await.unwrap();
+            stream.flush().await.unwrap();
+            let (response, remains) = stream.read_response().await.unwrap();
+            let mut body = remains.to_vec();
+            stream.read_to_end(&mut body).await.unwrap();
+            (response, String::from_utf8(body).unwrap())
+        }
+
+        pub struct Context {
+            pub context: crate::helper::Context,
+            dir: PathBuf,
+        }
+
+        impl Drop for Context {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.dir);
+            }
+        }
+
+        pub async fn before_each() -> Context {
+            let dir = env::temp_dir().join(format!(
+                "gateway-static-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            let root = dir.join("root");
+            std::fs::create_dir_all(&root).unwrap();
+            std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+            std::fs::write(root.join("index.html"), INDEX_HTML).unwrap();
+            std::fs::write(root.join("app.js"), APP_JS).unwrap();
+            std::fs::write(root.join("app.js.br"), APP_JS_BR).unwrap();
+            let context = crate::helper::setup_with_builder(crate::helper::serve_plain_http(
+                gateway::builder(
+                    fs::Builder::new()
+                        .add_root(
+                            "app",
+                            fs::config::Root::new(&root)
+                                .with_fallback("index.html")
+                                .with_precompressed(),
+                        )
+                        .build(),
+                    |request| {
+                        request
+                            .header(header::HOST)
+                            .and_then(|value| value.to_str().ok())
+                            .map(|x| (x.to_string(), None))
+                    },
+                )
+                .register_peer("app".to_string(), AnyRouterBuilder),
+            ))
+            .await;
+            Context { context, dir }
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}
@@ -0,0 +1,103 @@
+mod helper;
+
+#[cfg(not(feature = "tls"))]
+mod tests {
+    use gateway::{http::HeaderMapExt, ReadResponse, Request, WriteRequest};
+    use helper::*;
+    use http::{header, Method, StatusCode};
+    use pretty_assertions::assert_eq;
+    use testing_utils::macros as utils;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    async fn upgrade(ctx: &Context) -> (StatusCode, TcpStream) {
+        let mut stream = TcpStream::connect(format!("127.0.0.1:{}", ctx.app))
+            .await
+            .unwrap();
+        let mut request = Request::new("/hello".to_string(), Method::GET);
+        request.insert_header(header::HOST, "app");
+        request.insert_header(header::CONNECTION, "Upgrade");
+        request.insert_header(header::UPGRADE, "websocket");
+        stream.write_request(&request).await.unwrap();
+        stream.flush().await.unwrap();
+        let (response, remains) = stream.read_response().await.unwrap();
+        assert_eq!(remains.len(), 0);
+        (response.status, stream)
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_tunnel_upgraded_connection(ctx: Context) {
+        let (status, mut stream) = upgrade(&ctx).await;
+        assert_eq!(status, StatusCode::SWITCHING_PROTOCOLS);
+        for message in ["ping", "pong"] {
+            stream.write_all(message.as_bytes()).await.unwrap();
+            let mut buf = [0_u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, message.as_bytes());
+        }
+    }
+
+    #[utils::test(setup = before_each, teardown = after_each)]
+    async fn should_reject_upgrades_over_limit(ctx: Context) {
+        let (status, _stream) = upgrade(&ctx).await;
+        assert_eq!(status, StatusCode::SWITCHING_PROTOCOLS);
+        let (status, _) = upgrade(&ctx).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    mod helper {
+        pub use crate::helper::Context;
+        use gateway::{tcp, time, ReadRequest};
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt, BufReader},
+            net::TcpListener,
+        };
+
+        /// Origin that accepts every upgrade and echoes whatever it receives.
+        async fn create_echo_origin() -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    tokio::spawn(async move {
+                        let (rx, mut tx) = stream.into_split();
+                        let mut rx = BufReader::new(rx);
+                        rx.read_request().await.unwrap();
+                        tx.write_all(
+                            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+                        )
+                        .await
+                        .unwrap();
+                        let mut buf = [0_u8; 1024];
+                        loop {
+                            match rx.read(&mut buf).await {
+                                Ok(0) | Err(_) => break,
+                                Ok(read) => tx.write_all(&buf[..read]).await.unwrap(),
+                            }
+                        }
+                    });
+                }
+            });
+            addr
+        }
+
+        pub async fn before_each() -> Context {
+            let origin = create_echo_origin().await;
+            crate::helper::setup_with_origin(
+                tcp::config::Connection::new(origin)
+                    .with_idle_timeout(time::Time {
+                        amount: 5,
+                        unit: time::TimeUnit::Seconds,
+                    })
+                    .with_max_upgrades(1),
+                |server_builder| server_builder,
+            )
+            .await
+        }
+
+        pub async fn after_each(_ctx: ()) {}
+    }
+}